@@ -0,0 +1,31 @@
+//! Per-system tick times on headless worlds, run with `cargo bench -p game_plugin --features native,game_plugin/bench`
+
+use std::time::Instant;
+
+use game_plugin::benchmarks::{build_world, time_systems, WorldSize};
+
+const TICKS: u32 = 60;
+const VILLAGERS: usize = 300;
+
+fn main() {
+    for &trees in &[1_000, 10_000, 100_000] {
+        let size = WorldSize {
+            trees,
+            villagers: VILLAGERS,
+        };
+        let started = Instant::now();
+        let mut app = build_world(&size, 1);
+        println!(
+            "{} trees, {} villagers, built in {:.2?}",
+            size.trees,
+            size.villagers,
+            started.elapsed()
+        );
+        for timing in time_systems(&mut app, TICKS) {
+            println!(
+                "    {:<28} mean {:>10.2?}    max {:>10.2?}",
+                timing.system, timing.mean, timing.max
+            );
+        }
+    }
+}
@@ -1,4 +1,7 @@
-use crate::GameState;
+use crate::{
+    camera::{cursor_world_position, MainCamera},
+    GameState,
+};
 use bevy::prelude::*;
 
 pub struct ActionsPlugin;
@@ -6,7 +9,10 @@ pub struct ActionsPlugin;
 impl Plugin for ActionsPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.init_resource::<Actions>().add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(set_movement_actions.system()),
+            SystemSet::on_update(GameState::Playing)
+                .with_system(set_movement_actions.system())
+                .with_system(set_command_actions.system())
+                .with_system(set_designation_actions.system()),
         );
     }
 }
@@ -14,6 +20,92 @@ impl Plugin for ActionsPlugin {
 #[derive(Default)]
 pub struct Actions {
     pub player_movement: Option<Vec2>,
+    /// Drafts villagers to defend the village, or sends the defenders back to work
+    pub toggle_draft: bool,
+    /// Stops or resumes cutting trees
+    pub toggle_woodcutting: bool,
+    /// Opens or closes the statistics panel
+    pub toggle_stats: bool,
+    /// Writes the statistics to a CSV file
+    pub export_stats: bool,
+    /// Shows or hides frame times and entity counts
+    pub toggle_diagnostics: bool,
+    pub designation_tool: Option<DesignationTool>,
+    /// Where the cursor points to in the world, if it is over the window
+    pub cursor_position: Option<Vec2>,
+    /// Where the left mouse button was pressed with a designation tool
+    pub drag_start: Option<Vec2>,
+    /// Corners of the area dragged over, in the frame the mouse button is released
+    pub designated_area: Option<(Vec2, Vec2)>,
+    /// Where the world was clicked without a designation tool, in the frame of the click
+    pub inspected_position: Option<Vec2>,
+}
+
+/// What a click or a drag over the world does, see `PlayerCommandsPlugin`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum DesignationTool {
+    Fell,
+    StockpileZone,
+    HouseBlueprint,
+    Cancel,
+}
+
+fn set_command_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<KeyCode>>) {
+    actions.toggle_draft = keyboard_input.just_pressed(KeyCode::F);
+    actions.toggle_woodcutting = keyboard_input.just_pressed(KeyCode::P);
+    actions.toggle_stats = keyboard_input.just_pressed(KeyCode::H);
+    actions.export_stats = keyboard_input.just_pressed(KeyCode::E);
+    actions.toggle_diagnostics = keyboard_input.just_pressed(KeyCode::F3);
+}
+
+fn set_designation_actions(
+    mut actions: ResMut<Actions>,
+    keyboard_input: Res<Input<KeyCode>>,
+    mouse_input: Res<Input<MouseButton>>,
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+) {
+    let tools = [
+        (KeyCode::Key1, DesignationTool::Fell),
+        (KeyCode::Key2, DesignationTool::StockpileZone),
+        (KeyCode::Key3, DesignationTool::HouseBlueprint),
+        (KeyCode::Key4, DesignationTool::Cancel),
+    ];
+    for (key, tool) in tools.iter() {
+        if keyboard_input.just_pressed(*key) {
+            // the key of the selected tool puts it away
+            actions.designation_tool = if actions.designation_tool == Some(*tool) {
+                None
+            } else {
+                Some(*tool)
+            };
+            actions.drag_start = None;
+            println!("Designation tool: {:?}", actions.designation_tool);
+        }
+    }
+    if mouse_input.just_pressed(MouseButton::Right) {
+        actions.designation_tool = None;
+        actions.drag_start = None;
+    }
+
+    actions.cursor_position = cursor_world_position(&windows, camera_query.single().ok());
+    actions.designated_area = None;
+    actions.inspected_position = None;
+    if actions.designation_tool.is_none() {
+        if mouse_input.just_pressed(MouseButton::Left) {
+            actions.inspected_position = actions.cursor_position;
+        }
+        return;
+    }
+    if mouse_input.just_pressed(MouseButton::Left) {
+        actions.drag_start = actions.cursor_position;
+    }
+    if mouse_input.just_released(MouseButton::Left) {
+        if let (Some(start), Some(end)) = (actions.drag_start, actions.cursor_position) {
+            actions.designated_area = Some((start, end));
+        }
+        actions.drag_start = None;
+    }
 }
 
 fn set_movement_actions(mut actions: ResMut<Actions>, keyboard_input: Res<Input<KeyCode>>) {
@@ -0,0 +1,271 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+
+use crate::{
+    combat::Attacker,
+    designations::Constructing,
+    layers::Shadow,
+    loading::Materials,
+    physics::{Mobile, PhysicalObject},
+    plants::Tree,
+    tree_cutting::{CuttingTree, ResourceCarrier},
+    GameState,
+};
+
+/// Frames of the sprite sheets are square
+pub const FRAME_SIZE: f32 = 16.0;
+pub const SHEET_COLUMNS: usize = 4;
+pub const SHEET_ROWS: usize = 4;
+
+// how far a tree sways while it is being cut, in radians
+const SHAKE_ANGLE: f32 = 0.06;
+const SHAKES_PER_SECOND: f32 = 4.0;
+
+/// Every state is a row of the sprite sheet. Frames are drawn facing left.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum AnimationState {
+    Idle,
+    Walk,
+    /// Swinging an axe, also at enemies, or biting for wolves
+    Chop,
+    Carry,
+}
+
+impl AnimationState {
+    fn row(&self) -> usize {
+        match self {
+            AnimationState::Idle => 0,
+            AnimationState::Walk => 1,
+            AnimationState::Chop => 2,
+            AnimationState::Carry => 3,
+        }
+    }
+
+    fn frame_count(&self) -> usize {
+        match self {
+            AnimationState::Idle => 2,
+            _ => 4,
+        }
+    }
+
+    fn frames_per_second(&self) -> f32 {
+        match self {
+            AnimationState::Idle => 1.5,
+            AnimationState::Walk => 8.0,
+            AnimationState::Chop => 6.0,
+            AnimationState::Carry => 6.0,
+        }
+    }
+}
+
+/// Plays a sprite sheet on the `MainSprite` child, which has to be a `SpriteSheetBundle`
+pub struct Animation {
+    pub state: AnimationState,
+    frame: usize,
+    timer: Timer,
+    last_position: Vec2,
+    facing_right: bool,
+}
+
+impl Animation {
+    pub fn new(position: Vec2) -> Self {
+        let state = AnimationState::Idle;
+        Self {
+            state,
+            frame: 0,
+            timer: Timer::from_seconds(1.0 / state.frames_per_second(), true),
+            last_position: position,
+            facing_right: false,
+        }
+    }
+
+    fn play(&mut self, state: AnimationState) {
+        if self.state != state {
+            self.state = state;
+            self.frame = 0;
+            self.timer = Timer::from_seconds(1.0 / state.frames_per_second(), true);
+        }
+    }
+}
+
+/// Logs drawn over the torso of a villager while they carry wood
+pub struct CarriedWood;
+
+/// A tree that sways and is highlighted while somebody cuts it
+pub struct BeingCut;
+
+pub struct TreeHighlight;
+
+pub struct AnimationPlugin;
+
+impl Plugin for AnimationPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(animate_creatures.system())
+                .with_system(show_carried_wood.system())
+                .with_system(shake_trees_being_cut.system()),
+        );
+    }
+}
+
+pub fn spawn_carried_wood(parent: &mut ChildBuilder, wood_logs: Handle<ColorMaterial>) {
+    // slightly in front of the main sprite, but not as far as the next object
+    parent
+        .spawn_bundle(SpriteBundle {
+            material: wood_logs,
+            transform: Transform::from_translation(Vec3::new(0.0, 6.0, 0.01)),
+            sprite: Sprite::new(Vec2::new(10.0, 5.0)),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(CarriedWood);
+}
+
+#[allow(clippy::type_complexity)]
+fn animate_creatures(
+    time: Res<Time>,
+    mut creature_query: Query<(
+        &Mobile,
+        &PhysicalObject,
+        Option<&ResourceCarrier>,
+        Option<&CuttingTree>,
+        Option<&Constructing>,
+        Option<&Attacker>,
+        &mut Animation,
+        &Children,
+    )>,
+    mut sprite_query: Query<&mut TextureAtlasSprite>,
+) {
+    for (
+        mobile,
+        physical_object,
+        carrier,
+        cutting,
+        constructing,
+        attacker,
+        mut animation,
+        children,
+    ) in creature_query.iter_mut()
+    {
+        let is_carrying = carrier.map_or(false, |carrier| carrier.wood > 0.0);
+        let is_striking = attacker.map_or(false, |attacker| attacker.is_striking);
+        let state = if cutting.is_some() || constructing.is_some() || is_striking {
+            AnimationState::Chop
+        } else if mobile.0.is_moving() && is_carrying {
+            AnimationState::Carry
+        } else if mobile.0.is_moving() {
+            AnimationState::Walk
+        } else {
+            AnimationState::Idle
+        };
+        animation.play(state);
+
+        // small sideways nudges, e.g. from avoiding others, do not turn anybody around
+        let moved = physical_object.position.x - animation.last_position.x;
+        if moved.abs() > 0.1 {
+            animation.facing_right = moved > 0.0;
+        }
+        animation.last_position = physical_object.position;
+
+        animation.timer.tick(time.delta());
+        if animation.timer.just_finished() {
+            animation.frame = (animation.frame + 1) % state.frame_count();
+        }
+
+        for child in children.iter() {
+            if let Ok(mut sprite) = sprite_query.get_mut(*child) {
+                sprite.index = (state.row() * SHEET_COLUMNS + animation.frame) as u32;
+                sprite.flip_x = animation.facing_right;
+            }
+        }
+    }
+}
+
+fn show_carried_wood(
+    carrier_query: Query<(&ResourceCarrier, &Children), Changed<ResourceCarrier>>,
+    mut carried_wood_query: Query<&mut Visible, With<CarriedWood>>,
+) {
+    for (carrier, children) in carrier_query.iter() {
+        for child in children.iter() {
+            if let Ok(mut visible) = carried_wood_query.get_mut(*child) {
+                visible.is_visible = carrier.wood > 0.0;
+            }
+        }
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn shake_trees_being_cut(
+    mut commands: Commands,
+    time: Res<Time>,
+    materials: Res<Materials>,
+    cutting_tree_query: Query<&CuttingTree>,
+    tree_query: Query<(Option<&Children>, Option<&BeingCut>), With<Tree>>,
+    being_cut_query: Query<(Entity, &Children), With<BeingCut>>,
+    mut sprite_query: Query<(&mut Transform, &Sprite), Without<Shadow>>,
+    highlight_query: Query<Entity, With<TreeHighlight>>,
+) {
+    let angle = (time.seconds_since_startup() as f32 * SHAKES_PER_SECOND * std::f32::consts::TAU)
+        .sin()
+        * SHAKE_ANGLE;
+    let mut trees_being_cut = HashSet::new();
+
+    for cutting in cutting_tree_query.iter() {
+        // trees out of view have no sprites to shake
+        let (children, being_cut) = match tree_query.get(cutting.tree_id) {
+            Ok((Some(children), being_cut)) => (children, being_cut),
+            _ => continue,
+        };
+        trees_being_cut.insert(cutting.tree_id);
+
+        if being_cut.is_none() {
+            commands
+                .entity(cutting.tree_id)
+                .insert(BeingCut)
+                .with_children(|parent| {
+                    parent
+                        .spawn_bundle(SpriteBundle {
+                            material: materials.highlight.clone(),
+                            sprite: Sprite::new(Vec2::new(16.0, 8.0)),
+                            ..Default::default()
+                        })
+                        .insert(Shadow)
+                        .insert(TreeHighlight);
+                });
+        }
+        for child in children.iter() {
+            if let Ok((mut transform, sprite)) = sprite_query.get_mut(*child) {
+                sway(&mut transform, sprite, angle);
+            }
+        }
+    }
+
+    for (tree_id, children) in being_cut_query.iter() {
+        if trees_being_cut.contains(&tree_id) {
+            continue;
+        }
+        commands.entity(tree_id).remove::<BeingCut>();
+        for child in children.iter() {
+            if let Ok((mut transform, sprite)) = sprite_query.get_mut(*child) {
+                sway(&mut transform, sprite, 0.0);
+            }
+            if highlight_query.get(*child).is_ok() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+    }
+}
+
+// turns the sprite around the bottom of the trunk instead of its center
+fn sway(transform: &mut Transform, sprite: &Sprite, angle: f32) {
+    transform.rotation = Quat::from_rotation_z(angle);
+    let z = transform.translation.z;
+    transform.translation = (transform.rotation * Vec3::new(0.0, sprite.size.y / 2.0, 0.0))
+        .truncate()
+        .extend(z);
+}
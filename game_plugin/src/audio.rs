@@ -1,4 +1,5 @@
 use crate::loading::AudioAssets;
+use crate::weather::{Weather, WeatherKind};
 use crate::GameState;
 use bevy::prelude::*;
 use bevy_kira_audio::{Audio, AudioChannel, AudioPlugin};
@@ -9,37 +10,72 @@ impl Plugin for InternalAudioPlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.insert_resource(AudioChannels {
             birds: AudioChannel::new("birds".to_owned()),
+            rain: AudioChannel::new("rain".to_owned()),
+            wind: AudioChannel::new("wind".to_owned()),
         })
         .add_plugin(AudioPlugin)
         .add_system_set(SystemSet::on_enter(GameState::Playing).with_system(start_audio.system()))
         .add_system_set(
-            SystemSet::on_update(GameState::Playing).with_system(control_bird_sound.system()),
+            SystemSet::on_update(GameState::Playing)
+                .with_system(control_bird_sound.system())
+                .with_system(control_weather_sound.system()),
         )
+        .add_system_set(SystemSet::on_pause(GameState::Playing).with_system(pause_audio.system()))
+        .add_system_set(SystemSet::on_resume(GameState::Playing).with_system(resume_audio.system()))
         .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(stop_audio.system()));
     }
 }
 
 struct AudioChannels {
     birds: AudioChannel,
+    rain: AudioChannel,
+    wind: AudioChannel,
 }
 
 fn start_audio(audio_assets: Res<AudioAssets>, audio: Res<Audio>, channels: Res<AudioChannels>) {
     audio.set_volume_in_channel(0.3, &channels.birds);
     audio.play_looped_in_channel(audio_assets.birds.clone(), &channels.birds);
     audio.pause_channel(&channels.birds);
+
+    for (source, channel) in [
+        (&audio_assets.rain, &channels.rain),
+        (&audio_assets.wind, &channels.wind),
+    ]
+    .iter()
+    {
+        audio.set_volume_in_channel(0.0, channel);
+        audio.play_looped_in_channel((*source).clone(), channel);
+    }
+}
+
+fn pause_audio(audio: Res<Audio>, channels: Res<AudioChannels>) {
+    audio.pause_channel(&channels.birds);
+    audio.pause_channel(&channels.rain);
+    audio.pause_channel(&channels.wind);
+}
+
+// the birds are resumed by `control_bird_sound`, if they sing at all
+fn resume_audio(audio: Res<Audio>, channels: Res<AudioChannels>) {
+    audio.resume_channel(&channels.rain);
+    audio.resume_channel(&channels.wind);
 }
 
 fn stop_audio(audio: Res<Audio>, channels: Res<AudioChannels>) {
     audio.stop_channel(&channels.birds);
+    audio.stop_channel(&channels.rain);
+    audio.stop_channel(&channels.wind);
 }
 
 fn control_bird_sound(
     ambience_query: Query<&Ambience>,
+    weather: Res<Weather>,
     audio: Res<Audio>,
     channels: Res<AudioChannels>,
 ) {
+    // birds hide when it rains heavily
+    let birds_sing = weather.kind == WeatherKind::Clear || weather.intensity < 0.3;
     for ambience in ambience_query.iter() {
-        if ambience.is_forest {
+        if ambience.is_forest && birds_sing {
             audio.resume_channel(&channels.birds);
         } else {
             audio.pause_channel(&channels.birds);
@@ -47,6 +83,31 @@ fn control_bird_sound(
     }
 }
 
+fn control_weather_sound(
+    weather: Res<Weather>,
+    audio: Res<Audio>,
+    channels: Res<AudioChannels>,
+    mut last_volumes: Local<(f32, f32)>,
+) {
+    let (rain_volume, wind_volume) = match weather.kind {
+        WeatherKind::Clear => (0.0, 0.0),
+        WeatherKind::Rain => (0.4, 0.05),
+        WeatherKind::Storm => (0.5, 0.4),
+        WeatherKind::Snow => (0.0, 0.2),
+    };
+    let volumes = (
+        rain_volume * weather.intensity,
+        wind_volume * weather.intensity,
+    );
+
+    // avoid flooding the audio thread with commands while the intensity slowly changes
+    if (volumes.0 - last_volumes.0).abs() > 0.01 || (volumes.1 - last_volumes.1).abs() > 0.01 {
+        audio.set_volume_in_channel(volumes.0, &channels.rain);
+        audio.set_volume_in_channel(volumes.1, &channels.wind);
+        *last_volumes = volumes;
+    }
+}
+
 pub struct Ambience {
     pub is_forest: bool,
 }
@@ -1,50 +1,56 @@
-use bevy::{core::Time, math::Vec2, prelude::*};
+use bevy::{math::Vec2, prelude::*};
 
 use crate::{
-    physics::{get_point_between, Mobile, PhysicalObject},
-    world_gen::{gen_in_rect, SimParams},
+    land_grid::LandGrid,
+    physics::{get_point_between, Mobile, PhysicalObject, Velocity},
+    roads::{find_route, Route},
+    time_cycle::TimeCycle,
+    village::Building,
+    weather::Weather,
+    GameState,
 };
 
+/// Speeds are in world units per game hour, see `Velocity`
 pub struct Walker {
     pub acceleration: f32,
+    pub deceleration: f32,
     pub max_speed: f32,
 }
 
 pub struct CheckTaskEvent(pub Entity);
 
+// walkers keep a little distance from each other when passing by
+const PERSONAL_SPACE: f32 = 2.0;
+// close enough to a destination to stop there
+const ARRIVAL_RADIUS: f32 = 1.0;
+
 pub struct MovementPlugin;
 
 impl Plugin for MovementPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_event::<CheckTaskEvent>()
-            .add_system(go_to_target.system())
-            .add_system(go_to_position.system());
+        app.add_event::<CheckTaskEvent>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(go_to_target.system().label("travel"))
+                .with_system(go_to_position.system().label("travel"))
+                .with_system(avoid_collisions.system().after("travel")),
+        );
     }
 }
 
 pub struct TravelToTarget {
-    pub time_to_next_location_check: f32,
     pub target_id: Entity,
+    /// Where the target was approached from in the last frame, it is tracked every frame as it may move
     pub last_target_position: Option<Vec2>,
 }
 
-impl TravelToTarget {
-    fn update(&mut self, new_position: Vec2, recheck_position_interval: f32, delta_seconds: f32) {
-        if self.time_to_next_location_check == 0.0 {
-            self.time_to_next_location_check = recheck_position_interval;
-            self.last_target_position = Some(new_position);
-        } else {
-            self.time_to_next_location_check -= delta_seconds.min(self.time_to_next_location_check);
-        }
-    }
-}
-
 pub struct TravelToPosition {
     pub position: Vec2,
 }
 
+#[allow(clippy::type_complexity)]
 pub fn go_to_target(
-    time: Res<Time>,
+    time_cycle: Res<TimeCycle>,
+    land_grid: Res<LandGrid>,
     mut commands: Commands,
     mut moving_query: Query<(
         Entity,
@@ -52,40 +58,65 @@ pub fn go_to_target(
         &Walker,
         &mut TravelToTarget,
         &mut Mobile,
+        Option<&mut Route>,
     )>,
     mut physical_object_query: Query<&mut PhysicalObject>,
     mut ev_check_intent: EventWriter<CheckTaskEvent>,
+    weather: Res<Weather>,
 ) {
-    let recheck_position_interval = 3000.0;
-    let game_hour = 3600.0 * 0.00001; // TODO: move to a daytime calc
-    let hours = time.delta_seconds() * game_hour;
+    let hours = time_cycle.delta_hours();
 
-    for (entity, mut transform, walker, mut travel_to_target, mut mobile) in moving_query.iter_mut()
+    for (entity, mut transform, walker, mut travel_to_target, mut mobile, route) in
+        moving_query.iter_mut()
     {
-        let position = physical_object_query
-            .get_mut(travel_to_target.target_id)
-            .unwrap()
-            .position;
-        travel_to_target.update(position, recheck_position_interval, time.delta_seconds());
-
-        let mut physical_object = physical_object_query.get_mut(entity).unwrap();
-        let destination: Vec2 = travel_to_target.last_target_position.unwrap();
+        let walker_object = *physical_object_query.get_mut(entity).unwrap();
+        let target = match physical_object_query.get_mut(travel_to_target.target_id) {
+            Ok(target) => *target,
+            Err(_) => {
+                println!("The target is gone, stopping");
+                mobile.0.stop();
+                commands
+                    .entity(entity)
+                    .remove::<TravelToTarget>()
+                    .remove::<Route>();
+                ev_check_intent.send(CheckTaskEvent(entity));
+                continue;
+            }
+        };
+        // walk up to the border of the target rather than into its center
+        let clearance = walker_object
+            .footprint
+            .extent_towards(target.position - walker_object.position);
+        let destination = target.approach_point(walker_object.position, clearance);
+        travel_to_target.last_target_position = Some(destination);
 
-        let result = _go_to_position(
-            &mut physical_object,
-            destination,
-            &mut mobile,
+        let (waypoint, distance) = follow_route(
             &mut commands,
+            &land_grid,
             entity,
-            &mut ev_check_intent,
+            route,
+            walker_object.position,
+            destination,
+        );
+        let mut physical_object = physical_object_query.get_mut(entity).unwrap();
+        let result = step_towards(
+            &mut physical_object,
+            waypoint,
+            distance,
+            &mut mobile.0,
             walker,
+            weather.walking_speed_factor()
+                * surface_speed_factor(&land_grid, walker_object.position),
             hours,
-            &mut transform,
         );
+        transform.translation = physical_object.position.extend(transform.translation.z);
 
         match result {
             TravelResult::Arrived => {
-                commands.entity(entity).remove::<TravelToTarget>();
+                commands
+                    .entity(entity)
+                    .remove::<TravelToTarget>()
+                    .remove::<Route>();
                 ev_check_intent.send(CheckTaskEvent(entity));
                 println!("Arrived to a target located at {}", destination);
             }
@@ -94,8 +125,10 @@ pub fn go_to_target(
     }
 }
 
+#[allow(clippy::type_complexity)]
 pub fn go_to_position(
-    time: Res<Time>,
+    time_cycle: Res<TimeCycle>,
+    land_grid: Res<LandGrid>,
     mut commands: Commands,
     mut moving_query: Query<(
         Entity,
@@ -103,13 +136,13 @@ pub fn go_to_position(
         &Walker,
         &TravelToPosition,
         &mut Mobile,
+        Option<&mut Route>,
     )>,
     mut physical_object_query: Query<&mut PhysicalObject>,
     mut ev_check_intent: EventWriter<CheckTaskEvent>,
+    weather: Res<Weather>,
 ) {
-    let recheck_position_interval = 3000.0;
-    let game_hour = 3600.0 * 0.00001; // TODO: move to a daytime calc
-    let hours = time.delta_seconds() * game_hour;
+    let hours = time_cycle.delta_hours();
 
     for (
         entity,
@@ -119,25 +152,38 @@ pub fn go_to_position(
             position: destination,
         },
         mut mobile,
+        route,
     ) in moving_query.iter_mut()
     {
         let mut physical_object = physical_object_query.get_mut(entity).unwrap();
 
-        let result = _go_to_position(
-            &mut physical_object,
-            *destination,
-            &mut mobile,
+        let (waypoint, distance) = follow_route(
             &mut commands,
+            &land_grid,
             entity,
-            &mut ev_check_intent,
+            route,
+            physical_object.position,
+            *destination,
+        );
+        let speed_factor = weather.walking_speed_factor()
+            * surface_speed_factor(&land_grid, physical_object.position);
+        let result = step_towards(
+            &mut physical_object,
+            waypoint,
+            distance,
+            &mut mobile.0,
             walker,
+            speed_factor,
             hours,
-            &mut transform,
         );
+        transform.translation = physical_object.position.extend(transform.translation.z);
 
         match result {
             TravelResult::Arrived => {
-                commands.entity(entity).remove::<TravelToPosition>();
+                commands
+                    .entity(entity)
+                    .remove::<TravelToPosition>()
+                    .remove::<Route>();
                 ev_check_intent.send(CheckTaskEvent(entity));
                 println!("Arrived to a position {}", destination);
             }
@@ -146,35 +192,152 @@ pub fn go_to_position(
     }
 }
 
+#[allow(clippy::type_complexity)]
+pub fn avoid_collisions(
+    mut walker_query: Query<
+        (
+            Entity,
+            &mut PhysicalObject,
+            &mut Transform,
+            &Mobile,
+            Option<&TravelToTarget>,
+            Option<&TravelToPosition>,
+        ),
+        With<Walker>,
+    >,
+    obstacle_query: Query<(Entity, &PhysicalObject), (With<Building>, Without<Walker>)>,
+) {
+    let walkers: Vec<(Entity, PhysicalObject)> = walker_query
+        .iter_mut()
+        .map(|(entity, physical_object, ..)| (entity, *physical_object))
+        .collect();
+
+    for (
+        entity,
+        mut physical_object,
+        mut transform,
+        mobile,
+        travel_to_target,
+        travel_to_position,
+    ) in walker_query.iter_mut()
+    {
+        // only walkers on the move step aside, standing ones are not pushed around
+        if !mobile.0.is_moving() {
+            continue;
+        }
+
+        let destination = travel_to_target
+            .and_then(|travel| travel.last_target_position)
+            .or_else(|| travel_to_position.map(|travel| travel.position));
+        let target_id = travel_to_target.map(|travel| travel.target_id);
+        let mut walker_object = *physical_object;
+
+        let is_arriving = destination.map_or(false, |destination| {
+            walker_object.position.distance(destination)
+                < 2.0 * walker_object.footprint.extent_towards(Vec2::X)
+        });
+        if !is_arriving {
+            for (other_id, other) in walkers.iter() {
+                let gap = walker_object.gap_to(other) - PERSONAL_SPACE;
+                if *other_id != entity && gap < 0.0 {
+                    let away = (walker_object.position - other.position).normalize_or_zero();
+                    walker_object.position += away * -gap / 2.0;
+                }
+            }
+        }
+
+        for (obstacle_id, obstacle) in obstacle_query.iter() {
+            let gap = walker_object.gap_to(obstacle);
+            let is_destination_inside = destination.map_or(false, |destination| {
+                obstacle.position.distance(destination)
+                    < obstacle
+                        .footprint
+                        .extent_towards(destination - obstacle.position)
+            });
+            if Some(obstacle_id) == target_id || is_destination_inside || gap >= 0.0 {
+                continue;
+            }
+
+            // step out of the building and slide along its border towards the destination
+            let normal = (walker_object.position - obstacle.position).normalize_or_zero();
+            let mut tangent = normal.perp();
+            if let Some(destination) = destination {
+                if tangent.dot(destination - walker_object.position) < 0.0 {
+                    tangent = -tangent;
+                }
+            }
+            walker_object.position += (normal + tangent) * -gap;
+        }
+
+        if walker_object.position != physical_object.position {
+            physical_object.position = walker_object.position;
+            transform.translation = walker_object.position.extend(transform.translation.z);
+        }
+    }
+}
+
 pub enum TravelResult {
     Arrived,
     Traveling,
 }
 
-fn _go_to_position(
-    physical_object: &mut PhysicalObject,
-    destination: Vec2,
-    moving: &mut Mobile,
+// keeps following the planned route while it leads to the destination, plans a new one otherwise
+fn follow_route(
     commands: &mut Commands,
+    land_grid: &LandGrid,
     entity: Entity,
-    ev_check_intent: &mut EventWriter<CheckTaskEvent>,
+    route: Option<Mut<Route>>,
+    position: Vec2,
+    destination: Vec2,
+) -> (Vec2, f32) {
+    match route {
+        Some(mut route) if route.leads_to(position, destination) => {
+            route.next_waypoint(position, destination)
+        }
+        _ => {
+            let mut route = find_route(land_grid, position, destination);
+            let next = route.next_waypoint(position, destination);
+            commands.entity(entity).insert(route);
+            next
+        }
+    }
+}
+
+fn surface_speed_factor(land_grid: &LandGrid, position: Vec2) -> f32 {
+    land_grid
+        .tile_at(position)
+        .map_or(1.0, |tile| tile.surface.speed_factor())
+}
+
+/// Accelerates towards `waypoint` and brakes in time to stop at the end of the route, `distance` away.
+/// `hours` is the game time passed since the last step
+fn step_towards(
+    physical_object: &mut PhysicalObject,
+    waypoint: Vec2,
+    distance: f32,
+    velocity: &mut Velocity,
     walker: &Walker,
+    speed_factor: f32,
     hours: f32,
-    transform: &mut Transform,
 ) -> TravelResult {
-    if physical_object.position == destination {
-        moving.0 .0 = 0.0;
-        TravelResult::Arrived
+    if distance <= ARRIVAL_RADIUS {
+        velocity.stop();
+        return TravelResult::Arrived;
+    }
+
+    // the fastest a walker can go and still stop at the destination, v² = 2·a·d
+    let braking_speed = (2.0 * walker.deceleration * distance).sqrt();
+    let wanted_speed = (walker.max_speed * speed_factor).min(braking_speed);
+    velocity.speed = if velocity.speed < wanted_speed {
+        (velocity.speed + walker.acceleration * hours).min(wanted_speed)
     } else {
-        let new_speed = moving.0 .0 + walker.acceleration * hours;
-        moving.0 .0 = walker.max_speed.min(new_speed);
+        (velocity.speed - walker.deceleration * hours).max(wanted_speed)
+    };
+    velocity.direction = (waypoint - physical_object.position).normalize_or_zero();
 
-        let speed = moving.0 .0;
-        let distance_travelled = moving.0 .0 * hours;
-        physical_object.position = get_point_between(physical_object.position, destination, speed);
-        transform.translation = physical_object.position.extend(transform.translation.z);
-        TravelResult::Traveling
-    }
+    physical_object.position =
+        get_point_between(physical_object.position, waypoint, velocity.speed * hours);
+    TravelResult::Traveling
 }
 
 #[derive(Debug, Clone)]
@@ -182,5 +345,40 @@ pub enum Task {
     CutTree(Entity),
     PickUpWood(f32),
     DropOffResources,
-    WanderAimlessly,
+    /// Walks to a spot, e.g. somewhere near home
+    StrollTo(Vec2),
+    /// Runs away from an enemy, see `EnemiesPlugin`
+    FleeTo(Vec2),
+    /// Fights an enemy until it is gone, see `CombatPlugin`
+    Attack(Entity),
+    /// Walks up to a building or another creature
+    Visit(Entity),
+    /// Stays put for some game hours
+    Idle(f32),
+    /// Walks to a log pile or a storage and takes up to that much wood from it
+    TakeWood(Entity, f32),
+    /// Brings the carried wood to a construction site, see `DesignationsPlugin`
+    Deliver(Entity),
+    /// Works on a construction site until the building is finished
+    Build(Entity),
+}
+
+impl Task {
+    /// Tasks a creature does when there is no work for it, see `IdlePlugin`
+    pub fn is_idle(&self) -> bool {
+        matches!(self, Task::StrollTo(_) | Task::Visit(_) | Task::Idle(_))
+    }
+
+    /// Tasks that tire a villager out, see `MoodPlugin`
+    pub fn is_work(&self) -> bool {
+        matches!(
+            self,
+            Task::CutTree(_)
+                | Task::PickUpWood(_)
+                | Task::DropOffResources
+                | Task::TakeWood(..)
+                | Task::Deliver(_)
+                | Task::Build(_)
+        )
+    }
 }
@@ -0,0 +1,230 @@
+//! Headless worlds for timing the simulation systems, see `benches/systems.rs`
+
+use std::{
+    collections::VecDeque,
+    time::{Duration, Instant},
+};
+
+use bevy::{app::Events, ecs::schedule::SystemDescriptor, prelude::*};
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use crate::{
+    behaviour::{go_to_target, CheckTaskEvent, Task, TravelToTarget, Walker},
+    blueprints::SpawnRequest,
+    definitions::{CreatureDefinition, SpeciesDefinition},
+    headless::build_headless,
+    hexagon::{HexagonBuilder, Rectangle},
+    physics::{Mobile, PhysicalObject, Velocity},
+    plants::{seed, tree_bundle},
+    time_cycle::time_cycle_system,
+    tree_cutting::{check_tasks, ResourceCarrier, ResourceStorage, TaskCompletedEvent, TaskQue},
+    world_gen::SimParams,
+};
+
+// a tree on every 20x20 square, like a dense forest
+const TREE_SPACING: f32 = 20.0;
+// villagers start around the storage in the middle, with a tree to cut somewhere in the forest
+const VILLAGE_RADIUS: f32 = 100.0;
+
+pub struct WorldSize {
+    pub trees: usize,
+    pub villagers: usize,
+}
+
+pub struct SystemTiming {
+    pub system: &'static str,
+    pub mean: Duration,
+    pub max: Duration,
+}
+
+/// A world with the trees and villagers only, without sprites, a window or the other systems
+pub fn build_world(size: &WorldSize, seed: u64) -> App {
+    let side = (size.trees as f32).sqrt().max(1.0) * TREE_SPACING + VILLAGE_RADIUS * 2.0;
+    let hexagon_builder = HexagonBuilder::new(10.0);
+    let (columns, rows) = hexagon_builder.get_world_columns_rows(side, side);
+    let sim_params = SimParams {
+        start_pos: Vec2::ZERO,
+        world_rect: hexagon_builder.get_world_rect(columns, rows),
+        hexagon_builder,
+        seed,
+        villagers: size.villagers as u32,
+        houses: 0,
+        tree_density: 0.0,
+        starting_wood: 0.0,
+    };
+
+    let species_definition: SpeciesDefinition =
+        ron::de::from_str(include_str!("../../assets/definitions/tree.species.ron")).unwrap();
+    let villager_definition: CreatureDefinition = ron::de::from_str(include_str!(
+        "../../assets/definitions/villager.creature.ron"
+    ))
+    .unwrap();
+    let tree_footprint = species_definition.footprint;
+    let world_rect = Rectangle {
+        position: sim_params.world_rect.position,
+        size: sim_params.world_rect.size - Vec2::splat(TREE_SPACING),
+    };
+    let centre = world_rect.position;
+
+    let mut app_builder = build_headless(sim_params);
+    app_builder
+        .add_asset::<SpeciesDefinition>()
+        .add_event::<SpawnRequest>()
+        .add_event::<CheckTaskEvent>()
+        .add_event::<TaskCompletedEvent>();
+    let mut app = app_builder.app;
+    let world = &mut app.world;
+
+    let rng = &mut StdRng::seed_from_u64(seed);
+    let species = world
+        .get_resource_mut::<Assets<SpeciesDefinition>>()
+        .unwrap()
+        .add(species_definition);
+    let species_definition = world
+        .get_resource::<Assets<SpeciesDefinition>>()
+        .unwrap()
+        .get(&species)
+        .unwrap();
+    let tree_bundles: Vec<_> = (0..size.trees)
+        .map(|_| tree_bundle(rng.gen_range(0.0..1.0), &species, species_definition))
+        .collect();
+    let mut tree_ids = Vec::with_capacity(size.trees);
+    for bundle in tree_bundles {
+        let position = random_position(rng, &world_rect);
+        let tree_id = world
+            .spawn()
+            .insert(Transform::from_translation(position.extend(0.0)))
+            .insert(PhysicalObject {
+                position,
+                footprint: tree_footprint,
+            })
+            .insert_bundle(bundle)
+            .id();
+        tree_ids.push(tree_id);
+    }
+
+    world
+        .spawn()
+        .insert(PhysicalObject {
+            position: centre,
+            footprint: tree_footprint,
+        })
+        .insert(ResourceStorage { wood: 0.0 });
+
+    for _ in 0..size.villagers {
+        let position = centre
+            + Vec2::new(
+                rng.gen_range(-VILLAGE_RADIUS..VILLAGE_RADIUS),
+                rng.gen_range(-VILLAGE_RADIUS..VILLAGE_RADIUS),
+            );
+        let mut villager = world.spawn();
+        villager
+            .insert(Transform::from_translation(position.extend(0.0)))
+            .insert(PhysicalObject {
+                position,
+                footprint: villager_definition.footprint,
+            })
+            .insert(Walker {
+                acceleration: villager_definition.acceleration,
+                deceleration: villager_definition.deceleration,
+                max_speed: villager_definition.max_speed,
+            })
+            .insert(Mobile(Velocity::default()))
+            .insert(ResourceCarrier {
+                wood: 0.0,
+                max_wood: villager_definition.max_wood,
+            });
+        if let Some(tree_id) = pick(rng, &tree_ids) {
+            villager
+                .insert(TravelToTarget {
+                    target_id: tree_id,
+                    last_target_position: None,
+                })
+                .insert(TaskQue(VecDeque::from(vec![
+                    Task::CutTree(tree_id),
+                    Task::PickUpWood(5.0),
+                    Task::DropOffResources,
+                ])));
+        } else {
+            villager.insert(TaskQue(VecDeque::new()));
+        }
+    }
+
+    app
+}
+
+fn random_position(rng: &mut StdRng, rect: &Rectangle) -> Vec2 {
+    rect.position
+        + Vec2::new(
+            rng.gen_range(-0.5..0.5) * rect.size.x,
+            rng.gen_range(-0.5..0.5) * rect.size.y,
+        )
+}
+
+fn pick(rng: &mut StdRng, ids: &[Entity]) -> Option<Entity> {
+    if ids.is_empty() {
+        None
+    } else {
+        Some(ids[rng.gen_range(0..ids.len())])
+    }
+}
+
+/// Runs the systems one after another for `ticks` frames, every villager has its tasks checked every frame
+pub fn time_systems(app: &mut App, ticks: u32) -> Vec<SystemTiming> {
+    let mut stages: Vec<(&'static str, SystemStage)> = vec![
+        ("plants::seed", stage(seed.system())),
+        ("tree_cutting::check_tasks", stage(check_tasks.system())),
+        ("behaviour::go_to_target", stage(go_to_target.system())),
+    ];
+    // not timed, it only advances the clock of the world by a tick
+    let mut time_cycle_stage = stage(time_cycle_system.system());
+    let mut totals = vec![Duration::ZERO; stages.len()];
+    let mut maxima = vec![Duration::ZERO; stages.len()];
+    let world = &mut app.world;
+
+    for _ in 0..ticks {
+        time_cycle_stage.run(world);
+        let mut query = world.query_filtered::<Entity, With<TaskQue>>();
+        let creature_ids: Vec<Entity> = query.iter(world).collect();
+        let mut check_task_events = world.get_resource_mut::<Events<CheckTaskEvent>>().unwrap();
+        for creature_id in creature_ids {
+            check_task_events.send(CheckTaskEvent(creature_id));
+        }
+
+        for (i, (_, stage)) in stages.iter_mut().enumerate() {
+            let start = Instant::now();
+            stage.run(world);
+            let elapsed = start.elapsed();
+            totals[i] += elapsed;
+            maxima[i] = maxima[i].max(elapsed);
+        }
+
+        // the requested saplings and log piles are not spawned, the events are only dropped
+        world
+            .get_resource_mut::<Events<SpawnRequest>>()
+            .unwrap()
+            .update();
+        world
+            .get_resource_mut::<Events<CheckTaskEvent>>()
+            .unwrap()
+            .update();
+        world
+            .get_resource_mut::<Events<TaskCompletedEvent>>()
+            .unwrap()
+            .update();
+    }
+
+    stages
+        .iter()
+        .zip(totals.iter().zip(maxima.iter()))
+        .map(|((system, _), (total, max))| SystemTiming {
+            system,
+            mean: *total / ticks.max(1),
+            max: *max,
+        })
+        .collect()
+}
+
+fn stage(system: impl Into<SystemDescriptor>) -> SystemStage {
+    SystemStage::single_threaded().with_system(system)
+}
@@ -0,0 +1,235 @@
+use bevy::{app::Events, prelude::*};
+
+use crate::{
+    buildings::{
+        spawn_house, spawn_house_blueprint, spawn_log_pile, spawn_stockpile, spawn_zone_storage,
+    },
+    definitions::{BuildingDefinition, CreatureDefinition, SpeciesDefinition},
+    enemies::spawn_wolf,
+    loading::{Definitions, Materials, SpriteSheets},
+    names::{NameGenerator, VillagerName},
+    plants::spawn_tree,
+    relationships::Parents,
+    residence::{spawn_villager, CreatureJoinedVillageEvent, Resident},
+    village::LivingSpaceAvailableEvent,
+    world_gen::{SimParams, SimRng},
+    GameState,
+};
+
+/// Asks for a new entity, so that gameplay systems do not need materials and definitions to create one.
+/// Materials, sizes and footprints are resolved from definitions by `spawn_requested`.
+pub enum SpawnRequest {
+    Tree {
+        species: Handle<SpeciesDefinition>,
+        position: Vec2,
+        size: f32,
+    },
+    /// A villager with a name made up, unless it is loaded with one
+    Villager {
+        position: Vec2,
+        name: Option<VillagerName>,
+    },
+    /// A villager born to a couple, living in their house
+    Child {
+        position: Vec2,
+        parents: (Entity, Entity),
+        residence_id: Entity,
+        surname: String,
+    },
+    Wolf {
+        position: Vec2,
+    },
+    House {
+        position: Vec2,
+    },
+    /// A house for the villagers to build, see `ConstructionSite`
+    HouseBlueprint {
+        position: Vec2,
+    },
+    Stockpile {
+        position: Vec2,
+        wood: f32,
+    },
+    LogPile {
+        position: Vec2,
+        wood: f32,
+    },
+    /// A storage on a tile of a stockpile zone
+    ZoneStorage {
+        position: Vec2,
+    },
+}
+
+pub struct BlueprintsPlugin;
+
+impl Plugin for BlueprintsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<SpawnRequest>()
+            // the generated world is spawned before the first update, like it was spawned in place
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(spawn_requested.system().after("generate_world")),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(spawn_requested.system().label("spawn")),
+            );
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_requested(
+    mut commands: Commands,
+    mut spawn_requests: ResMut<Events<SpawnRequest>>,
+    materials: Res<Materials>,
+    sprite_sheets: Res<SpriteSheets>,
+    definitions: Res<Definitions>,
+    sim_params: Res<SimParams>,
+    mut name_generator: ResMut<NameGenerator>,
+    mut sim_rng: ResMut<SimRng>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    creature_definitions: Res<Assets<CreatureDefinition>>,
+    building_definitions: Res<Assets<BuildingDefinition>>,
+    mut ev_creature_joined_village: EventWriter<CreatureJoinedVillageEvent>,
+    mut ev_living_space_available: EventWriter<LivingSpaceAvailableEvent>,
+) {
+    // drained, so that every request is spawned once even though this system runs in two system sets
+    for spawn_request in spawn_requests.drain() {
+        match spawn_request {
+            SpawnRequest::Tree {
+                species,
+                position,
+                size,
+            } => {
+                let species_definition = species_definitions.get(&species).unwrap();
+                spawn_tree(
+                    position,
+                    size,
+                    &species,
+                    species_definition,
+                    &sim_params.world_rect,
+                    &mut commands,
+                    &materials.tree,
+                    &materials.shadow,
+                );
+            }
+            SpawnRequest::Villager { position, name } => {
+                let villager_definition = creature_definitions.get(&definitions.villager).unwrap();
+                let name = match name {
+                    Some(name) => name_generator.reserve(name),
+                    None => name_generator.unique_name(None),
+                };
+                let villager_id = spawn_villager(
+                    &mut commands,
+                    &materials,
+                    &sprite_sheets,
+                    position,
+                    &definitions.villager,
+                    villager_definition,
+                    &sim_params,
+                    name,
+                    &mut sim_rng.0,
+                );
+                ev_creature_joined_village.send(CreatureJoinedVillageEvent(villager_id));
+            }
+            SpawnRequest::Child {
+                position,
+                parents,
+                residence_id,
+                surname,
+            } => {
+                let villager_definition = creature_definitions.get(&definitions.villager).unwrap();
+                let child_id = spawn_villager(
+                    &mut commands,
+                    &materials,
+                    &sprite_sheets,
+                    position,
+                    &definitions.villager,
+                    villager_definition,
+                    &sim_params,
+                    name_generator.unique_name(Some(&surname)),
+                    &mut sim_rng.0,
+                );
+                commands
+                    .entity(child_id)
+                    .insert(Parents(parents.0, parents.1))
+                    .insert(Resident { residence_id });
+                ev_creature_joined_village.send(CreatureJoinedVillageEvent(child_id));
+            }
+            SpawnRequest::Wolf { position } => {
+                let wolf_definition = creature_definitions.get(&definitions.wolf).unwrap();
+                spawn_wolf(
+                    &mut commands,
+                    &materials,
+                    &sprite_sheets,
+                    position,
+                    &definitions.wolf,
+                    wolf_definition,
+                    &sim_params,
+                );
+            }
+            SpawnRequest::House { position } => {
+                let house_definition = building_definitions.get(&definitions.house).unwrap();
+                let residence_id = spawn_house(
+                    &mut commands,
+                    &materials,
+                    position,
+                    &definitions.house,
+                    house_definition,
+                    &sim_params,
+                );
+                // one for every place, so that a family can live in it
+                for _ in 0..house_definition.max_people {
+                    ev_living_space_available.send(LivingSpaceAvailableEvent { residence_id });
+                }
+            }
+            SpawnRequest::HouseBlueprint { position } => {
+                let house_definition = building_definitions.get(&definitions.house).unwrap();
+                spawn_house_blueprint(
+                    &mut commands,
+                    &materials,
+                    position,
+                    &definitions.house,
+                    house_definition,
+                    &sim_params,
+                );
+            }
+            SpawnRequest::Stockpile { position, wood } => {
+                let stockpile_definition =
+                    building_definitions.get(&definitions.stockpile).unwrap();
+                spawn_stockpile(
+                    &mut commands,
+                    &materials,
+                    position,
+                    &definitions.stockpile,
+                    stockpile_definition,
+                    wood,
+                    &sim_params,
+                );
+            }
+            SpawnRequest::LogPile { position, wood } => {
+                let log_pile_definition = building_definitions.get(&definitions.log_pile).unwrap();
+                spawn_log_pile(
+                    &mut commands,
+                    &materials,
+                    position,
+                    &definitions.log_pile,
+                    log_pile_definition,
+                    wood,
+                    &sim_params,
+                );
+            }
+            SpawnRequest::ZoneStorage { position } => {
+                let log_pile_definition = building_definitions.get(&definitions.log_pile).unwrap();
+                spawn_zone_storage(
+                    &mut commands,
+                    &materials,
+                    position,
+                    &definitions.log_pile,
+                    log_pile_definition,
+                    &sim_params,
+                );
+            }
+        }
+    }
+}
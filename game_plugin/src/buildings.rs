@@ -4,11 +4,14 @@ use bevy::{
 };
 
 use crate::{
+    definitions::{BuildingDefinition, BuildingType},
+    designations::{ConstructionSite, StockpileZone},
+    idle::GatheringSpot,
     loading::Materials,
     physics::PhysicalObject,
-    sprite_helpers::spawn_sprite_bundles_,
-    tree_cutting::ResourceStorage,
-    village::{Building, LivingSpace, LivingSpaceAvailableEvent},
+    sprite_helpers::{spawn_sprite_bundles_, ShadowStyle},
+    tree_cutting::{LogPile, ResourceStorage},
+    village::{Building, Heating, LivingSpace},
     world_gen::SimParams,
 };
 
@@ -16,49 +19,150 @@ pub fn spawn_house(
     commands: &mut Commands,
     materials: &Res<Materials>,
     position: Vec2,
+    building_type: &Handle<BuildingDefinition>,
+    house_definition: &BuildingDefinition,
     sim_params: &Res<SimParams>,
-    max_people: u32,
-    ev_living_space_available: &mut EventWriter<LivingSpaceAvailableEvent>,
-) {
-    let bounding_box = Vec3::new(40.0, 30.0, 40.0);
-    let residence_id = spawn_sprite_bundles_(
+) -> Entity {
+    spawn_sprite_bundles_(
         commands,
         Vec3::ONE,
-        position,
-        bounding_box,
+        PhysicalObject {
+            position,
+            footprint: house_definition.footprint,
+        },
+        house_definition.bounding_box(),
         materials.house.clone(),
         materials.shadow.clone(),
-        sim_params.world_rect.size,
-        Vec2::new(0.0, -10.0),
+        ShadowStyle::Directional,
+        &sim_params.world_rect,
+        house_definition.origin(),
     )
+    .insert(BuildingType(building_type.clone()))
     .insert(Building)
     .insert(LivingSpace {
         current_people: 0,
-        max_people,
+        max_people: house_definition.max_people,
     })
-    .id();
+    .insert(Heating { is_heated: true })
+    .id()
+}
 
-    ev_living_space_available.send(LivingSpaceAvailableEvent { residence_id })
+pub fn spawn_house_blueprint(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    position: Vec2,
+    building_type: &Handle<BuildingDefinition>,
+    house_definition: &BuildingDefinition,
+    sim_params: &Res<SimParams>,
+) {
+    let (wood, work_hours) = house_definition
+        .construction
+        .map_or((0.0, 0.0), |construction| {
+            (construction.wood, construction.work_hours)
+        });
+    spawn_sprite_bundles_(
+        commands,
+        Vec3::ONE,
+        PhysicalObject {
+            position,
+            footprint: house_definition.footprint,
+        },
+        house_definition.bounding_box(),
+        materials.house_blueprint.clone(),
+        materials.shadow.clone(),
+        ShadowStyle::Directional,
+        &sim_params.world_rect,
+        house_definition.origin(),
+    )
+    .insert(BuildingType(building_type.clone()))
+    .insert(ConstructionSite {
+        wood_required: wood,
+        wood_delivered: 0.0,
+        work_hours_left: work_hours,
+    });
 }
 
 pub fn spawn_stockpile(
     commands: &mut Commands,
     materials: &Res<Materials>,
     position: Vec2,
+    building_type: &Handle<BuildingDefinition>,
+    stockpile_definition: &BuildingDefinition,
+    wood: f32,
     sim_params: &Res<SimParams>,
 ) {
-    let bounding_box = Vec3::new(100.0, 40.0, 40.0);
     spawn_sprite_bundles_(
         commands,
         Vec3::ONE,
-        position,
-        bounding_box,
+        PhysicalObject {
+            position,
+            footprint: stockpile_definition.footprint,
+        },
+        stockpile_definition.bounding_box(),
         materials.stockpile.clone(),
         materials.shadow.clone(),
-        sim_params.world_rect.size,
-        Vec2::new(0.0, -20.0),
+        ShadowStyle::Directional,
+        &sim_params.world_rect,
+        stockpile_definition.origin(),
+    )
+    .insert(BuildingType(building_type.clone()))
+    .insert(Building)
+    .insert(GatheringSpot)
+    .insert(ResourceStorage { wood });
+}
+
+pub fn spawn_log_pile(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    position: Vec2,
+    building_type: &Handle<BuildingDefinition>,
+    log_pile_definition: &BuildingDefinition,
+    wood: f32,
+    sim_params: &Res<SimParams>,
+) {
+    spawn_sprite_bundles_(
+        commands,
+        Vec3::ONE,
+        PhysicalObject {
+            position,
+            footprint: log_pile_definition.footprint,
+        },
+        log_pile_definition.bounding_box(),
+        materials.wood_logs.clone(),
+        materials.shadow.clone(),
+        ShadowStyle::Contact,
+        &sim_params.world_rect,
+        log_pile_definition.origin(),
+    )
+    .insert(BuildingType(building_type.clone()))
+    .insert(LogPile { wood });
+}
+
+// wood stored in a zone is drawn like a log pile, see `show_stored_wood`
+pub fn spawn_zone_storage(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    position: Vec2,
+    building_type: &Handle<BuildingDefinition>,
+    log_pile_definition: &BuildingDefinition,
+    sim_params: &Res<SimParams>,
+) {
+    spawn_sprite_bundles_(
+        commands,
+        Vec3::ONE,
+        PhysicalObject {
+            position,
+            footprint: log_pile_definition.footprint,
+        },
+        log_pile_definition.bounding_box(),
+        materials.wood_logs.clone(),
+        materials.shadow.clone(),
+        ShadowStyle::Contact,
+        &sim_params.world_rect,
+        log_pile_definition.origin(),
     )
-    .insert(PhysicalObject { position })
+    .insert(BuildingType(building_type.clone()))
+    .insert(StockpileZone)
     .insert(ResourceStorage { wood: 0.0 });
 }
 
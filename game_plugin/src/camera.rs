@@ -0,0 +1,82 @@
+use bevy::{input::mouse::MouseWheel, prelude::*};
+
+use crate::{actions::Actions, world_gen::SimParams, GameState};
+
+const SCROLL_SPEED: f32 = 500.0;
+const MIN_ZOOM: f32 = 0.5;
+const MAX_ZOOM: f32 = 4.0;
+
+pub struct MainCamera;
+
+pub struct CameraPlugin;
+
+impl Plugin for CameraPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(scroll_camera.system())
+                .with_system(zoom_camera.system()),
+        );
+    }
+}
+
+/// Center and size of the area of the world seen by the camera
+pub fn get_view_rect(
+    windows: &Windows,
+    camera_transform: Option<&Transform>,
+) -> Option<(Vec2, Vec2)> {
+    let window = windows.get_primary()?;
+    let camera_transform = camera_transform?;
+    let size = Vec2::new(window.width(), window.height()) * camera_transform.scale.truncate();
+    Some((camera_transform.translation.truncate(), size))
+}
+
+/// Where the mouse cursor points to in the world
+pub fn cursor_world_position(
+    windows: &Windows,
+    camera_transform: Option<&Transform>,
+) -> Option<Vec2> {
+    let window = windows.get_primary()?;
+    let cursor = window.cursor_position()?;
+    let camera_transform = camera_transform?;
+    let from_center = cursor - Vec2::new(window.width(), window.height()) / 2.0;
+    Some(camera_transform.translation.truncate() + from_center * camera_transform.scale.truncate())
+}
+
+fn scroll_camera(
+    time: Res<Time>,
+    actions: Res<Actions>,
+    sim_params: Res<SimParams>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    let movement = match actions.player_movement {
+        Some(movement) => movement,
+        None => return,
+    };
+
+    if let Ok(mut transform) = camera_query.single_mut() {
+        // zoomed out cameras scroll faster, so that crossing the screen takes the same time
+        let offset = movement * SCROLL_SPEED * transform.scale.x * time.delta_seconds();
+        let half_world = sim_params.world_rect.size / 2.0;
+        let position = (transform.translation.truncate() + offset).clamp(
+            sim_params.world_rect.position - half_world,
+            sim_params.world_rect.position + half_world,
+        );
+        transform.translation = position.extend(transform.translation.z);
+    }
+}
+
+fn zoom_camera(
+    mut ev_mouse_wheel: EventReader<MouseWheel>,
+    mut camera_query: Query<&mut Transform, With<MainCamera>>,
+) {
+    let scroll: f32 = ev_mouse_wheel.iter().map(|event| event.y).sum();
+    if scroll == 0.0 {
+        return;
+    }
+
+    if let Ok(mut transform) = camera_query.single_mut() {
+        let zoom = (transform.scale.x * 0.9f32.powf(scroll)).clamp(MIN_ZOOM, MAX_ZOOM);
+        transform.scale = Vec3::new(zoom, zoom, 1.0);
+    }
+}
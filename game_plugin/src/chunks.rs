@@ -0,0 +1,352 @@
+use std::collections::{BTreeSet, HashMap};
+
+use bevy::prelude::*;
+use rand::{prelude::StdRng, Rng, SeedableRng};
+
+use crate::{
+    blueprints::SpawnRequest,
+    camera::{get_view_rect, MainCamera},
+    creatures::Creature,
+    definitions::{Species, SpeciesDefinition},
+    hexagon::Rectangle,
+    land_grid::{
+        spawn_chunk_meshes, ChunkCoord, GridLines, LandGrid, TerrainChunk, TerrainPipeline,
+    },
+    loading::{Definitions, Materials},
+    physics::{PhysicalObject, Placement},
+    plants::{catch_up_growth, Dormant, PlantSize, Seeder, Tree, WoodResource},
+    sprite_helpers::{spawn_main_sprite, spawn_shadow, ShadowStyle},
+    time_cycle::TimeCycle,
+    village::Building,
+    world_gen::{gen_in_rect, SimParams},
+    GameState,
+};
+
+// chunks around villagers and buildings keep being simulated when nobody looks at them
+const ACTIVE_RADIUS: i32 = 1;
+// chunks just outside of the view are prepared before they scroll in
+const VIEW_MARGIN: i32 = 1;
+// trees per 100x100 area at which dormant chunks stop adding saplings
+const MAX_TREE_DENSITY: f32 = 4.0;
+
+pub struct ChunkStreaming {
+    pub visible: BTreeSet<ChunkCoord>,
+    pub simulated: BTreeSet<ChunkCoord>,
+    /// Whether the view generates and wakes up chunks, in lockstep sessions only the villagers and buildings do
+    pub follows_view: bool,
+}
+
+impl Default for ChunkStreaming {
+    fn default() -> Self {
+        Self {
+            visible: BTreeSet::new(),
+            simulated: BTreeSet::new(),
+            follows_view: true,
+        }
+    }
+}
+
+pub struct ChunkPlugin;
+
+impl Plugin for ChunkPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<ChunkStreaming>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_streaming.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(stream_chunks.system())
+                    .with_system(update_trees_in_chunks.system())
+                    .with_system(hide_new_trees_out_of_view.system()),
+            );
+    }
+}
+
+// whether the view is followed is up to the session, not the game
+fn reset_streaming(mut streaming: ResMut<ChunkStreaming>) {
+    *streaming = ChunkStreaming {
+        follows_view: streaming.follows_view,
+        ..Default::default()
+    };
+}
+
+fn chunks_in_view(land_grid: &LandGrid, center: Vec2, size: Vec2) -> BTreeSet<ChunkCoord> {
+    let bottom_left = land_grid.chunk_at(center - size / 2.0);
+    let top_right = land_grid.chunk_at(center + size / 2.0);
+
+    let mut chunks = BTreeSet::new();
+    for x in bottom_left.0 - VIEW_MARGIN..=top_right.0 + VIEW_MARGIN {
+        for y in bottom_left.1 - VIEW_MARGIN..=top_right.1 + VIEW_MARGIN {
+            if land_grid.contains_chunk((x, y)) {
+                chunks.insert((x, y));
+            }
+        }
+    }
+    chunks
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn stream_chunks(
+    mut commands: Commands,
+    mut streaming: ResMut<ChunkStreaming>,
+    mut land_grid: ResMut<LandGrid>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    terrain_pipeline: Res<TerrainPipeline>,
+    sim_params: Res<SimParams>,
+    definitions: Res<Definitions>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+    anchor_query: Query<&PhysicalObject, Or<(With<Creature>, With<Building>)>>,
+    terrain_query: Query<(Entity, &TerrainChunk)>,
+    grid_lines_query: Query<(Entity, &GridLines)>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    let in_view = match get_view_rect(&windows, camera_query.single().ok()) {
+        Some((center, size)) => chunks_in_view(&land_grid, center, size),
+        None => return,
+    };
+    // the view of one peer must not change the world of both
+    let mut simulated = if streaming.follows_view {
+        in_view.clone()
+    } else {
+        BTreeSet::new()
+    };
+    for physical_object in anchor_query.iter() {
+        let (x, y) = land_grid.chunk_at(physical_object.position);
+        for dx in -ACTIVE_RADIUS..=ACTIVE_RADIUS {
+            for dy in -ACTIVE_RADIUS..=ACTIVE_RADIUS {
+                if land_grid.contains_chunk((x + dx, y + dy)) {
+                    simulated.insert((x + dx, y + dy));
+                }
+            }
+        }
+    }
+
+    // only what has been generated is shown, which is everything in view unless the view is not followed
+    let shown = |land_grid: &LandGrid| -> BTreeSet<ChunkCoord> {
+        in_view
+            .iter()
+            .copied()
+            .filter(|chunk| land_grid.is_generated(*chunk))
+            .collect()
+    };
+    if shown(&land_grid) == streaming.visible && simulated == streaming.simulated {
+        return;
+    }
+
+    let tree_definition = species_definitions.get(&definitions.tree).unwrap();
+    let mut placement = Placement::new(anchor_query.iter().copied().collect());
+    for chunk in simulated.iter() {
+        if !land_grid.is_generated(*chunk) {
+            land_grid.generate_chunk(*chunk);
+
+            let rect = land_grid.chunk_rect(*chunk);
+            let rng = &mut StdRng::seed_from_u64(chunk_seed(sim_params.seed, *chunk));
+            let tree_count = (sim_params.trees_in_area(rect.size) + rng.gen_range(0.0..1.0)) as u32;
+            for _ in 0..tree_count {
+                let size = rng.gen_range(0.0..1.0);
+                let position = match placement.find_free(rng, &rect, tree_definition.footprint, 5) {
+                    Some(position) => position,
+                    None => continue,
+                };
+                spawn_requests.send(SpawnRequest::Tree {
+                    species: definitions.tree.clone(),
+                    position,
+                    size,
+                });
+            }
+        }
+    }
+
+    let visible = shown(&land_grid);
+    for chunk in visible.difference(&streaming.visible) {
+        spawn_chunk_meshes(
+            &mut commands,
+            &mut meshes,
+            &terrain_pipeline,
+            &land_grid,
+            *chunk,
+        );
+    }
+    for (entity, terrain_chunk) in terrain_query.iter() {
+        if !visible.contains(&terrain_chunk.chunk) {
+            commands.entity(entity).despawn();
+        }
+    }
+    for (entity, grid_lines) in grid_lines_query.iter() {
+        if !visible.contains(&grid_lines.chunk) {
+            commands.entity(entity).despawn();
+        }
+    }
+
+    streaming.visible = visible;
+    streaming.simulated = simulated;
+}
+
+// every chunk gets its own stream of random numbers, so it looks the same whenever it is generated
+fn chunk_seed(seed: u64, chunk: ChunkCoord) -> u64 {
+    seed ^ ((chunk.0 as u32 as u64) << 32 | chunk.1 as u32 as u64)
+        .wrapping_mul(0x2545_f491_4f6c_dd1d)
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn update_trees_in_chunks(
+    mut commands: Commands,
+    streaming: Res<ChunkStreaming>,
+    land_grid: Res<LandGrid>,
+    materials: Res<Materials>,
+    sim_params: Res<SimParams>,
+    time_cycle: Res<TimeCycle>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    mut tree_query: Query<
+        (
+            Entity,
+            &PhysicalObject,
+            &Species,
+            &mut Transform,
+            &mut PlantSize,
+            &mut WoodResource,
+            &Seeder,
+            Option<&Dormant>,
+            Option<&Children>,
+        ),
+        With<Tree>,
+    >,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    if !streaming.is_changed() {
+        return;
+    }
+
+    let now = time_cycle.seconds_since_start();
+    let mut trees_per_chunk: HashMap<ChunkCoord, u32> = HashMap::new();
+    // a chunk waking up again draws other numbers than the last time
+    let mut chunk_rngs: HashMap<ChunkCoord, StdRng> = HashMap::new();
+    let mut saplings: Vec<(Vec2, Handle<SpeciesDefinition>, ChunkCoord)> = vec![];
+    let mut placement = Placement::default();
+
+    for (
+        tree_id,
+        physical_object,
+        Species(species),
+        mut transform,
+        mut plant_size,
+        mut wood_res,
+        seeder,
+        dormant,
+        children,
+    ) in tree_query.iter_mut()
+    {
+        let chunk = land_grid.chunk_at(physical_object.position);
+        placement.add(*physical_object);
+        *trees_per_chunk.entry(chunk).or_insert(0) += 1;
+        let species_definition = species_definitions.get(species).unwrap();
+
+        match (streaming.simulated.contains(&chunk), dormant) {
+            (true, Some(dormant)) => {
+                let rng = chunk_rngs.entry(chunk).or_insert_with(|| {
+                    StdRng::seed_from_u64(chunk_seed(sim_params.seed, chunk) ^ now.to_bits())
+                });
+                let seed_count = catch_up_growth(
+                    time_cycle.real_seconds(now - dormant.since),
+                    &mut transform,
+                    &mut plant_size,
+                    &mut wood_res,
+                    seeder,
+                    rng,
+                );
+                let seeding_area = Rectangle {
+                    position: physical_object.position,
+                    size: species_definition.seeding_area(),
+                };
+                for _ in 0..seed_count {
+                    let position = gen_in_rect(rng, &seeding_area);
+                    saplings.push((position, species.clone(), land_grid.chunk_at(position)));
+                }
+                commands.entity(tree_id).remove::<Dormant>();
+            }
+            (false, None) => {
+                commands.entity(tree_id).insert(Dormant { since: now });
+            }
+            _ => {}
+        }
+
+        // in lockstep sessions the trees keep their sprites, taking them away would reorder the trees of one peer only
+        if !streaming.follows_view {
+            continue;
+        }
+        let has_sprite = children.map_or(false, |children| !children.is_empty());
+        if streaming.visible.contains(&chunk) && !has_sprite {
+            let material = materials.tree_for_season(time_cycle.season());
+            commands.entity(tree_id).with_children(|parent| {
+                spawn_main_sprite(
+                    parent,
+                    species_definition.bounding_box(),
+                    material,
+                    Vec2::ZERO,
+                );
+                spawn_shadow(
+                    parent,
+                    physical_object.footprint,
+                    species_definition.bounding_box(),
+                    materials.shadow.clone(),
+                    ShadowStyle::Contact,
+                );
+            });
+        } else if !streaming.visible.contains(&chunk) && has_sprite {
+            for child in children.unwrap().iter() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+    }
+
+    for (position, species, chunk) in saplings {
+        let capacity = (land_grid.chunk_rect(chunk).size.x * land_grid.chunk_rect(chunk).size.y
+            / 10_000.0
+            * MAX_TREE_DENSITY) as u32;
+        let count = trees_per_chunk.entry(chunk).or_insert(0);
+        if *count >= capacity || !land_grid.contains_chunk(chunk) {
+            continue;
+        }
+        let species_definition = species_definitions.get(&species).unwrap();
+        let sapling = PhysicalObject {
+            position,
+            footprint: species_definition.footprint,
+        };
+        if !placement.is_free(&sapling) {
+            continue;
+        }
+        placement.add(sapling);
+        *count += 1;
+
+        spawn_requests.send(SpawnRequest::Tree {
+            species,
+            position,
+            size: 0.0,
+        });
+    }
+}
+
+// saplings seeded in simulated chunks out of view are spawned with sprites like any other tree
+fn hide_new_trees_out_of_view(
+    mut commands: Commands,
+    streaming: Res<ChunkStreaming>,
+    land_grid: Res<LandGrid>,
+    new_tree_query: Query<(&PhysicalObject, &Children), Added<Tree>>,
+) {
+    if !streaming.follows_view {
+        return;
+    }
+    for (physical_object, children) in new_tree_query.iter() {
+        if !streaming
+            .visible
+            .contains(&land_grid.chunk_at(physical_object.position))
+        {
+            for child in children.iter() {
+                commands.entity(*child).despawn_recursive();
+            }
+        }
+    }
+}
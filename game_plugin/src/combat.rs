@@ -0,0 +1,269 @@
+use bevy::prelude::*;
+
+use crate::{
+    actions::Actions,
+    behaviour::{CheckTaskEvent, Task, TravelToPosition, TravelToTarget},
+    blueprints::SpawnRequest,
+    creatures::{creature_name, Perception},
+    definitions::{Archetype, CreatureDefinition},
+    enemies::Hostile,
+    idle::Idling,
+    names::VillagerName,
+    physics::PhysicalObject,
+    residence::{let_go_of_villager, CreatureLeftVillageEvent, Villager},
+    time_cycle::TimeCycle,
+    tree_cutting::{is_located_near, CuttingTree, ResourceCarrier, TaskQue},
+    GameState,
+};
+
+// share of the max health recovered every game hour
+const RECOVERY_PER_HOUR: f32 = 0.1;
+
+pub struct Health {
+    pub current: f32,
+    pub max: f32,
+}
+
+/// Share of the damage which is blocked, from 0.0 to 1.0
+pub struct Armour(pub f32);
+
+/// Melee attacks, the target is the prey of a `Hostile` or the `Task::Attack` of a defender
+pub struct Attacker {
+    pub damage: f32,
+    pub range: f32,
+    pub cooldown_hours: f32,
+    pub cooldown_left: f32,
+    /// In range of the target, for the animation
+    pub is_striking: bool,
+}
+
+/// A villager drafted to fight the enemies instead of working
+pub struct Defender;
+
+pub struct CreatureDiedEvent(pub Entity);
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<CreatureDiedEvent>().add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(draft_defenders.system())
+                .with_system(defend_village.system())
+                .with_system(strike.system().label("strike"))
+                .with_system(recover_health.system())
+                .with_system(
+                    handle_deaths
+                        .system()
+                        .after("strike")
+                        .before("residence")
+                        .before("spawn"),
+                ),
+        );
+    }
+}
+
+pub fn combat_bundle(creature_definition: &CreatureDefinition) -> (Health, Armour, Attacker) {
+    (
+        Health {
+            current: creature_definition.max_health,
+            max: creature_definition.max_health,
+        },
+        Armour(creature_definition.armour),
+        Attacker {
+            damage: creature_definition.melee.damage,
+            range: creature_definition.melee.range,
+            cooldown_hours: creature_definition.melee.cooldown_hours,
+            cooldown_left: 0.0,
+            is_striking: false,
+        },
+    )
+}
+
+// every villager is drafted at once, and sent back to work at once
+fn draft_defenders(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    mut villager_query: Query<(Entity, &mut TaskQue, Option<&Defender>), With<Villager>>,
+) {
+    if !actions.toggle_draft {
+        return;
+    }
+
+    let is_drafting = villager_query
+        .iter_mut()
+        .all(|(_, _, defender)| defender.is_none());
+    for (villager_id, mut task_que, _) in villager_query.iter_mut() {
+        task_que.0.clear();
+        let mut villager = commands.entity(villager_id);
+        villager
+            .remove::<TravelToTarget>()
+            .remove::<TravelToPosition>()
+            .remove::<CuttingTree>()
+            .remove::<Idling>();
+        if is_drafting {
+            villager.insert(Defender);
+        } else {
+            villager.remove::<Defender>();
+        }
+    }
+    println!(
+        "{}",
+        if is_drafting {
+            "Villagers are drafted to defend the village"
+        } else {
+            "Defenders go back to work"
+        }
+    );
+}
+
+// defenders go after the closest enemy they notice and chase it until it is gone
+#[allow(clippy::type_complexity)]
+fn defend_village(
+    mut commands: Commands,
+    mut defender_query: Query<
+        (
+            Entity,
+            &PhysicalObject,
+            &Perception,
+            &Attacker,
+            &mut TaskQue,
+            Option<&TravelToTarget>,
+        ),
+        With<Defender>,
+    >,
+    enemy_query: Query<(Entity, &PhysicalObject), With<Hostile>>,
+    mut ev_check_task: EventWriter<CheckTaskEvent>,
+) {
+    for (defender_id, defender, perception, attacker, mut task_que, travel_to_target) in
+        defender_query.iter_mut()
+    {
+        match task_que.0.front() {
+            Some(Task::Attack(enemy_id)) => match enemy_query.get(*enemy_id) {
+                Ok((_, enemy)) => {
+                    let is_chasing =
+                        travel_to_target.map_or(false, |travel| travel.target_id == *enemy_id);
+                    if !is_chasing && defender.gap_to(enemy) > attacker.range {
+                        commands.entity(defender_id).insert(TravelToTarget {
+                            target_id: *enemy_id,
+                            last_target_position: None,
+                        });
+                    }
+                }
+                Err(_) => ev_check_task.send(CheckTaskEvent(defender_id)),
+            },
+            _ => {
+                let enemy = enemy_query
+                    .iter()
+                    .filter(|(_, enemy)| defender.gap_to(enemy) <= perception.radius)
+                    .min_by(|(_, a), (_, b)| {
+                        defender.gap_to(a).partial_cmp(&defender.gap_to(b)).unwrap()
+                    });
+                if let Some((enemy_id, _)) = enemy {
+                    commands
+                        .entity(defender_id)
+                        .remove::<TravelToPosition>()
+                        .remove::<Idling>();
+                    task_que.0.push_front(Task::Attack(enemy_id));
+                }
+            }
+        }
+    }
+}
+
+fn strike(
+    time_cycle: Res<TimeCycle>,
+    mut attacker_query: Query<(Entity, &mut Attacker, Option<&Hostile>, Option<&TaskQue>)>,
+    physical_object_query: Query<&PhysicalObject>,
+    mut target_query: Query<(&mut Health, Option<&Armour>)>,
+) {
+    let hours = time_cycle.delta_hours();
+
+    for (attacker_id, mut attacker, hostile, task_que) in attacker_query.iter_mut() {
+        attacker.cooldown_left = (attacker.cooldown_left - hours).max(0.0);
+        attacker.is_striking = false;
+
+        let target = hostile.and_then(|hostile| hostile.prey).or_else(|| {
+            match task_que.and_then(|task_que| task_que.0.front()) {
+                Some(Task::Attack(enemy_id)) => Some(*enemy_id),
+                _ => None,
+            }
+        });
+        let target_id = match target {
+            Some(target_id) if physical_object_query.get(target_id).is_ok() => target_id,
+            _ => continue,
+        };
+        if !is_located_near(
+            &physical_object_query,
+            &attacker_id,
+            &target_id,
+            attacker.range,
+        ) {
+            continue;
+        }
+
+        attacker.is_striking = true;
+        if attacker.cooldown_left > 0.0 {
+            continue;
+        }
+        attacker.cooldown_left = attacker.cooldown_hours;
+        if let Ok((mut health, armour)) = target_query.get_mut(target_id) {
+            health.current -= attacker.damage * (1.0 - armour.map_or(0.0, |armour| armour.0));
+        }
+    }
+}
+
+fn recover_health(time_cycle: Res<TimeCycle>, mut health_query: Query<&mut Health>) {
+    let hours = time_cycle.delta_hours();
+    for mut health in health_query.iter_mut() {
+        if health.current > 0.0 && health.current < health.max {
+            health.current =
+                (health.current + health.max * RECOVERY_PER_HOUR * hours).min(health.max);
+        }
+    }
+}
+
+// the village lets go of dead villagers in the same frame, while their residence can still be looked up
+#[allow(clippy::type_complexity)]
+fn handle_deaths(
+    mut commands: Commands,
+    dead_query: Query<(
+        Entity,
+        &Health,
+        Option<&VillagerName>,
+        &Archetype,
+        &PhysicalObject,
+        Option<&ResourceCarrier>,
+        Option<&Villager>,
+    )>,
+    creature_definitions: Res<Assets<CreatureDefinition>>,
+    mut ev_creature_left_village: EventWriter<CreatureLeftVillageEvent>,
+    mut ev_creature_died: EventWriter<CreatureDiedEvent>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    for (creature_id, health, name, archetype, physical_object, carrier, villager) in
+        dead_query.iter()
+    {
+        if health.current > 0.0 {
+            continue;
+        }
+
+        println!(
+            "{} has died",
+            creature_name(name, archetype, &creature_definitions)
+        );
+        ev_creature_died.send(CreatureDiedEvent(creature_id));
+        if villager.is_some() {
+            let_go_of_villager(
+                &mut commands,
+                creature_id,
+                physical_object.position,
+                carrier,
+                &mut ev_creature_left_village,
+                &mut spawn_requests,
+            );
+        } else {
+            commands.entity(creature_id).despawn_recursive();
+        }
+    }
+}
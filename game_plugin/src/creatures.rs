@@ -1,5 +1,40 @@
+use bevy::prelude::*;
+
+use crate::{
+    definitions::{Archetype, CreatureDefinition},
+    names::VillagerName,
+};
+
 pub struct Creature {
-    pub name: &'static str,
+    pub personality: Personality,
+}
+
+/// Villagers go by their own name, other creatures by the one of their kind
+pub fn creature_name(
+    name: Option<&VillagerName>,
+    archetype: &Archetype,
+    creature_definitions: &Assets<CreatureDefinition>,
+) -> String {
+    match name {
+        Some(name) => name.full(),
+        None => creature_definitions
+            .get(&archetype.0)
+            .map_or_else(|| "A creature".to_string(), |definition| definition.name.clone()),
+    }
+}
+
+/// Traits from 0.0 to 1.0 weighing what a creature does when it is idle
+#[derive(Clone, Copy, Debug)]
+pub struct Personality {
+    /// Likes to chat with others and sit at the gathering spot
+    pub sociability: f32,
+    /// Prefers staying close to home over hanging around the village centre
+    pub homeliness: f32,
+}
+
+/// How far other creatures are noticed, in world units
+pub struct Perception {
+    pub radius: f32,
 }
 
 pub struct Fatigue(pub f32);
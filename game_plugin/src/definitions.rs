@@ -0,0 +1,308 @@
+use std::{marker::PhantomData, ops::Range};
+
+use bevy::{
+    asset::{Asset, AssetLoader, BoxedFuture, LoadContext, LoadedAsset},
+    prelude::*,
+    reflect::TypeUuid,
+};
+use serde::{de::DeserializeOwned, Deserialize};
+
+use crate::{
+    behaviour::Walker,
+    combat::Attacker,
+    creatures::Perception,
+    physics::{Footprint, PhysicalObject},
+    plants::{PlantSize, Seeder, Tree},
+    tree_cutting::ResourceCarrier,
+    village::{LivingSpace, LivingSpaceAvailableEvent},
+    GameState,
+};
+
+/// Content definitions are RON files in `assets/definitions`, so balance can be tuned without recompiling.
+/// The file name suffix tells which kind of definition it is, e.g. `tree.species.ron`.
+/// Edited species, creatures and buildings apply to the living entities spawned from them,
+/// the world definition is read by the new-game screen and applies to the next game.
+pub struct DefinitionsPlugin;
+
+impl Plugin for DefinitionsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_asset::<WorldDefinition>()
+            .add_asset::<SpeciesDefinition>()
+            .add_asset::<CreatureDefinition>()
+            .add_asset::<BuildingDefinition>()
+            .add_asset_loader(RonAssetLoader::<WorldDefinition>::new(&["world.ron"]))
+            .add_asset_loader(RonAssetLoader::<SpeciesDefinition>::new(&["species.ron"]))
+            .add_asset_loader(RonAssetLoader::<CreatureDefinition>::new(&["creature.ron"]))
+            .add_asset_loader(RonAssetLoader::<BuildingDefinition>::new(&["building.ron"]))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(reload_species.system())
+                    .with_system(reload_creatures.system())
+                    .with_system(reload_buildings.system()),
+            );
+    }
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "5b1d0f0e-6f39-4c43-9d0a-3a4f5d8e2c11"]
+pub struct WorldDefinition {
+    pub hexagon_size: f32,
+    pub size: (f32, f32),
+    pub start_pos: (f32, f32),
+    pub villagers: u32,
+    pub houses: u32,
+    /// Trees per 100x100 area
+    pub tree_density: f32,
+    pub starting_wood: f32,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "0e6f7c02-8a0b-4f45-9e3e-5e0b7c9d4a21"]
+pub struct SpeciesDefinition {
+    pub name: String,
+    pub bounding_box: (f32, f32, f32),
+    pub max_size: f32,
+    pub growth_per_second: f32,
+    pub seed_growth_per_second: Range<f32>,
+    pub survival_probability: f32,
+    pub seeding_area: (f32, f32),
+    pub footprint: Footprint,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "a3c2d1e7-1f5b-4b8e-8a6d-2c7e9f0b3d32"]
+pub struct CreatureDefinition {
+    pub name: String,
+    pub bounding_box: (f32, f32, f32),
+    /// World units per game hour, gained every game hour
+    pub acceleration: f32,
+    /// World units per game hour, lost every game hour when braking
+    pub deceleration: f32,
+    /// World units per game hour
+    pub max_speed: f32,
+    pub max_wood: f32,
+    pub construction_skill: f32,
+    /// How far other creatures are noticed, villagers flee from enemies and enemies hunt villagers within it
+    pub perception_radius: f32,
+    pub max_health: f32,
+    /// Share of the damage which is blocked, from 0.0 to 1.0
+    pub armour: f32,
+    pub melee: MeleeDefinition,
+    pub footprint: Footprint,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct MeleeDefinition {
+    pub damage: f32,
+    /// Measured between the borders of the attacker and the target
+    pub range: f32,
+    pub cooldown_hours: f32,
+}
+
+#[derive(Deserialize, TypeUuid)]
+#[uuid = "c7b9e4a5-3d2f-4e1a-b6c8-4f0a2e9d1b43"]
+pub struct BuildingDefinition {
+    pub name: String,
+    pub bounding_box: (f32, f32, f32),
+    /// Offset of the sprite relative to the building position
+    pub origin: (f32, f32),
+    pub max_people: u32,
+    pub footprint: Footprint,
+    /// Buildings placed by the player are built by the villagers first
+    #[serde(default)]
+    pub construction: Option<ConstructionDefinition>,
+}
+
+#[derive(Deserialize, Clone, Copy)]
+pub struct ConstructionDefinition {
+    pub wood: f32,
+    pub work_hours: f32,
+}
+
+impl WorldDefinition {
+    pub fn size(&self) -> Vec2 {
+        self.size.into()
+    }
+
+    pub fn start_pos(&self) -> Vec2 {
+        self.start_pos.into()
+    }
+}
+
+impl SpeciesDefinition {
+    pub fn bounding_box(&self) -> Vec3 {
+        self.bounding_box.into()
+    }
+
+    pub fn seeding_area(&self) -> Vec2 {
+        self.seeding_area.into()
+    }
+}
+
+impl CreatureDefinition {
+    pub fn bounding_box(&self) -> Vec3 {
+        self.bounding_box.into()
+    }
+}
+
+impl BuildingDefinition {
+    pub fn bounding_box(&self) -> Vec3 {
+        self.bounding_box.into()
+    }
+
+    pub fn origin(&self) -> Vec2 {
+        self.origin.into()
+    }
+}
+
+/// Which species definition an entity was spawned from
+pub struct Species(pub Handle<SpeciesDefinition>);
+
+/// Which creature definition an entity was spawned from
+pub struct Archetype(pub Handle<CreatureDefinition>);
+
+/// Which building definition an entity was spawned from
+pub struct BuildingType(pub Handle<BuildingDefinition>);
+
+struct RonAssetLoader<T> {
+    extensions: &'static [&'static str],
+    marker: PhantomData<fn() -> T>,
+}
+
+impl<T> RonAssetLoader<T> {
+    fn new(extensions: &'static [&'static str]) -> Self {
+        Self {
+            extensions,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<T: Asset + DeserializeOwned> AssetLoader for RonAssetLoader<T> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), anyhow::Error>> {
+        Box::pin(async move {
+            let definition = ron::de::from_bytes::<T>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(definition));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        self.extensions
+    }
+}
+
+fn modified_handles<T: Asset>(events: &mut EventReader<AssetEvent<T>>) -> Vec<Handle<T>> {
+    events
+        .iter()
+        .filter_map(|event| match event {
+            AssetEvent::Modified { handle } => Some(handle.clone()),
+            _ => None,
+        })
+        .collect()
+}
+
+fn reload_species(
+    mut events: EventReader<AssetEvent<SpeciesDefinition>>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    mut tree_query: Query<(&Species, &mut Seeder, &mut PlantSize), With<Tree>>,
+) {
+    for handle in modified_handles(&mut events) {
+        let definition = match species_definitions.get(&handle) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        println!("Species '{}' definition is reloaded", definition.name);
+
+        for (Species(species), mut seeder, mut plant_size) in tree_query.iter_mut() {
+            if *species == handle {
+                seeder.seed_growth_per_second = definition.seed_growth_per_second.clone();
+                seeder.survival_probability = definition.survival_probability;
+                plant_size.max = definition.max_size;
+                plant_size.growth_per_second = definition.growth_per_second;
+            }
+        }
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn reload_creatures(
+    mut events: EventReader<AssetEvent<CreatureDefinition>>,
+    creature_definitions: Res<Assets<CreatureDefinition>>,
+    mut creature_query: Query<(
+        &Archetype,
+        &mut Walker,
+        &mut Perception,
+        Option<&mut ResourceCarrier>,
+        Option<&mut Attacker>,
+    )>,
+) {
+    for handle in modified_handles(&mut events) {
+        let definition = match creature_definitions.get(&handle) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        println!("Creature '{}' definition is reloaded", definition.name);
+
+        for (Archetype(archetype), mut walker, mut perception, carrier, attacker) in
+            creature_query.iter_mut()
+        {
+            if *archetype == handle {
+                walker.acceleration = definition.acceleration;
+                walker.deceleration = definition.deceleration;
+                walker.max_speed = definition.max_speed;
+                perception.radius = definition.perception_radius;
+                if let Some(mut carrier) = carrier {
+                    carrier.max_wood = definition.max_wood;
+                }
+                if let Some(mut attacker) = attacker {
+                    attacker.damage = definition.melee.damage;
+                    attacker.range = definition.melee.range;
+                    attacker.cooldown_hours = definition.melee.cooldown_hours;
+                }
+            }
+        }
+    }
+}
+
+fn reload_buildings(
+    mut events: EventReader<AssetEvent<BuildingDefinition>>,
+    building_definitions: Res<Assets<BuildingDefinition>>,
+    mut building_query: Query<(
+        Entity,
+        &BuildingType,
+        &mut PhysicalObject,
+        Option<&mut LivingSpace>,
+    )>,
+    mut ev_living_space_available: EventWriter<LivingSpaceAvailableEvent>,
+) {
+    for handle in modified_handles(&mut events) {
+        let definition = match building_definitions.get(&handle) {
+            Some(definition) => definition,
+            None => continue,
+        };
+        println!("Building '{}' definition is reloaded", definition.name);
+
+        for (building_id, BuildingType(building_type), mut physical_object, living_space) in
+            building_query.iter_mut()
+        {
+            if *building_type == handle {
+                // the shadow keeps the size it was spawned with
+                physical_object.footprint = definition.footprint;
+                if let Some(mut living_space) = living_space {
+                    // residents beyond a smaller house stay until they leave
+                    for _ in living_space.max_people..definition.max_people {
+                        ev_living_space_available.send(LivingSpaceAvailableEvent {
+                            residence_id: building_id,
+                        });
+                    }
+                    living_space.max_people = definition.max_people;
+                }
+            }
+        }
+    }
+}
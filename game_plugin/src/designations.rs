@@ -0,0 +1,357 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+
+use crate::{
+    behaviour::{CheckTaskEvent, Task, TravelToPosition, TravelToTarget},
+    blueprints::SpawnRequest,
+    combat::Defender,
+    idle::Idling,
+    land_grid::LandGrid,
+    layers::Shadow,
+    loading::Materials,
+    mood::{Mood, Needs},
+    physics::PhysicalObject,
+    relationships::Relationships,
+    residence::Villager,
+    roads::{pave, RoadWorks},
+    time_cycle::TimeCycle,
+    tree_cutting::{LogPile, ResourceCarrier, ResourceStorage, TaskQue},
+    village::WorkPriorities,
+    weather::Weather,
+    GameState,
+};
+
+// wood a villager picks up from a felled tree before taking it to a storage
+const WOOD_PER_FELLING_TRIP: f32 = 5.0;
+// villagers rather fell a tree next to the one a friend is felling, even if it is this much further away
+const WORK_TOGETHER_DISTANCE: f32 = 30.0;
+const WORK_TOGETHER_BONUS: f32 = 60.0;
+
+/// A tree the player wants cut down, see `PlayerCommand::DesignateFelling`
+pub struct MarkedForFelling;
+
+/// Drawn on the ground under a tree marked for felling
+pub struct FellingMark;
+
+/// A blueprint placed by the player or `RoadWorks`, the building is spawned or the road paved
+/// once the wood is delivered and the work done
+pub struct ConstructionSite {
+    pub wood_required: f32,
+    pub wood_delivered: f32,
+    pub work_hours_left: f32,
+}
+
+impl ConstructionSite {
+    pub fn wood_missing(&self) -> f32 {
+        (self.wood_required - self.wood_delivered).max(0.0)
+    }
+}
+
+/// A villager working on a construction site
+pub struct Constructing {
+    pub site_id: Entity,
+}
+
+/// A storage on a hex tile of a stockpile zone painted by the player
+pub struct StockpileZone;
+
+pub struct DesignationsPlugin;
+
+impl Plugin for DesignationsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(assign_work.system())
+                .with_system(construct.system().before("spawn"))
+                .with_system(show_felling_marks.system())
+                .with_system(show_stored_wood.system()),
+        );
+    }
+}
+
+// villagers with nothing better to do than idling take on the designated work, building comes first
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn assign_work(
+    mut commands: Commands,
+    time_cycle: Res<TimeCycle>,
+    weather: Res<Weather>,
+    work_priorities: Res<WorkPriorities>,
+    relationships: Res<Relationships>,
+    mut villager_query: Query<
+        (
+            Entity,
+            &PhysicalObject,
+            &ResourceCarrier,
+            &Needs,
+            &mut TaskQue,
+        ),
+        (With<Villager>, Without<Defender>),
+    >,
+    marked_tree_query: Query<(Entity, &PhysicalObject), With<MarkedForFelling>>,
+    log_pile_query: Query<(Entity, &PhysicalObject), With<LogPile>>,
+    site_query: Query<(Entity, &PhysicalObject, &ConstructionSite)>,
+    storage_query: Query<(Entity, &PhysicalObject, &ResourceStorage)>,
+) {
+    // villagers sleep at night
+    if time_cycle.is_night() {
+        return;
+    }
+
+    let mut claimed = HashSet::new();
+    let mut promised_wood: HashMap<Entity, f32> = HashMap::new();
+    // who fells which tree
+    let mut felling = Vec::new();
+    for (worker_id, .., task_que) in villager_query.iter_mut() {
+        for task in task_que.0.iter() {
+            match task {
+                Task::CutTree(target_id) => {
+                    claimed.insert(*target_id);
+                    felling.push((worker_id, *target_id));
+                }
+                Task::Deliver(target_id) => {
+                    claimed.insert(*target_id);
+                }
+                Task::TakeWood(source_id, amount) => {
+                    claimed.insert(*source_id);
+                    *promised_wood.entry(*source_id).or_insert(0.0) += amount;
+                }
+                _ => {}
+            }
+        }
+    }
+    let has_storage = storage_query.iter().next().is_some();
+    let can_fell = work_priorities.woodcutting > 0 && !weather.halts_outdoor_work();
+
+    for (villager_id, villager, carrier, needs, mut task_que) in villager_query.iter_mut() {
+        // hungry villagers go for a meal first
+        if !task_que.0.iter().all(Task::is_idle) || needs.is_hungry() {
+            continue;
+        }
+
+        let mut work = None;
+        if carrier.wood > 0.0 && has_storage {
+            work = Some(vec![Task::DropOffResources]);
+        }
+        if work.is_none() && carrier.max_wood > 0.0 {
+            let site = nearest(
+                villager,
+                site_query
+                    .iter()
+                    .filter(|(site_id, _, site)| {
+                        site.wood_missing() > 0.0 && !claimed.contains(site_id)
+                    })
+                    .map(|(site_id, site_object, _)| (site_id, site_object)),
+            );
+            let storage = nearest(
+                villager,
+                storage_query
+                    .iter()
+                    .filter(|(storage_id, _, storage)| {
+                        storage.wood > *promised_wood.get(storage_id).unwrap_or(&0.0)
+                    })
+                    .map(|(storage_id, storage_object, _)| (storage_id, storage_object)),
+            );
+            if let (Some(site_id), Some(storage_id)) = (site, storage) {
+                let (.., site) = site_query.get(site_id).unwrap();
+                let (.., storage) = storage_query.get(storage_id).unwrap();
+                let promised = promised_wood.entry(storage_id).or_insert(0.0);
+                let amount = site
+                    .wood_missing()
+                    .min(carrier.max_wood)
+                    .min(storage.wood - *promised);
+                *promised += amount;
+                claimed.insert(site_id);
+                work = Some(vec![
+                    Task::TakeWood(storage_id, amount),
+                    Task::Deliver(site_id),
+                    Task::DropOffResources,
+                ]);
+            }
+        }
+        if work.is_none() {
+            let site = nearest(
+                villager,
+                site_query
+                    .iter()
+                    .filter(|(_, _, site)| site.wood_missing() <= 0.0)
+                    .map(|(site_id, site_object, _)| (site_id, site_object)),
+            );
+            work = site.map(|site_id| vec![Task::Build(site_id)]);
+        }
+        if work.is_none() && can_fell && has_storage {
+            let tree = marked_tree_query
+                .iter()
+                .filter(|(tree_id, _)| !claimed.contains(tree_id))
+                .map(|(tree_id, tree)| {
+                    let is_near_friend = felling.iter().any(|(worker_id, other_tree_id)| {
+                        relationships.is_friendly(villager_id, *worker_id)
+                            && marked_tree_query
+                                .get(*other_tree_id)
+                                .map_or(false, |(_, other_tree)| {
+                                    tree.gap_to(other_tree) <= WORK_TOGETHER_DISTANCE
+                                })
+                    });
+                    let bonus = if is_near_friend {
+                        WORK_TOGETHER_BONUS
+                    } else {
+                        0.0
+                    };
+                    (tree_id, villager.gap_to(tree) - bonus)
+                })
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+                .map(|(tree_id, _)| tree_id);
+            if let Some(tree_id) = tree {
+                claimed.insert(tree_id);
+                felling.push((villager_id, tree_id));
+                work = Some(vec![
+                    Task::CutTree(tree_id),
+                    Task::PickUpWood(WOOD_PER_FELLING_TRIP),
+                    Task::DropOffResources,
+                ]);
+            }
+        }
+        if work.is_none() && has_storage && carrier.max_wood > 0.0 {
+            let log_pile = nearest(
+                villager,
+                log_pile_query
+                    .iter()
+                    .filter(|(log_pile_id, _)| !claimed.contains(log_pile_id)),
+            );
+            if let Some(log_pile_id) = log_pile {
+                claimed.insert(log_pile_id);
+                work = Some(vec![
+                    Task::TakeWood(log_pile_id, carrier.max_wood),
+                    Task::DropOffResources,
+                ]);
+            }
+        }
+        let work = match work {
+            Some(work) => work,
+            None => continue,
+        };
+
+        if !task_que.0.is_empty() {
+            commands
+                .entity(villager_id)
+                .remove::<TravelToTarget>()
+                .remove::<TravelToPosition>()
+                .remove::<Idling>();
+        }
+        task_que.0 = work.into_iter().collect();
+    }
+}
+
+fn nearest<'a>(
+    to: &PhysicalObject,
+    objects: impl Iterator<Item = (Entity, &'a PhysicalObject)>,
+) -> Option<Entity> {
+    objects
+        .map(|(entity, object)| (entity, to.gap_to(object)))
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(entity, _)| entity)
+}
+
+#[allow(clippy::too_many_arguments)]
+fn construct(
+    mut commands: Commands,
+    time_cycle: Res<TimeCycle>,
+    weather: Res<Weather>,
+    mut land_grid: ResMut<LandGrid>,
+    mut site_query: Query<(
+        Entity,
+        &PhysicalObject,
+        &mut ConstructionSite,
+        Option<&RoadWorks>,
+    )>,
+    builder_query: Query<(Entity, &Constructing, Option<&Mood>)>,
+    mut ev_check_task: EventWriter<CheckTaskEvent>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    if weather.halts_outdoor_work() {
+        return;
+    }
+
+    let hours = time_cycle.delta_hours();
+    for (_, constructing, mood) in builder_query.iter() {
+        if let Ok((_, _, mut site, _)) = site_query.get_mut(constructing.site_id) {
+            if site.wood_missing() <= 0.0 {
+                site.work_hours_left -= hours * mood.map_or(1.0, Mood::work_speed);
+            }
+        }
+    }
+
+    for (site_id, site_object, site, road_works) in site_query.iter_mut() {
+        if site.wood_missing() > 0.0 || site.work_hours_left > 0.0 {
+            continue;
+        }
+        commands.entity(site_id).despawn_recursive();
+        if let Some(road_works) = road_works {
+            pave(&mut land_grid, road_works.hex);
+        } else {
+            println!("A house has been built");
+            spawn_requests.send(SpawnRequest::House {
+                position: site_object.position,
+            });
+        }
+        // the builders are done
+        for (builder_id, constructing, _) in builder_query.iter() {
+            if constructing.site_id == site_id {
+                ev_check_task.send(CheckTaskEvent(builder_id));
+            }
+        }
+    }
+}
+
+// trees out of view have no sprites, they get their mark once they come into view
+fn show_felling_marks(
+    mut commands: Commands,
+    materials: Res<Materials>,
+    marked_tree_query: Query<(Entity, &Children), With<MarkedForFelling>>,
+    felling_mark_query: Query<(Entity, &Parent), With<FellingMark>>,
+    tree_query: Query<(), With<MarkedForFelling>>,
+) {
+    for (felling_mark_id, parent) in felling_mark_query.iter() {
+        if tree_query.get(parent.0).is_err() {
+            commands.entity(felling_mark_id).despawn_recursive();
+        }
+    }
+
+    for (tree_id, children) in marked_tree_query.iter() {
+        if children.is_empty()
+            || children
+                .iter()
+                .any(|child| felling_mark_query.get(*child).is_ok())
+        {
+            continue;
+        }
+        commands.entity(tree_id).with_children(|parent| {
+            parent
+                .spawn_bundle(SpriteBundle {
+                    material: materials.felling_mark.clone(),
+                    sprite: Sprite::new(Vec2::new(12.0, 6.0)),
+                    ..Default::default()
+                })
+                .insert(Shadow)
+                .insert(FellingMark);
+        });
+    }
+}
+
+// empty stockpile zones only show on the land grid
+#[allow(clippy::type_complexity)]
+fn show_stored_wood(
+    zone_query: Query<
+        (&ResourceStorage, &Children),
+        (With<StockpileZone>, Changed<ResourceStorage>),
+    >,
+    mut visible_query: Query<&mut Visible>,
+) {
+    for (storage, children) in zone_query.iter() {
+        for child in children.iter() {
+            if let Ok(mut visible) = visible_query.get_mut(*child) {
+                visible.is_visible = storage.wood > 0.0;
+            }
+        }
+    }
+}
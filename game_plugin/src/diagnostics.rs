@@ -0,0 +1,115 @@
+use bevy::{
+    diagnostic::{Diagnostics, FrameTimeDiagnosticsPlugin},
+    prelude::*,
+};
+
+use crate::{
+    actions::Actions,
+    enemies::Hostile,
+    plants::Tree,
+    residence::Villager,
+    tree_cutting::{LogPile, ResourceStorage},
+    village::LivingSpace,
+    GameState,
+};
+
+struct DiagnosticsOverlay;
+
+/// Frame times and entity counts per type, toggled with F3
+pub struct DiagnosticsPlugin;
+
+impl Plugin for DiagnosticsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_plugin(FrameTimeDiagnosticsPlugin::default())
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_overlay.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(toggle_overlay.system().label("toggle_diagnostics"))
+                    .with_system(sync_overlay.system().after("toggle_diagnostics")),
+            );
+    }
+}
+
+fn remove_overlay(mut commands: Commands, overlay_query: Query<Entity, With<DiagnosticsOverlay>>) {
+    for overlay_id in overlay_query.iter() {
+        commands.entity(overlay_id).despawn_recursive();
+    }
+}
+
+fn toggle_overlay(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    asset_server: Res<AssetServer>,
+    overlay_query: Query<Entity, With<DiagnosticsOverlay>>,
+) {
+    if !actions.toggle_diagnostics {
+        return;
+    }
+    if let Some(overlay_id) = overlay_query.iter().next() {
+        commands.entity(overlay_id).despawn_recursive();
+        return;
+    }
+    commands
+        .spawn_bundle(TextBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    bottom: Val::Px(5.0),
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            text: Text::with_section(
+                "",
+                TextStyle {
+                    font: asset_server.get_handle("fonts/FiraSans-Bold.ttf"),
+                    font_size: 16.0,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+                Default::default(),
+            ),
+            ..Default::default()
+        })
+        .insert(DiagnosticsOverlay);
+}
+
+#[allow(clippy::too_many_arguments)]
+fn sync_overlay(
+    diagnostics: Res<Diagnostics>,
+    mut overlay_query: Query<&mut Text, With<DiagnosticsOverlay>>,
+    entity_query: Query<Entity>,
+    tree_query: Query<(), With<Tree>>,
+    villager_query: Query<(), With<Villager>>,
+    hostile_query: Query<(), With<Hostile>>,
+    house_query: Query<(), With<LivingSpace>>,
+    storage_query: Query<(), With<ResourceStorage>>,
+    log_pile_query: Query<(), With<LogPile>>,
+    sprite_query: Query<(), With<Sprite>>,
+) {
+    let mut text = match overlay_query.iter_mut().next() {
+        Some(text) => text,
+        None => return,
+    };
+    let measurement = |id| {
+        diagnostics
+            .get(id)
+            .and_then(|diagnostic| diagnostic.average())
+            .unwrap_or(0.0)
+    };
+    text.sections[0].value = format!(
+        "FPS {:.0} ({:.1} ms)\nEntities {}  Sprites {}\nTrees {}  Villagers {}  Wolves {}\nHouses {}  Storages {}  Log piles {}",
+        measurement(FrameTimeDiagnosticsPlugin::FPS),
+        measurement(FrameTimeDiagnosticsPlugin::FRAME_TIME) * 1000.0,
+        entity_query.iter().count(),
+        sprite_query.iter().count(),
+        tree_query.iter().count(),
+        villager_query.iter().count(),
+        hostile_query.iter().count(),
+        house_query.iter().count(),
+        storage_query.iter().count(),
+        log_pile_query.iter().count(),
+    );
+}
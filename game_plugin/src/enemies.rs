@@ -0,0 +1,271 @@
+use bevy::prelude::*;
+use rand::{seq::SliceRandom, Rng};
+
+use crate::{
+    animation::Animation,
+    behaviour::{Task, TravelToPosition, TravelToTarget, Walker},
+    blueprints::SpawnRequest,
+    combat::{combat_bundle, Defender},
+    creatures::{Creature, Perception, Personality},
+    definitions::{Archetype, CreatureDefinition},
+    idle::Idling,
+    loading::{Materials, SpriteSheets},
+    names::VillagerName,
+    physics::{Mobile, PhysicalObject, Velocity},
+    plants::Tree,
+    residence::Villager,
+    sprite_helpers::spawn_animated_sprite_bundles,
+    time_cycle::TimeCycle,
+    tree_cutting::{CuttingTree, TaskQue},
+    village::{village_centre, Building},
+    world_gen::{SimParams, SimRng},
+    GameState,
+};
+
+// wolves come out of the forest at least this far from the village
+const FOREST_EDGE_DISTANCE: f32 = 150.0;
+// trees closest to the village, out of which wolves pick where to come out
+const FOREST_EDGE_TREES: usize = 20;
+const WOLVES_PER_NIGHT: usize = 2;
+const MAX_WOLVES: usize = 6;
+// wolves sneak up on the village in steps this long
+const PROWL_STEP: f32 = 60.0;
+// close enough to the prey or the den to stop there
+const CATCH_DISTANCE: f32 = 4.0;
+// scared villagers run this far before looking around again
+const FLEE_DISTANCE: f32 = 60.0;
+
+/// A creature hunting villagers at night, it goes back to its den in the forest at dawn
+pub struct Hostile {
+    pub den: Vec2,
+    pub prey: Option<Entity>,
+}
+
+pub struct EnemiesPlugin;
+
+impl Plugin for EnemiesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(spawn_wolves_at_night.system())
+                .with_system(hunt_villagers.system())
+                .with_system(flee_from_enemies.system()),
+        );
+    }
+}
+
+pub fn spawn_wolf(
+    commands: &mut Commands,
+    materials: &Res<Materials>,
+    sprite_sheets: &Res<SpriteSheets>,
+    position: Vec2,
+    archetype: &Handle<CreatureDefinition>,
+    creature_definition: &CreatureDefinition,
+    sim_params: &Res<SimParams>,
+) -> Entity {
+    spawn_animated_sprite_bundles(
+        commands,
+        PhysicalObject {
+            position,
+            footprint: creature_definition.footprint,
+        },
+        creature_definition.bounding_box(),
+        sprite_sheets.wolf.clone(),
+        materials.shadow.clone(),
+        &sim_params.world_rect,
+    )
+    .insert(Animation::new(position))
+    .insert(Creature {
+        personality: Personality {
+            sociability: 0.0,
+            homeliness: 0.0,
+        },
+    })
+    .insert(Archetype(archetype.clone()))
+    .insert(Mobile(Velocity::default()))
+    .insert(Walker {
+        acceleration: creature_definition.acceleration,
+        deceleration: creature_definition.deceleration,
+        max_speed: creature_definition.max_speed,
+    })
+    .insert(Perception {
+        radius: creature_definition.perception_radius,
+    })
+    .insert_bundle(combat_bundle(creature_definition))
+    .insert(Hostile {
+        den: position,
+        prey: None,
+    })
+    .id()
+}
+
+// a pack comes out of the forest edge closest to the village every nightfall
+fn spawn_wolves_at_night(
+    time_cycle: Res<TimeCycle>,
+    mut was_night: Local<bool>,
+    building_query: Query<&PhysicalObject, With<Building>>,
+    tree_query: Query<&PhysicalObject, With<Tree>>,
+    wolf_query: Query<(), With<Hostile>>,
+    mut sim_rng: ResMut<SimRng>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    let is_nightfall = time_cycle.is_night() && !*was_night;
+    *was_night = time_cycle.is_night();
+    if !is_nightfall {
+        return;
+    }
+
+    let centre = match village_centre(building_query.iter()) {
+        Some(centre) => centre,
+        None => return,
+    };
+    let mut forest_edge: Vec<Vec2> = tree_query
+        .iter()
+        .map(|tree| tree.position)
+        .filter(|position| position.distance(centre) >= FOREST_EDGE_DISTANCE)
+        .collect();
+    forest_edge.sort_by(|a, b| a.distance(centre).partial_cmp(&b.distance(centre)).unwrap());
+    forest_edge.truncate(FOREST_EDGE_TREES);
+
+    let count = WOLVES_PER_NIGHT.min(MAX_WOLVES.saturating_sub(wolf_query.iter().count()));
+    for tree_position in forest_edge.choose_multiple(&mut sim_rng.0, count) {
+        // out from behind the tree
+        spawn_requests.send(SpawnRequest::Wolf {
+            position: *tree_position + Vec2::new(0.0, -8.0),
+        });
+    }
+    if count > 0 {
+        println!("{} wolves come out of the forest", count);
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn hunt_villagers(
+    mut commands: Commands,
+    time_cycle: Res<TimeCycle>,
+    mut wolf_query: Query<(
+        Entity,
+        &PhysicalObject,
+        &Perception,
+        &mut Hostile,
+        Option<&TravelToTarget>,
+        Option<&TravelToPosition>,
+    )>,
+    villager_query: Query<(Entity, &PhysicalObject), With<Villager>>,
+    building_query: Query<&PhysicalObject, With<Building>>,
+    mut sim_rng: ResMut<SimRng>,
+) {
+    let rng = &mut sim_rng.0;
+
+    for (wolf_id, wolf, perception, mut hostile, travel_to_target, travel_to_position) in
+        wolf_query.iter_mut()
+    {
+        if !time_cycle.is_night() {
+            if wolf.position.distance(hostile.den) < CATCH_DISTANCE {
+                commands.entity(wolf_id).despawn_recursive();
+            } else if travel_to_position.map_or(true, |travel| travel.position != hostile.den) {
+                hostile.prey = None;
+                commands
+                    .entity(wolf_id)
+                    .remove::<TravelToTarget>()
+                    .insert(TravelToPosition {
+                        position: hostile.den,
+                    });
+            }
+            continue;
+        }
+
+        let prey = villager_query
+            .iter()
+            .map(|(villager_id, villager)| (villager_id, wolf.gap_to(villager)))
+            .filter(|(_, gap)| *gap <= perception.radius)
+            .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+
+        match prey {
+            Some((prey_id, gap)) => {
+                let is_chasing =
+                    travel_to_target.map_or(false, |travel| travel.target_id == prey_id);
+                if !is_chasing && gap > CATCH_DISTANCE {
+                    if hostile.prey != Some(prey_id) {
+                        println!("A wolf is after a villager");
+                    }
+                    commands
+                        .entity(wolf_id)
+                        .remove::<TravelToPosition>()
+                        .insert(TravelToTarget {
+                            target_id: prey_id,
+                            last_target_position: None,
+                        });
+                }
+                hostile.prey = Some(prey_id);
+            }
+            None => {
+                hostile.prey = None;
+                if travel_to_target.is_some() {
+                    commands.entity(wolf_id).remove::<TravelToTarget>();
+                }
+                if travel_to_target.is_some() || travel_to_position.is_none() {
+                    // prowls towards the village, zigzagging a little
+                    let towards = village_centre(building_query.iter())
+                        .map_or(Vec2::ZERO, |centre| {
+                            (centre - wolf.position).normalize_or_zero()
+                        });
+                    let zigzag = Vec2::new(rng.gen_range(-0.5..0.5), rng.gen_range(-0.5..0.5));
+                    commands.entity(wolf_id).insert(TravelToPosition {
+                        position: wolf.position + (towards + zigzag) * PROWL_STEP,
+                    });
+                }
+            }
+        }
+    }
+}
+
+// villagers noticing an enemy drop what they are doing and run away from it, unless they are drafted to fight
+#[allow(clippy::type_complexity)]
+fn flee_from_enemies(
+    mut commands: Commands,
+    sim_params: Res<SimParams>,
+    mut villager_query: Query<
+        (
+            Entity,
+            &VillagerName,
+            &PhysicalObject,
+            &Perception,
+            &mut TaskQue,
+        ),
+        (With<Villager>, Without<Defender>),
+    >,
+    enemy_query: Query<&PhysicalObject, With<Hostile>>,
+) {
+    let world_rect = &sim_params.world_rect;
+    for (villager_id, name, villager, perception, mut task_que) in villager_query.iter_mut() {
+        if matches!(task_que.0.front(), Some(Task::FleeTo(_))) {
+            continue;
+        }
+
+        let threat = enemy_query
+            .iter()
+            .filter(|enemy| villager.gap_to(enemy) <= perception.radius)
+            .min_by(|a, b| villager.gap_to(a).partial_cmp(&villager.gap_to(b)).unwrap());
+        if let Some(enemy) = threat {
+            let mut away = (villager.position - enemy.position).normalize_or_zero();
+            if away == Vec2::ZERO {
+                away = Vec2::Y;
+            }
+            let destination = (villager.position + away * FLEE_DISTANCE).clamp(
+                world_rect.position - world_rect.size / 2.0,
+                world_rect.position + world_rect.size / 2.0,
+            );
+
+            // whatever was being done is picked up again once safe
+            commands
+                .entity(villager_id)
+                .remove::<TravelToTarget>()
+                .remove::<TravelToPosition>()
+                .remove::<CuttingTree>()
+                .remove::<Idling>();
+            task_que.0.push_front(Task::FleeTo(destination));
+            println!("{} flees from a wolf", name.full());
+        }
+    }
+}
@@ -0,0 +1,31 @@
+//! Worlds without a window, rendering or audio, see `scenarios` and `benchmarks`
+
+use bevy::{asset::AssetPlugin, prelude::*};
+
+use crate::{
+    land_grid::LandGrid,
+    ordered_executor::run_in_order,
+    time_cycle::{TimeCycle, TICK_SECONDS},
+    weather::Weather,
+    world_gen::{SimParams, SimRng},
+};
+
+/// The resources of a world of `sim_params`, without any of its plugins.
+/// Every update is a tick as long as a frame of the game however long it takes to run,
+/// and the systems run in the same order every time, so that the same seed plays out the same way.
+pub fn build_headless(sim_params: SimParams) -> AppBuilder {
+    let mut time_cycle = TimeCycle::default();
+    time_cycle.set_fixed_step(Some(TICK_SECONDS));
+
+    let mut app_builder = App::build();
+    app_builder
+        .add_plugins(MinimalPlugins)
+        .add_plugin(AssetPlugin)
+        .insert_resource(LandGrid::new(&sim_params))
+        .insert_resource(SimRng::new(sim_params.seed))
+        .insert_resource(sim_params)
+        .insert_resource(time_cycle)
+        .init_resource::<Weather>();
+    run_in_order(&mut app_builder);
+    app_builder
+}
@@ -0,0 +1,325 @@
+use std::ops::{Add, Mul, Sub};
+
+/// Axial coordinates of a flat topped hexagon. The third cube coordinate is implied by q + r + s = 0.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Default)]
+pub struct Hex {
+    pub q: i32,
+    pub r: i32,
+}
+
+// starting with the bottom right neighbour and going counter clockwise
+const DIRECTIONS: [Hex; 6] = [
+    Hex { q: 1, r: 0 },
+    Hex { q: 1, r: -1 },
+    Hex { q: 0, r: -1 },
+    Hex { q: -1, r: 0 },
+    Hex { q: -1, r: 1 },
+    Hex { q: 0, r: 1 },
+];
+
+impl Hex {
+    pub fn new(q: i32, r: i32) -> Self {
+        Self { q, r }
+    }
+
+    pub fn s(&self) -> i32 {
+        -self.q - self.r
+    }
+
+    pub fn length(&self) -> i32 {
+        (self.q.abs() + self.r.abs() + self.s().abs()) / 2
+    }
+
+    pub fn distance(&self, other: Hex) -> i32 {
+        (*self - other).length()
+    }
+
+    pub fn direction(direction: usize) -> Hex {
+        DIRECTIONS[direction % 6]
+    }
+
+    pub fn neighbour(&self, direction: usize) -> Hex {
+        *self + Hex::direction(direction)
+    }
+
+    pub fn neighbours(&self) -> Vec<Hex> {
+        DIRECTIONS
+            .iter()
+            .map(|direction| *self + *direction)
+            .collect()
+    }
+
+    /// Hexes exactly `radius` steps away, walking around the ring
+    pub fn ring(&self, radius: i32) -> Vec<Hex> {
+        if radius <= 0 {
+            return vec![*self];
+        }
+
+        let mut hex = *self + Hex::direction(4) * radius;
+        let mut ring = Vec::with_capacity(6 * radius as usize);
+        for direction in 0..6 {
+            for _ in 0..radius {
+                ring.push(hex);
+                hex = hex.neighbour(direction);
+            }
+        }
+        ring
+    }
+
+    /// All hexes within `radius` steps, including this one
+    pub fn range(&self, radius: i32) -> Vec<Hex> {
+        let mut hexes = vec![];
+        for q in -radius..=radius {
+            for r in (-radius).max(-q - radius)..=radius.min(-q + radius) {
+                hexes.push(*self + Hex::new(q, r));
+            }
+        }
+        hexes
+    }
+
+    /// Hexes crossed by a straight line to `other`, both ends included
+    pub fn line_to(&self, other: Hex) -> Vec<Hex> {
+        let steps = self.distance(other);
+        if steps == 0 {
+            return vec![*self];
+        }
+
+        // nudging the ends keeps points lying exactly on an edge from rounding inconsistently
+        let from = FractionalHex::from(*self).nudge();
+        let to = FractionalHex::from(other).nudge();
+        (0..=steps)
+            .map(|step| from.lerp(&to, step as f32 / steps as f32).round())
+            .collect()
+    }
+}
+
+impl Add for Hex {
+    type Output = Hex;
+
+    fn add(self, other: Hex) -> Hex {
+        Hex::new(self.q + other.q, self.r + other.r)
+    }
+}
+
+impl Sub for Hex {
+    type Output = Hex;
+
+    fn sub(self, other: Hex) -> Hex {
+        Hex::new(self.q - other.q, self.r - other.r)
+    }
+}
+
+impl Mul<i32> for Hex {
+    type Output = Hex;
+
+    fn mul(self, factor: i32) -> Hex {
+        Hex::new(self.q * factor, self.r * factor)
+    }
+}
+
+/// A point in axial coordinates which is not necessarily at the center of a hexagon
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FractionalHex {
+    pub q: f32,
+    pub r: f32,
+}
+
+impl FractionalHex {
+    pub fn new(q: f32, r: f32) -> Self {
+        Self { q, r }
+    }
+
+    pub fn s(&self) -> f32 {
+        -self.q - self.r
+    }
+
+    fn nudge(&self) -> Self {
+        Self::new(self.q + 1e-4, self.r + 1e-4)
+    }
+
+    pub fn lerp(&self, other: &FractionalHex, t: f32) -> Self {
+        Self::new(
+            self.q + (other.q - self.q) * t,
+            self.r + (other.r - self.r) * t,
+        )
+    }
+
+    /// The hexagon containing this point
+    pub fn round(&self) -> Hex {
+        let mut q = self.q.round();
+        let mut r = self.r.round();
+        let s = self.s().round();
+
+        // the coordinate which was rounded the most is restored from the other two
+        let q_diff = (q - self.q).abs();
+        let r_diff = (r - self.r).abs();
+        let s_diff = (s - self.s()).abs();
+        if q_diff > r_diff && q_diff > s_diff {
+            q = -r - s;
+        } else if r_diff > s_diff {
+            r = -q - s;
+        }
+
+        Hex::new(q as i32, r as i32)
+    }
+}
+
+impl From<Hex> for FractionalHex {
+    fn from(hex: Hex) -> Self {
+        Self::new(hex.q as f32, hex.r as f32)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hexagon::HexagonBuilder;
+    use bevy::math::Vec2;
+    use rand::{prelude::StdRng, Rng, SeedableRng};
+
+    const CASES: usize = 500;
+
+    fn gen_hex<R: Rng>(rng: &mut R) -> Hex {
+        Hex::new(rng.gen_range(-50..50), rng.gen_range(-50..50))
+    }
+
+    #[test]
+    fn cube_coordinates_sum_to_zero() {
+        let rng = &mut StdRng::seed_from_u64(1);
+        for _ in 0..CASES {
+            let hex = gen_hex(rng);
+            assert_eq!(hex.q + hex.r + hex.s(), 0);
+        }
+    }
+
+    #[test]
+    fn distance_is_a_metric() {
+        let rng = &mut StdRng::seed_from_u64(2);
+        for _ in 0..CASES {
+            let (a, b, c) = (gen_hex(rng), gen_hex(rng), gen_hex(rng));
+            assert_eq!(a.distance(a), 0);
+            assert_eq!(a.distance(b), b.distance(a));
+            assert!(a.distance(c) <= a.distance(b) + b.distance(c));
+        }
+    }
+
+    #[test]
+    fn neighbours_are_one_step_away() {
+        let rng = &mut StdRng::seed_from_u64(3);
+        for _ in 0..CASES {
+            let hex = gen_hex(rng);
+            let neighbours = hex.neighbours();
+            assert_eq!(neighbours.len(), 6);
+            for neighbour in neighbours {
+                assert_eq!(hex.distance(neighbour), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn ring_has_hexes_at_radius() {
+        let rng = &mut StdRng::seed_from_u64(4);
+        for _ in 0..CASES {
+            let center = gen_hex(rng);
+            let radius = rng.gen_range(1..10);
+            let ring = center.ring(radius);
+            assert_eq!(ring.len(), 6 * radius as usize);
+            for hex in ring.iter() {
+                assert_eq!(center.distance(*hex), radius);
+            }
+            // every hex on the ring is adjacent to the next one
+            for (i, hex) in ring.iter().enumerate() {
+                assert_eq!(hex.distance(ring[(i + 1) % ring.len()]), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn range_has_all_hexes_within_radius() {
+        let rng = &mut StdRng::seed_from_u64(5);
+        for _ in 0..CASES {
+            let center = gen_hex(rng);
+            let radius = rng.gen_range(0..10);
+            let range = center.range(radius);
+            assert_eq!(range.len() as i32, 3 * radius * (radius + 1) + 1);
+            for hex in range.iter() {
+                assert!(center.distance(*hex) <= radius);
+            }
+        }
+    }
+
+    #[test]
+    fn line_is_connected_and_as_long_as_distance() {
+        let rng = &mut StdRng::seed_from_u64(6);
+        for _ in 0..CASES {
+            let (a, b) = (gen_hex(rng), gen_hex(rng));
+            let line = a.line_to(b);
+            assert_eq!(line.len() as i32, a.distance(b) + 1);
+            assert_eq!(line[0], a);
+            assert_eq!(line[line.len() - 1], b);
+            for pair in line.windows(2) {
+                assert_eq!(pair[0].distance(pair[1]), 1);
+            }
+        }
+    }
+
+    #[test]
+    fn world_position_round_trips() {
+        let rng = &mut StdRng::seed_from_u64(7);
+        for _ in 0..CASES {
+            let builder = HexagonBuilder::new(rng.gen_range(4.0..40.0));
+            let origin = Vec2::new(rng.gen_range(0.0..600.0), rng.gen_range(0.0..600.0));
+            let hex = gen_hex(rng);
+            let center = builder.hex_to_world(origin, hex);
+            assert_eq!(builder.world_to_hex(origin, center), hex);
+        }
+    }
+
+    #[test]
+    fn world_position_is_inside_its_hexagon() {
+        let rng = &mut StdRng::seed_from_u64(8);
+        for _ in 0..CASES {
+            let builder = HexagonBuilder::new(10.0);
+            let position = Vec2::new(rng.gen_range(-300.0..300.0), rng.gen_range(-300.0..300.0));
+            let hex = builder.world_to_hex(Vec2::ZERO, position);
+            let center = builder.hex_to_world(Vec2::ZERO, hex);
+            // no other hexagon center can be closer than the one containing the point
+            for neighbour in hex.neighbours() {
+                let other = builder.hex_to_world(Vec2::ZERO, neighbour);
+                assert!(position.distance(center) <= position.distance(other) + 1e-3);
+            }
+        }
+    }
+
+    #[test]
+    fn offset_coordinates_round_trip() {
+        let rng = &mut StdRng::seed_from_u64(9);
+        let builder = HexagonBuilder::new(10.0);
+        for _ in 0..CASES {
+            let (column, row) = (rng.gen_range(-50..50), rng.gen_range(-50..50));
+            let hex = builder.offset_to_hex(column, row);
+            assert_eq!(builder.hex_to_offset(hex), (column, row));
+        }
+    }
+
+    #[test]
+    fn hex_center_matches_grid_hexagon() {
+        let builder = HexagonBuilder::new(10.0);
+        let origin = Vec2::new(300.0, 300.0);
+        // columns are 30 apart and rows half a hexagon height apart, even rows are shifted right by 15
+        let half_height = 3.0f32.sqrt() * 10.0 / 2.0;
+        let expected_centers = [
+            ((0, 0), Vec2::new(-285.0, -300.0)),
+            ((0, 1), Vec2::new(-300.0, half_height - 300.0)),
+            ((2, 3), Vec2::new(-240.0, 3.0 * half_height - 300.0)),
+            ((5, 10), Vec2::new(-135.0, 10.0 * half_height - 300.0)),
+        ];
+        for ((column, row), expected) in expected_centers.iter() {
+            let hexagon = builder.get_hexagon_at(origin, *column, *row);
+            let center = hexagon.vertices.iter().fold(Vec2::ZERO, |sum, v| sum + *v) / 6.0;
+            assert!(center.distance(*expected) < 1e-3, "{:?} != {:?}", center, expected);
+            let hex_center = builder.hex_to_world(origin, builder.offset_to_hex(*column, *row));
+            assert!(hex_center.distance(*expected) < 1e-3, "{:?} != {:?}", hex_center, expected);
+        }
+    }
+}
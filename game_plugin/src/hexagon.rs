@@ -1,6 +1,10 @@
 use bevy::math::Vec2;
+use serde::{Deserialize, Serialize};
+
+use crate::hex_coords::{FractionalHex, Hex};
 
 // TODO: World should not use this REctangle, and actually be calculated around hexagon grid
+#[derive(Clone, Serialize, Deserialize)]
 pub struct Rectangle {
     pub position: Vec2,
     pub size: Vec2,
@@ -10,18 +14,7 @@ pub struct Hexagon {
     pub vertices: Vec<Vec2>,
 }
 
-impl Hexagon {
-    pub fn get_bounding_rectangle(&self) -> Rectangle {
-        Rectangle {
-            position: Vec2::new(self.vertices[0].x, self.vertices[1].y),
-            size: Vec2::new(
-                self.vertices[3].x - self.vertices[0].x,
-                self.vertices[4].y - self.vertices[2].y,
-            ),
-        }
-    }
-}
-
+#[derive(Clone, Serialize, Deserialize)]
 pub struct HexagonBuilder {
     size: f32,
     height: f32,
@@ -35,7 +28,45 @@ fn is_even(value: i32) -> bool {
 
 impl HexagonBuilder {
     pub fn get_hexagon_at(&self, origin: Vec2, column: i32, row: i32) -> Hexagon {
-        let pos = self.get_top_left(column, row) - origin;
+        self.get_hexagon(origin, self.offset_to_hex(column, row))
+    }
+
+    pub fn get_world_rect(&self, column_count: i32, row_count: i32) -> Rectangle {
+        let width = (3.0 * self.size * column_count as f32 + 0.5 * self.size).ceil();
+        let height = ((self.height * row_count as f32 + self.height) / 2.0).ceil();
+        Rectangle {
+            position: Vec2::new(0., 0.),
+            size: Vec2::new(width, height),
+        }
+    }
+
+    /// Center of the hexagon in world coordinates, `origin` is the same one as in `get_hexagon_at`
+    pub fn hex_to_world(&self, origin: Vec2, hex: Hex) -> Vec2 {
+        Vec2::new(
+            self.pointy_offset * hex.q as f32,
+            self.height * (hex.r as f32 + hex.q as f32 / 2.0) - self.height / 2.0,
+        ) - origin
+    }
+
+    pub fn world_to_hex(&self, origin: Vec2, position: Vec2) -> Hex {
+        let pos = position + origin + Vec2::new(0.0, self.height / 2.0);
+        let q = pos.x / self.pointy_offset;
+        let r = pos.y / self.height - q / 2.0;
+        FractionalHex::new(q, r).round()
+    }
+
+    // rows of the grid are shifted by half a hexagon, so every row holds every other q
+    pub fn offset_to_hex(&self, column: i32, row: i32) -> Hex {
+        let q = 2 * column + if is_even(row) { 1 } else { 0 };
+        Hex::new(q, (row + 1 - q) / 2)
+    }
+
+    pub fn hex_to_offset(&self, hex: Hex) -> (i32, i32) {
+        (hex.q.div_euclid(2), 2 * hex.r + hex.q - 1)
+    }
+
+    pub fn get_hexagon(&self, origin: Vec2, hex: Hex) -> Hexagon {
+        let pos = self.hex_to_world(origin, hex);
         Hexagon {
             vertices: self
                 .hexagon_shape
@@ -45,12 +76,26 @@ impl HexagonBuilder {
         }
     }
 
-    pub fn get_world_rect(&self, column_count: i32, row_count: i32) -> Rectangle {
-        let width = (3.0 * self.size * column_count as f32 + 0.5 * self.size).ceil();
-        let height = ((self.height * row_count as f32 + self.height) / 2.0).ceil();
+    /// Rectangle around the centers of a block of tiles in offset coordinates
+    pub fn get_block_rect(
+        &self,
+        origin: Vec2,
+        column: i32,
+        row: i32,
+        column_count: i32,
+        row_count: i32,
+    ) -> Rectangle {
+        let bottom_left = Vec2::new(
+            3.0 * self.size * column as f32,
+            self.height / 2.0 * row as f32,
+        ) - origin;
+        let size = Vec2::new(
+            3.0 * self.size * column_count as f32,
+            self.height / 2.0 * row_count as f32,
+        );
         Rectangle {
-            position: Vec2::new(0., 0.),
-            size: Vec2::new(width, height),
+            position: bottom_left + size / 2.0,
+            size,
         }
     }
 
@@ -83,16 +128,4 @@ impl HexagonBuilder {
             .collect(),
         }
     }
-
-    fn get_top_left(&self, column: i32, row: i32) -> Vec2 {
-        Vec2::new(
-            column as f32 * self.size * 3.0
-                + (if is_even(row) {
-                    self.pointy_offset
-                } else {
-                    0.0
-                }),
-            row as f32 * (self.height / 2.0),
-        )
-    }
 }
@@ -0,0 +1,228 @@
+use bevy::prelude::*;
+
+use crate::{
+    actions::Actions,
+    combat::Health,
+    creatures::{creature_name, Creature},
+    definitions::{Archetype, CreatureDefinition},
+    mood::{average_mood, Mood},
+    names::{Gender, VillagerName},
+    physics::PhysicalObject,
+    relationships::{Partner, Relationships},
+    residence::Villager,
+    tree_cutting::{ResourceStorage, TaskQue},
+    village::Village,
+    GameState,
+};
+
+// how close to a creature a click has to be to inspect it
+const INSPECT_DISTANCE: f32 = 10.0;
+
+/// The creature clicked last, shown in the inspector panel
+#[derive(Default)]
+pub struct Inspector {
+    pub creature_id: Option<Entity>,
+}
+
+struct HudUi;
+
+struct InspectorUi;
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Inspector>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(set_up_hud.system()),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Playing).with_system(remove_hud.system()))
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(inspect_creature.system())
+                    .with_system(sync_hud.system())
+                    .with_system(sync_inspector.system()),
+            );
+    }
+}
+
+fn set_up_hud(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    mut inspector: ResMut<Inspector>,
+) {
+    inspector.creature_id = None;
+    let font = asset_server.get_handle("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(panel_text(
+            &font,
+            24.0,
+            Rect {
+                top: Val::Px(5.0),
+                left: Val::Px(10.0),
+                ..Default::default()
+            },
+        ))
+        .insert(HudUi);
+    commands
+        .spawn_bundle(panel_text(
+            &font,
+            20.0,
+            Rect {
+                bottom: Val::Px(5.0),
+                left: Val::Px(10.0),
+                ..Default::default()
+            },
+        ))
+        .insert(InspectorUi);
+}
+
+fn panel_text(font: &Handle<Font>, font_size: f32, position: Rect<Val>) -> TextBundle {
+    TextBundle {
+        style: Style {
+            position_type: PositionType::Absolute,
+            position,
+            ..Default::default()
+        },
+        text: Text::with_section(
+            "",
+            TextStyle {
+                font: font.clone(),
+                font_size,
+                color: Color::rgb(0.9, 0.9, 0.9),
+            },
+            Default::default(),
+        ),
+        ..Default::default()
+    }
+}
+
+type HudUiFilter = Or<(With<HudUi>, With<InspectorUi>)>;
+
+fn remove_hud(mut commands: Commands, ui_query: Query<Entity, HudUiFilter>) {
+    for ui in ui_query.iter() {
+        commands.entity(ui).despawn_recursive();
+    }
+}
+
+// a click next to a creature inspects it, a click elsewhere closes the inspector
+fn inspect_creature(
+    actions: Res<Actions>,
+    mut inspector: ResMut<Inspector>,
+    creature_query: Query<(Entity, &PhysicalObject), With<Creature>>,
+) {
+    let position = match actions.inspected_position {
+        Some(position) => position,
+        None => return,
+    };
+    inspector.creature_id = creature_query
+        .iter()
+        .map(|(creature_id, creature)| (creature_id, creature.position.distance(position)))
+        .filter(|(_, distance)| *distance <= INSPECT_DISTANCE)
+        .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap())
+        .map(|(creature_id, _)| creature_id);
+}
+
+fn sync_hud(
+    village_query: Query<&Village>,
+    mood_query: Query<&Mood, With<Villager>>,
+    storage_query: Query<&ResourceStorage>,
+    mut ui_query: Query<&mut Text, With<HudUi>>,
+) {
+    let (village, mut text) = match (village_query.single(), ui_query.single_mut()) {
+        (Ok(village), Ok(text)) => (village, text),
+        _ => return,
+    };
+    let mood = average_mood(mood_query.iter())
+        .map_or("-".to_string(), |mood| format!("{:.0}%", mood * 100.0));
+    let stored_wood: f32 = storage_query.iter().map(|storage| storage.wood).sum();
+    text.sections[0].value = format!(
+        "Villagers {}  Homeless {}  Wood {:.0}  Mood {}",
+        village.habitants_count, village.homeless_count, stored_wood, mood
+    );
+}
+
+#[allow(clippy::type_complexity)]
+fn sync_inspector(
+    inspector: Res<Inspector>,
+    relationships: Res<Relationships>,
+    creature_definitions: Res<Assets<CreatureDefinition>>,
+    creature_query: Query<(
+        Option<&VillagerName>,
+        &Archetype,
+        Option<&Health>,
+        Option<&TaskQue>,
+        Option<&Mood>,
+        Option<&Partner>,
+    )>,
+    mut ui_query: Query<&mut Text, With<InspectorUi>>,
+) {
+    let mut text = match ui_query.single_mut() {
+        Ok(text) => text,
+        Err(_) => return,
+    };
+    let creature_id = match inspector.creature_id {
+        Some(creature_id) => creature_id,
+        None => {
+            text.sections[0].value.clear();
+            return;
+        }
+    };
+    let (name, archetype, health, task_que, mood, partner) = match creature_query.get(creature_id) {
+        Ok(creature) => creature,
+        Err(_) => {
+            text.sections[0].value.clear();
+            return;
+        }
+    };
+
+    let mut lines = vec![creature_name(name, archetype, &creature_definitions)];
+    if let Some(name) = name {
+        lines.push(
+            match name.gender {
+                Gender::Female => "Woman",
+                Gender::Male => "Man",
+            }
+            .to_string(),
+        );
+    }
+    if let Some(task) = task_que.and_then(|task_que| task_que.0.front()) {
+        lines.push(format!("Doing: {:?}", task));
+    }
+    if let Some(health) = health {
+        lines.push(format!("Health {:.0}/{:.0}", health.current, health.max));
+    }
+    if let Some(mood) = mood {
+        let factors = &mood.factors;
+        lines.push(format!("Mood {:.0}%", mood.value * 100.0));
+        lines.push(format!(
+            "Housing {:.0}%  Sleep {:.0}%  Food {:.0}%  Rest {:.0}%  Company {:.0}%",
+            factors.housing * 100.0,
+            factors.sleep * 100.0,
+            factors.food * 100.0,
+            factors.rest * 100.0,
+            factors.company * 100.0
+        ));
+        if factors.grief > 0.0 {
+            lines.push(format!("Grieving {:.0}%", factors.grief * 100.0));
+        }
+    }
+    let name_of = |id: Entity| {
+        creature_query
+            .get(id)
+            .map(|(name, archetype, ..)| creature_name(name, archetype, &creature_definitions))
+            .ok()
+    };
+    if let Some(partner_name) = partner.and_then(|partner| name_of(partner.0)) {
+        lines.push(format!("Partner: {}", partner_name));
+    }
+    let friend = relationships
+        .of(creature_id)
+        .filter(|(_, relationship)| relationship.is_friendly())
+        .max_by(|(_, a), (_, b)| a.closeness().partial_cmp(&b.closeness()).unwrap())
+        .and_then(|(friend_id, _)| name_of(friend_id));
+    if let Some(friend_name) = friend {
+        lines.push(format!("Best friend: {}", friend_name));
+    }
+    text.sections[0].value = lines.join("\n");
+}
@@ -0,0 +1,236 @@
+use bevy::prelude::*;
+use rand::{distributions::WeightedIndex, prelude::Distribution, seq::SliceRandom, Rng};
+
+use crate::{
+    behaviour::{CheckTaskEvent, Task},
+    creatures::Creature,
+    hexagon::Rectangle,
+    mood::Needs,
+    names::VillagerName,
+    physics::{PhysicalObject, Placement},
+    relationships::Relationships,
+    residence::Resident,
+    time_cycle::TimeCycle,
+    tree_cutting::TaskQue,
+    village::{village_centre, Building},
+    world_gen::{SimRng, PLACEMENT_ATTEMPTS},
+    GameState,
+};
+
+// how far idle villagers stroll from home or the village centre
+const STROLL_RADIUS: f32 = 40.0;
+const STROLL_IDLE_HOURS: f32 = 0.5;
+const CHAT_HOURS: f32 = 0.5;
+const SITTING_HOURS: f32 = 1.5;
+const MEAL_HOURS: f32 = 0.5;
+// sleepers wake up every hour to see whether it is morning yet
+const SLEEP_HOURS: f32 = 1.0;
+
+/// A place where villagers like to sit together, e.g. the stockpile in the village centre
+pub struct GatheringSpot;
+
+/// Waiting for `Task::Idle` to finish
+pub struct Idling {
+    pub hours_left: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+enum IdleBehaviour {
+    StayNearHome,
+    StayNearVillageCentre,
+    Socialize,
+    SitAtGatheringSpot,
+}
+
+pub struct IdlePlugin;
+
+impl Plugin for IdlePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(choose_idle_behaviour.system())
+                .with_system(wait_idle.system()),
+        );
+    }
+}
+
+// villagers without anything to do pick something to pass the time with, personality decides what
+#[allow(clippy::type_complexity)]
+fn choose_idle_behaviour(
+    time_cycle: Res<TimeCycle>,
+    mut sim_rng: ResMut<SimRng>,
+    relationships: Res<Relationships>,
+    mut villager_query: Query<(
+        Entity,
+        &Creature,
+        &VillagerName,
+        &PhysicalObject,
+        &mut TaskQue,
+        Option<&Resident>,
+        Option<&Needs>,
+    )>,
+    building_query: Query<&PhysicalObject, With<Building>>,
+    gathering_spot_query: Query<Entity, With<GatheringSpot>>,
+) {
+    let idle_villagers: Vec<Entity> = villager_query
+        .iter_mut()
+        .filter(|(.., task_que, _, _)| task_que.0.iter().all(Task::is_idle))
+        .map(|(entity, ..)| entity)
+        .collect();
+    let buildings: Vec<PhysicalObject> = building_query.iter().copied().collect();
+    let village_centre = village_centre(buildings.iter());
+    let gathering_spot = gathering_spot_query.iter().next();
+    let mut placement = Placement::new(buildings);
+    let rng = &mut sim_rng.0;
+
+    for (villager_id, creature, name, physical_object, mut task_que, resident, needs) in
+        villager_query.iter_mut()
+    {
+        if !task_que.0.is_empty() {
+            continue;
+        }
+
+        let home = resident.and_then(|resident| {
+            building_query
+                .get(resident.residence_id)
+                .ok()
+                .map(|_| resident.residence_id)
+        });
+
+        // meals are had at home, or at the gathering spot by the homeless
+        if needs.map_or(false, Needs::is_hungry) {
+            if let Some(place) = home.or(gathering_spot) {
+                task_que.0.push_back(Task::Visit(place));
+                task_que.0.push_back(Task::Idle(MEAL_HOURS));
+                continue;
+            }
+        }
+
+        if time_cycle.is_night() {
+            match home {
+                Some(home) => task_que.0.push_back(Task::Visit(home)),
+                None => {
+                    // the homeless sleep wherever they can around the village
+                    let center = village_centre.unwrap_or(physical_object.position);
+                    let spot = stroll_spot(rng, &mut placement, physical_object, center);
+                    task_que.0.push_back(Task::StrollTo(spot));
+                }
+            }
+            task_que.0.push_back(Task::Idle(SLEEP_HOURS));
+            continue;
+        }
+
+        let personality = creature.personality;
+        // friends are sought out more
+        let others: Vec<Entity> = idle_villagers
+            .iter()
+            .filter(|other_id| **other_id != villager_id)
+            .copied()
+            .collect();
+        let partner = others
+            .choose_weighted(rng, |other_id| {
+                relationships
+                    .get(villager_id, *other_id)
+                    .map_or(0.0, |relationship| relationship.closeness())
+                    + 0.1
+            })
+            .ok()
+            .copied();
+        let choices = [
+            (
+                IdleBehaviour::StayNearHome,
+                if home.is_some() {
+                    personality.homeliness
+                } else {
+                    0.0
+                },
+            ),
+            (
+                IdleBehaviour::StayNearVillageCentre,
+                // always an option, so that the weights never add up to nothing
+                1.0 - personality.homeliness + 0.1,
+            ),
+            (
+                IdleBehaviour::Socialize,
+                if partner.is_some() {
+                    personality.sociability
+                } else {
+                    0.0
+                },
+            ),
+            (
+                IdleBehaviour::SitAtGatheringSpot,
+                if gathering_spot.is_some() {
+                    personality.sociability / 2.0
+                } else {
+                    0.0
+                },
+            ),
+        ];
+        let weights = WeightedIndex::new(choices.iter().map(|(_, weight)| *weight)).unwrap();
+
+        match choices[weights.sample(rng)].0 {
+            IdleBehaviour::StayNearHome => {
+                let home_position = building_query.get(home.unwrap()).unwrap().position;
+                let spot = stroll_spot(rng, &mut placement, physical_object, home_position);
+                task_que.0.push_back(Task::StrollTo(spot));
+                task_que.0.push_back(Task::Idle(STROLL_IDLE_HOURS));
+            }
+            IdleBehaviour::StayNearVillageCentre => {
+                let center = village_centre.unwrap_or(physical_object.position);
+                let spot = stroll_spot(rng, &mut placement, physical_object, center);
+                task_que.0.push_back(Task::StrollTo(spot));
+                task_que.0.push_back(Task::Idle(STROLL_IDLE_HOURS));
+            }
+            IdleBehaviour::Socialize => {
+                println!("{} goes for a chat", name.full());
+                task_que.0.push_back(Task::Visit(partner.unwrap()));
+                task_que.0.push_back(Task::Idle(CHAT_HOURS));
+            }
+            IdleBehaviour::SitAtGatheringSpot => {
+                task_que.0.push_back(Task::Visit(gathering_spot.unwrap()));
+                task_que.0.push_back(Task::Idle(SITTING_HOURS));
+            }
+        }
+    }
+}
+
+// a free spot around `center`, so that idle villagers do not walk into buildings or each other
+fn stroll_spot<R: Rng>(
+    rng: &mut R,
+    placement: &mut Placement,
+    villager: &PhysicalObject,
+    center: Vec2,
+) -> Vec2 {
+    let area = Rectangle {
+        position: center,
+        size: Vec2::splat(STROLL_RADIUS * 2.0),
+    };
+    placement
+        .find_free(rng, &area, villager.footprint, PLACEMENT_ATTEMPTS)
+        .unwrap_or(center)
+}
+
+fn wait_idle(
+    mut commands: Commands,
+    time_cycle: Res<TimeCycle>,
+    mut idling_query: Query<(Entity, &mut Idling, &TaskQue)>,
+    mut ev_check_task: EventWriter<CheckTaskEvent>,
+) {
+    let hours = time_cycle.delta_hours();
+    for (villager_id, mut idling, task_que) in idling_query.iter_mut() {
+        // the task que was replaced, e.g. with some work
+        if !matches!(task_que.0.front(), Some(Task::Idle(_))) {
+            commands.entity(villager_id).remove::<Idling>();
+            continue;
+        }
+        if idling.hours_left <= 0.0 {
+            continue;
+        }
+
+        idling.hours_left -= hours;
+        if idling.hours_left <= 0.0 {
+            ev_check_task.send(CheckTaskEvent(villager_id));
+        }
+    }
+}
@@ -1,69 +1,473 @@
-// use crate::loading::TextureAssets;
-// use crate::GameState;
-// use crate::{actions::Actions, hexagon::HexagonBuilder};
-// use bevy::prelude::*;
-// use rand::{prelude::ThreadRng, Rng};
+use std::collections::{BTreeMap, HashSet};
 
-// pub struct LandGridPlugin;
+use crate::hex_coords::Hex;
+use crate::hexagon::{HexagonBuilder, Rectangle};
+use crate::layers::{GRID_LINES_LAYER, TILE_LAYER};
+use crate::save_game::LoadedGame;
+use crate::world_gen::SimParams;
+use crate::GameState;
+use bevy::{
+    prelude::*,
+    render::{
+        mesh::{Indices, VertexAttributeValues},
+        pipeline::{PipelineDescriptor, PrimitiveTopology, RenderPipeline},
+        shader::{ShaderStage, ShaderStages},
+    },
+};
+
+// a chunk is a block of the offset grid, so every tile belongs to exactly one chunk
+const CHUNK_COLUMNS: i32 = 8;
+const CHUNK_ROWS: i32 = 16;
+
+pub type ChunkCoord = (i32, i32);
+
+const VERTEX_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec3 Vertex_Position;
+layout(location = 1) in vec4 Vertex_Color;
+layout(location = 0) out vec4 v_Color;
+
+layout(set = 0, binding = 0) uniform CameraViewProj {
+    mat4 ViewProj;
+};
+layout(set = 1, binding = 0) uniform Transform {
+    mat4 Model;
+};
+void main() {
+    gl_Position = ViewProj * Model * vec4(Vertex_Position, 1.0);
+    v_Color = Vertex_Color;
+}
+"#;
+
+const FRAGMENT_SHADER: &str = r#"
+#version 450
+layout(location = 0) in vec4 v_Color;
+layout(location = 0) out vec4 o_Target;
+
+void main() {
+    o_Target = v_Color;
+}
+"#;
+
+pub struct LandGridPlugin;
+
+impl Plugin for LandGridPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_startup_system(setup_terrain_pipeline.system())
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(create_land_grid.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(rebuild_changed_chunks.system())
+                    .with_system(toggle_grid_lines.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_land_grid.system()),
+            );
+    }
+}
+
+/// What covers a tile, paths and roads are made by the villagers, see `RoadsPlugin`
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Surface {
+    Grass,
+    DirtPath,
+    PavedRoad,
+}
+
+impl Surface {
+    /// Multiplies the speed of walkers
+    pub fn speed_factor(&self) -> f32 {
+        match self {
+            Surface::Grass => 1.0,
+            Surface::DirtPath => 1.25,
+            Surface::PavedRoad => 1.5,
+        }
+    }
+
+    /// Cost of crossing a tile for the pathfinding, in tenths so that it can be summed up exactly
+    pub fn path_cost(&self) -> u32 {
+        match self {
+            Surface::Grass => 10,
+            Surface::DirtPath => 8,
+            Surface::PavedRoad => 6,
+        }
+    }
+}
 
 pub struct LandTile {
     pub column: i32,
     pub row: i32,
+    pub hex: Hex,
+    /// 0.0 is dry meadow, 1.0 is lush grass
+    pub fertility: f32,
+    pub surface: Surface,
+    /// Game hours walkers spent on the tile recently, it wears off over time
+    pub traffic: f32,
+    /// Painted by the player for the villagers to store wood on
+    pub stockpile_zone: bool,
+}
+
+impl LandTile {
+    pub fn colour(&self) -> Color {
+        let rgb = match self.surface {
+            Surface::Grass => {
+                let dry = Vec3::new(0.62, 0.72, 0.45);
+                let lush = Vec3::new(0.4, 0.7, 0.42);
+                dry.lerp(lush, self.fertility)
+            }
+            Surface::DirtPath => Vec3::new(0.66, 0.56, 0.4),
+            Surface::PavedRoad => Vec3::new(0.6, 0.58, 0.55),
+        };
+        let rgb = if self.stockpile_zone {
+            rgb.lerp(Vec3::new(0.55, 0.6, 0.85), 0.4)
+        } else {
+            rgb
+        };
+        Color::rgb(rgb.x, rgb.y, rgb.z)
+    }
 }
 
-// impl Plugin for LandGridPlugin {
-//     fn build(&self, app: &mut AppBuilder) {
-//         app.add_system_set(
-//             SystemSet::on_enter(GameState::Playing).with_system(spawn_land_grid.system()),
-//         )
-//         .add_system_set(
-//             SystemSet::on_exit(GameState::Playing).with_system(remove_land_grid.system()),
-//         );
-//     }
-// }
-
-// fn spawn_land_grid(
-//     mut commands: Commands,
-//     textures: Res<TextureAssets>,
-//     mut materials: ResMut<Assets<ColorMaterial>>,
-// ) {
-//     let hexagon_size = 30.0;
-//     let world_columns = 9;
-//     let world_rows = 27;
-
-//     let hexagon_builder = HexagonBuilder::new(hexagon_size);
-//     let mut rng = rand::thread_rng();
-//     let world_rect = hexagon_builder.get_world_rect(world_columns, world_rows);
-//     let generate_tree = |rng: &mut ThreadRng| {
-//         (
-//             rng.gen_range(0f32..world_rect.width),
-//             rng.gen_range(0f32..world_rect.height),
-//         )
-//     };
-
-//     let land_grid = LandGrid {
-//         tiles: (0..world_columns * world_rows)
-//             .map(|i| {
-//                 LandTile {
-//                     column: i.rem_euclid(world_columns),
-//                     row: i / world_columns,
-//                 }
-//                 // hexagon_builder.get_hexagon_at(i.rem_euclid(world_columns), i / world_columns)
-//             })
-//             .collect(),
-//     };
-
-//     commands
-//         .spawn_bundle(SpriteBundle {
-//             material: materials.add(textures.texture_.clone().into()),
-//             transform: Transform::from_translation(Vec3::new(0., 0., 1.)),
-//             ..Default::default()
-//         })
-//         .insert(land_grid);
-// }
-
-// fn remove_land_grid(mut commands: Commands, land_grid_query: Query<Entity, With<LandGrid>>) {
-//     for land_grid in land_grid_query.iter() {
-//         commands.entity(land_grid).despawn();
-//     }
-// }
+fn chunk_of(column: i32, row: i32) -> ChunkCoord {
+    (column.div_euclid(CHUNK_COLUMNS), row.div_euclid(CHUNK_ROWS))
+}
+
+// a hash of the seed and the coordinates, so that any tile can be generated without its neighbours
+fn noise(seed: u64, hex: Hex) -> f32 {
+    let mut x = seed ^ ((hex.q as u32 as u64) << 32 | hex.r as u32 as u64);
+    x = x.wrapping_add(0x9e37_79b9_7f4a_7c15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+    x ^= x >> 31;
+    (x >> 40) as f32 / (1u64 << 24) as f32
+}
+
+/// Tiles of the world, generated chunk by chunk when they are first needed
+pub struct LandGrid {
+    hexagon_builder: HexagonBuilder,
+    origin: Vec2,
+    seed: u64,
+    columns: i32,
+    rows: i32,
+    // ordered, so that the tiles are gone through in the same order on every run
+    chunks: BTreeMap<ChunkCoord, Vec<LandTile>>,
+    changed_chunks: HashSet<ChunkCoord>,
+    pub show_grid_lines: bool,
+}
+
+impl LandGrid {
+    pub fn new(sim_params: &SimParams) -> Self {
+        let (columns, rows) = sim_params
+            .hexagon_builder
+            .get_world_columns_rows(sim_params.world_rect.size.x, sim_params.world_rect.size.y);
+        Self {
+            hexagon_builder: sim_params.hexagon_builder.clone(),
+            origin: sim_params.world_rect.size / 2.0,
+            seed: sim_params.seed,
+            columns,
+            rows,
+            chunks: BTreeMap::new(),
+            changed_chunks: HashSet::new(),
+            show_grid_lines: false,
+        }
+    }
+
+    pub fn contains_chunk(&self, chunk: ChunkCoord) -> bool {
+        chunk.0 >= 0
+            && chunk.1 >= 0
+            && chunk.0 * CHUNK_COLUMNS < self.columns
+            && chunk.1 * CHUNK_ROWS < self.rows
+    }
+
+    pub fn is_generated(&self, chunk: ChunkCoord) -> bool {
+        self.chunks.contains_key(&chunk)
+    }
+
+    pub fn generated_chunks(&self) -> Vec<ChunkCoord> {
+        self.chunks.keys().copied().collect()
+    }
+
+    pub fn hex_at(&self, position: Vec2) -> Hex {
+        self.hexagon_builder.world_to_hex(self.origin, position)
+    }
+
+    pub fn chunk_at(&self, position: Vec2) -> ChunkCoord {
+        let (column, row) = self.hexagon_builder.hex_to_offset(self.hex_at(position));
+        chunk_of(column, row)
+    }
+
+    // chunks on the right and top edges of the world are cut off
+    fn chunk_size(&self, chunk: ChunkCoord) -> (i32, i32) {
+        (
+            CHUNK_COLUMNS.min(self.columns - chunk.0 * CHUNK_COLUMNS),
+            CHUNK_ROWS.min(self.rows - chunk.1 * CHUNK_ROWS),
+        )
+    }
+
+    pub fn chunk_rect(&self, chunk: ChunkCoord) -> Rectangle {
+        let (column_count, row_count) = self.chunk_size(chunk);
+        self.hexagon_builder.get_block_rect(
+            self.origin,
+            chunk.0 * CHUNK_COLUMNS,
+            chunk.1 * CHUNK_ROWS,
+            column_count,
+            row_count,
+        )
+    }
+
+    pub fn generate_chunk(&mut self, chunk: ChunkCoord) {
+        let (column_count, row_count) = self.chunk_size(chunk);
+        let mut tiles = Vec::with_capacity((column_count * row_count) as usize);
+        for row in chunk.1 * CHUNK_ROWS..chunk.1 * CHUNK_ROWS + row_count {
+            for column in chunk.0 * CHUNK_COLUMNS..chunk.0 * CHUNK_COLUMNS + column_count {
+                let hex = self.hexagon_builder.offset_to_hex(column, row);
+                // averaging with the neighbours turns the noise into patches
+                let fertility = std::iter::once(hex)
+                    .chain(hex.neighbours())
+                    .map(|hex| noise(self.seed, hex))
+                    .sum::<f32>()
+                    / 7.0;
+                tiles.push(LandTile {
+                    column,
+                    row,
+                    hex,
+                    fertility,
+                    surface: Surface::Grass,
+                    traffic: 0.0,
+                    stockpile_zone: false,
+                });
+            }
+        }
+        self.chunks.insert(chunk, tiles);
+    }
+
+    fn locate(&self, hex: Hex) -> Option<(ChunkCoord, usize)> {
+        let (column, row) = self.hexagon_builder.hex_to_offset(hex);
+        if column < 0 || row < 0 || column >= self.columns || row >= self.rows {
+            return None;
+        }
+        let chunk = chunk_of(column, row);
+        let (column_count, _) = self.chunk_size(chunk);
+        let index = (row - chunk.1 * CHUNK_ROWS) * column_count + column - chunk.0 * CHUNK_COLUMNS;
+        Some((chunk, index as usize))
+    }
+
+    /// Whether the hexagon is inside of the world, even if its chunk is not generated yet
+    pub fn contains(&self, hex: Hex) -> bool {
+        self.locate(hex).is_some()
+    }
+
+    pub fn hex_center(&self, hex: Hex) -> Vec2 {
+        self.hexagon_builder.hex_to_world(self.origin, hex)
+    }
+
+    /// Tiles of chunks which are not generated yet are not available
+    pub fn tile(&self, hex: Hex) -> Option<&LandTile> {
+        let (chunk, index) = self.locate(hex)?;
+        self.chunks.get(&chunk).map(|tiles| &tiles[index])
+    }
+
+    pub fn tile_at(&self, position: Vec2) -> Option<&LandTile> {
+        self.tile(self.hex_at(position))
+    }
+
+    pub fn tiles(&self) -> impl Iterator<Item = &LandTile> {
+        self.chunks.values().flatten()
+    }
+
+    /// `update` returns whether the tile looks different now, so that its chunk is rebuilt with the next frame
+    pub fn update_tile(&mut self, hex: Hex, update: impl FnOnce(&mut LandTile) -> bool) {
+        if let Some((chunk, index)) = self.locate(hex) {
+            if let Some(tiles) = self.chunks.get_mut(&chunk) {
+                if update(&mut tiles[index]) {
+                    self.changed_chunks.insert(chunk);
+                }
+            }
+        }
+    }
+
+    /// Same as `update_tile` for every generated tile
+    pub fn update_tiles(&mut self, mut update: impl FnMut(&mut LandTile) -> bool) {
+        for (chunk, tiles) in self.chunks.iter_mut() {
+            for tile in tiles.iter_mut() {
+                if update(tile) {
+                    self.changed_chunks.insert(*chunk);
+                }
+            }
+        }
+    }
+
+    fn chunk_tiles(&self, chunk: ChunkCoord) -> impl Iterator<Item = &LandTile> {
+        self.chunks.get(&chunk).into_iter().flatten()
+    }
+
+    fn build_chunk_mesh(&self, chunk: ChunkCoord) -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut colours: Vec<[f32; 4]> = vec![];
+        let mut indices: Vec<u32> = vec![];
+
+        for tile in self.chunk_tiles(chunk) {
+            let hexagon = self
+                .hexagon_builder
+                .get_hexagon_at(self.origin, tile.column, tile.row);
+            let center = hexagon.vertices.iter().fold(Vec2::ZERO, |sum, v| sum + *v) / 6.0;
+            let colour = tile.colour();
+            // corners are a bit darker so that neighbouring tiles of the same kind are still told apart
+            let edge_colour = Color::rgb(colour.r() * 0.94, colour.g() * 0.94, colour.b() * 0.94);
+
+            let first = positions.len() as u32;
+            positions.push([center.x, center.y, 0.0]);
+            colours.push(colour.as_linear_rgba_f32());
+            for vertex in hexagon.vertices.iter() {
+                positions.push([vertex.x, vertex.y, 0.0]);
+                colours.push(edge_colour.as_linear_rgba_f32());
+            }
+            for i in 0..6 {
+                indices.extend_from_slice(&[first, first + 1 + i, first + 1 + (i + 1) % 6]);
+            }
+        }
+
+        let mut mesh = Mesh::new(PrimitiveTopology::TriangleList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_attribute("Vertex_Color", VertexAttributeValues::from(colours));
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+
+    fn build_grid_lines_mesh(&self, chunk: ChunkCoord) -> Mesh {
+        let mut positions: Vec<[f32; 3]> = vec![];
+        let mut indices: Vec<u32> = vec![];
+
+        for tile in self.chunk_tiles(chunk) {
+            let hexagon = self
+                .hexagon_builder
+                .get_hexagon_at(self.origin, tile.column, tile.row);
+            let first = positions.len() as u32;
+            for vertex in hexagon.vertices.iter() {
+                positions.push([vertex.x, vertex.y, 0.0]);
+            }
+            for i in 0..6 {
+                indices.extend_from_slice(&[first + i, first + (i + 1) % 6]);
+            }
+        }
+
+        let colours = vec![Color::rgba(0.2, 0.3, 0.2, 0.35).as_linear_rgba_f32(); positions.len()];
+        let mut mesh = Mesh::new(PrimitiveTopology::LineList);
+        mesh.set_attribute(Mesh::ATTRIBUTE_POSITION, positions);
+        mesh.set_attribute("Vertex_Color", VertexAttributeValues::from(colours));
+        mesh.set_indices(Some(Indices::U32(indices)));
+        mesh
+    }
+}
+
+pub struct TerrainPipeline(Handle<PipelineDescriptor>);
+
+pub struct TerrainChunk {
+    pub chunk: ChunkCoord,
+}
+
+pub struct GridLines {
+    pub chunk: ChunkCoord,
+}
+
+fn setup_terrain_pipeline(
+    mut commands: Commands,
+    mut pipelines: ResMut<Assets<PipelineDescriptor>>,
+    mut shaders: ResMut<Assets<Shader>>,
+) {
+    let pipeline = pipelines.add(PipelineDescriptor::default_config(ShaderStages {
+        vertex: shaders.add(Shader::from_glsl(ShaderStage::Vertex, VERTEX_SHADER)),
+        fragment: Some(shaders.add(Shader::from_glsl(ShaderStage::Fragment, FRAGMENT_SHADER))),
+    }));
+    commands.insert_resource(TerrainPipeline(pipeline));
+}
+
+fn create_land_grid(
+    mut commands: Commands,
+    sim_params: Res<SimParams>,
+    loaded_game: Option<Res<LoadedGame>>,
+) {
+    let mut land_grid = LandGrid::new(&sim_params);
+    // the trees of the chunks are loaded, they are not generated again
+    if let Some(loaded_game) = loaded_game {
+        for chunk in loaded_game.0.chunks.iter() {
+            land_grid.generate_chunk(*chunk);
+        }
+    }
+    commands.insert_resource(land_grid);
+}
+
+/// Render entities of a generated chunk, only spawned while the chunk is in view
+pub fn spawn_chunk_meshes(
+    commands: &mut Commands,
+    meshes: &mut Assets<Mesh>,
+    terrain_pipeline: &TerrainPipeline,
+    land_grid: &LandGrid,
+    chunk: ChunkCoord,
+) {
+    let render_pipelines =
+        || RenderPipelines::from_pipelines(vec![RenderPipeline::new(terrain_pipeline.0.clone())]);
+
+    commands
+        .spawn_bundle(MeshBundle {
+            mesh: meshes.add(land_grid.build_chunk_mesh(chunk)),
+            render_pipelines: render_pipelines(),
+            transform: Transform::from_xyz(0.0, 0.0, TILE_LAYER),
+            ..Default::default()
+        })
+        .insert(TerrainChunk { chunk });
+    commands
+        .spawn_bundle(MeshBundle {
+            mesh: meshes.add(land_grid.build_grid_lines_mesh(chunk)),
+            render_pipelines: render_pipelines(),
+            transform: Transform::from_xyz(0.0, 0.0, GRID_LINES_LAYER),
+            visible: Visible {
+                is_visible: land_grid.show_grid_lines,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(GridLines { chunk });
+}
+
+fn rebuild_changed_chunks(
+    mut land_grid: ResMut<LandGrid>,
+    mut meshes: ResMut<Assets<Mesh>>,
+    chunk_query: Query<(&TerrainChunk, &Handle<Mesh>)>,
+) {
+    if land_grid.changed_chunks.is_empty() {
+        return;
+    }
+
+    for (terrain_chunk, mesh_handle) in chunk_query.iter() {
+        if land_grid.changed_chunks.contains(&terrain_chunk.chunk) {
+            if let Some(mesh) = meshes.get_mut(mesh_handle) {
+                *mesh = land_grid.build_chunk_mesh(terrain_chunk.chunk);
+            }
+        }
+    }
+    land_grid.changed_chunks.clear();
+}
+
+fn toggle_grid_lines(
+    keyboard_input: Res<Input<KeyCode>>,
+    mut land_grid: ResMut<LandGrid>,
+    mut grid_lines_query: Query<&mut Visible, With<GridLines>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::G) {
+        land_grid.show_grid_lines = !land_grid.show_grid_lines;
+        for mut visible in grid_lines_query.iter_mut() {
+            visible.is_visible = land_grid.show_grid_lines;
+        }
+    }
+}
+
+type TerrainFilter = Or<(With<TerrainChunk>, With<GridLines>)>;
+
+pub fn remove_land_grid(mut commands: Commands, chunk_query: Query<Entity, TerrainFilter>) {
+    for chunk in chunk_query.iter() {
+        commands.entity(chunk).despawn();
+    }
+    commands.remove_resource::<LandGrid>();
+}
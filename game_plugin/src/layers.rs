@@ -1,3 +1,75 @@
+use bevy::{prelude::*, transform::TransformSystem};
+
+use crate::{hexagon::Rectangle, physics::PhysicalObject, world_gen::SimParams};
+
 pub const TILE_LAYER: f32 = 1.0;
+pub const GRID_LINES_LAYER: f32 = 1.5;
+/// Shadows are y-sorted between `SHADOW_LAYER` and `SHADOW_LAYER + SHADOW_DEPTH`, always under every object
 pub const SHADOW_LAYER: f32 = 2.0;
+const SHADOW_DEPTH: f32 = 1.0;
+/// Object roots are y-sorted between `OBJECT_LAYER` and `OBJECT_LAYER + OBJECT_DEPTH`, whatever the world size is
 pub const OBJECT_LAYER: f32 = 10.0;
+const OBJECT_DEPTH: f32 = 800.0;
+/// Designations of the player, e.g. the area being dragged over, above every object
+pub const DESIGNATION_LAYER: f32 = 850.0;
+pub const WEATHER_LAYER: f32 = 900.0;
+// UI has its own camera which draws after the world, so it stays on top of all the layers
+
+/// A child sprite drawn on the ground under its parent object
+pub struct Shadow;
+
+pub struct LayersPlugin;
+
+impl Plugin for LayersPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_to_stage(
+            CoreStage::PostUpdate,
+            y_sort_objects
+                .system()
+                .before(TransformSystem::TransformPropagate),
+        );
+    }
+}
+
+// 0 at the top edge of the world, 1 at the bottom one
+fn depth_share(world_rect: &Rectangle, y: f32) -> f32 {
+    let top = world_rect.position.y + world_rect.size.y / 2.0;
+    ((top - y) / world_rect.size.y).clamp(0.0, 1.0)
+}
+
+/// Z of an object root, objects further down the screen are drawn in front
+pub fn object_depth(world_rect: &Rectangle, y: f32) -> f32 {
+    OBJECT_LAYER + depth_share(world_rect, y) * OBJECT_DEPTH
+}
+
+/// Z of a shadow relative to its parent object, so that its global z ends up in the shadow layer
+fn shadow_depth(world_rect: &Rectangle, y: f32, parent_depth: f32) -> f32 {
+    SHADOW_LAYER + depth_share(world_rect, y) * SHADOW_DEPTH - parent_depth
+}
+
+#[allow(clippy::type_complexity)]
+fn y_sort_objects(
+    sim_params: Option<Res<SimParams>>,
+    mut object_query: Query<
+        (&PhysicalObject, &mut Transform, Option<&Children>),
+        Or<(Changed<PhysicalObject>, Changed<Children>)>,
+    >,
+    mut shadow_query: Query<&mut Transform, (With<Shadow>, Without<PhysicalObject>)>,
+) {
+    let world_rect = match sim_params {
+        Some(ref sim_params) => &sim_params.world_rect,
+        None => return,
+    };
+
+    for (physical_object, mut transform, children) in object_query.iter_mut() {
+        let y = physical_object.position.y;
+        transform.translation.z = object_depth(world_rect, y);
+
+        for child in children.iter().flat_map(|children| children.iter()) {
+            if let Ok(mut shadow_transform) = shadow_query.get_mut(*child) {
+                shadow_transform.translation.z =
+                    shadow_depth(world_rect, y, transform.translation.z);
+            }
+        }
+    }
+}
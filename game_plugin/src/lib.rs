@@ -1,43 +1,94 @@
 mod actions;
+mod animation;
 mod audio;
 mod behaviour;
+#[cfg(feature = "bench")]
+pub mod benchmarks;
+mod blueprints;
 mod buildings;
+mod camera;
+mod chunks;
+mod combat;
 mod creatures;
+mod definitions;
+mod designations;
+mod diagnostics;
+mod enemies;
+mod headless;
+pub mod hex_coords;
 mod hexagon;
+mod hud;
+mod idle;
 mod land_grid;
 mod layers;
 mod loading;
+#[cfg(not(target_arch = "wasm32"))]
+mod lockstep;
 mod menu;
+mod mood;
+mod names;
+mod ordered_executor;
+mod pause_menu;
 mod physics;
 mod plants;
-mod random_names;
+mod player_commands;
+mod relationships;
 mod residence;
+mod roads;
+mod save_game;
+pub mod scenarios;
 mod sprite_helpers;
+mod stats;
 mod time_cycle;
 mod tree_cutting;
 mod village;
+mod weather;
 mod world_gen;
 
 use crate::actions::ActionsPlugin;
+use crate::animation::AnimationPlugin;
 use crate::audio::InternalAudioPlugin;
 use crate::loading::LoadingPlugin;
 use crate::menu::MenuPlugin;
+use crate::pause_menu::PauseMenuPlugin;
+use crate::save_game::SaveGamePlugin;
 
 use behaviour::MovementPlugin;
 use bevy::app::AppBuilder;
-// use bevy::diagnostic::{FrameTimeDiagnosticsPlugin, LogDiagnosticsPlugin};
+use blueprints::BlueprintsPlugin;
+use camera::CameraPlugin;
+use chunks::ChunkPlugin;
+use combat::CombatPlugin;
+use definitions::DefinitionsPlugin;
+use designations::DesignationsPlugin;
+use diagnostics::DiagnosticsPlugin;
+use enemies::EnemiesPlugin;
+use hud::HudPlugin;
+use idle::IdlePlugin;
+use land_grid::LandGridPlugin;
+use layers::LayersPlugin;
+#[cfg(not(target_arch = "wasm32"))]
+use lockstep::LockstepPlugin;
 use bevy::prelude::*;
+use mood::MoodPlugin;
+use names::NamesPlugin;
 use plants::PlantLifePlugin;
+use player_commands::PlayerCommandsPlugin;
+use relationships::RelationshipsPlugin;
 use residence::ResidencePlugin;
+use roads::RoadsPlugin;
+use stats::StatsPlugin;
 use time_cycle::TimeCyclePlugin;
 use tree_cutting::TaskQuePlugin;
 use village::VillagePlugin;
-use world_gen::{ExperimentalPlugin, WorldGenPlugin};
+use weather::WeatherPlugin;
+use world_gen::WorldGenPlugin;
 
 #[derive(Clone, Eq, PartialEq, Debug, Hash)]
 enum GameState {
     Loading,
     Playing,
+    Paused,
     Menu,
 }
 
@@ -47,19 +98,41 @@ impl Plugin for GamePlugin {
     fn build(&self, app: &mut AppBuilder) {
         app.add_state(GameState::Loading)
             .add_plugin(TimeCyclePlugin)
+            .add_plugin(DefinitionsPlugin)
             .add_plugin(LoadingPlugin)
             .add_plugin(ActionsPlugin)
             .add_plugin(MenuPlugin)
+            .add_plugin(PauseMenuPlugin)
+            .add_plugin(SaveGamePlugin)
             .add_plugin(InternalAudioPlugin)
             .add_plugin(VillagePlugin)
             .add_plugin(ResidencePlugin)
+            .add_plugin(NamesPlugin)
             .add_plugin(PlantLifePlugin)
+            .add_plugin(WeatherPlugin)
+            .add_plugin(LandGridPlugin)
+            .add_plugin(RoadsPlugin)
+            .add_plugin(ChunkPlugin)
+            .add_plugin(CameraPlugin)
+            .add_plugin(LayersPlugin)
             .add_plugin(WorldGenPlugin)
+            .add_plugin(BlueprintsPlugin)
             .add_plugin(MovementPlugin)
+            .add_plugin(AnimationPlugin)
             .add_plugin(TaskQuePlugin)
-            .add_plugin(ExperimentalPlugin)
-            // .add_plugin(FrameTimeDiagnosticsPlugin::default())
-            // .add_plugin(LogDiagnosticsPlugin::default())
-            ;
+            .add_plugin(IdlePlugin)
+            .add_plugin(EnemiesPlugin)
+            .add_plugin(CombatPlugin)
+            .add_plugin(PlayerCommandsPlugin)
+            .add_plugin(DesignationsPlugin)
+            .add_plugin(MoodPlugin)
+            .add_plugin(RelationshipsPlugin)
+            .add_plugin(HudPlugin)
+            .add_plugin(StatsPlugin)
+            .add_plugin(DiagnosticsPlugin);
+
+        // wasm builds can't open sockets
+        #[cfg(not(target_arch = "wasm32"))]
+        app.add_plugin(LockstepPlugin);
     }
 }
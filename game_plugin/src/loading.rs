@@ -1,8 +1,13 @@
 mod paths;
 
+use crate::animation::{FRAME_SIZE, SHEET_COLUMNS, SHEET_ROWS};
+use crate::definitions::{
+    BuildingDefinition, CreatureDefinition, SpeciesDefinition, WorldDefinition,
+};
 use crate::loading::paths::PATHS;
+use crate::time_cycle::Season;
 use crate::GameState;
-use bevy::asset::{Asset, HandleId, LoadState};
+use bevy::asset::{Asset, LoadState};
 use bevy::prelude::*;
 use bevy_kira_audio::AudioSource;
 
@@ -29,25 +34,71 @@ pub struct FontAssets {
 
 pub struct AudioAssets {
     pub birds: Handle<AudioSource>,
+    pub rain: Handle<AudioSource>,
+    pub wind: Handle<AudioSource>,
 }
 
+/// Default handles only in headless worlds, see `scenarios`
+#[derive(Default)]
 pub struct Materials {
-    pub tile: Handle<ColorMaterial>,
     pub tree: Handle<ColorMaterial>,
+    pub tree_spring: Handle<ColorMaterial>,
+    pub tree_autumn: Handle<ColorMaterial>,
+    pub tree_winter: Handle<ColorMaterial>,
     pub wood_logs: Handle<ColorMaterial>,
     pub stockpile: Handle<ColorMaterial>,
-    pub man: Handle<ColorMaterial>,
     pub shadow: Handle<ColorMaterial>,
+    pub highlight: Handle<ColorMaterial>,
     pub house: Handle<ColorMaterial>,
+    /// A house that is yet to be built
+    pub house_blueprint: Handle<ColorMaterial>,
+    /// Marks trees designated for felling
+    pub felling_mark: Handle<ColorMaterial>,
+    /// The area the player drags over with a designation tool
+    pub drag_area: Handle<ColorMaterial>,
+    pub rain_drop: Handle<ColorMaterial>,
+    pub snowflake: Handle<ColorMaterial>,
+}
+
+impl Materials {
+    pub fn tree_for_season(&self, season: Season) -> Handle<ColorMaterial> {
+        match season {
+            Season::Spring => self.tree_spring.clone(),
+            Season::Summer => self.tree.clone(),
+            Season::Autumn => self.tree_autumn.clone(),
+            Season::Winter => self.tree_winter.clone(),
+        }
+    }
+}
+
+/// Animated sprites, see `AnimationState` for the rows of each sheet
+#[derive(Default)]
+pub struct SpriteSheets {
+    pub villager: Handle<TextureAtlas>,
+    pub wolf: Handle<TextureAtlas>,
+}
+
+pub struct Definitions {
+    pub world: Handle<WorldDefinition>,
+    pub tree: Handle<SpeciesDefinition>,
+    pub villager: Handle<CreatureDefinition>,
+    pub wolf: Handle<CreatureDefinition>,
+    pub house: Handle<BuildingDefinition>,
+    pub stockpile: Handle<BuildingDefinition>,
+    pub log_pile: Handle<BuildingDefinition>,
 }
 
 fn start_loading(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     mut materials: ResMut<Assets<ColorMaterial>>,
+    mut texture_atlases: ResMut<Assets<TextureAtlas>>,
 ) {
     let mut items: Vec<HandleUntyped> = vec![];
 
+    // hot reload definitions (and other assets) when they are changed on disk
+    asset_server.watch_for_changes().unwrap();
+
     fn track_asset<T: Asset>(
         asset_server: &Res<AssetServer>,
         items: &mut Vec<HandleUntyped>,
@@ -64,42 +115,80 @@ fn start_loading(
 
     commands.insert_resource(AudioAssets {
         birds: track_asset(&asset_server, &mut items, PATHS.audio_birds),
+        rain: track_asset(&asset_server, &mut items, PATHS.audio_rain),
+        wind: track_asset(&asset_server, &mut items, PATHS.audio_wind),
+    });
+
+    let tree_texture: Handle<Texture> = track_asset(&asset_server, &mut items, PATHS.texture_tree);
+    let mut tinted_tree = |color: Color| {
+        materials.add(ColorMaterial {
+            color,
+            texture: Some(tree_texture.clone()),
+        })
+    };
+    let tree_spring = tinted_tree(Color::rgb(0.85, 1.0, 0.8));
+    let tree_autumn = tinted_tree(Color::rgb(1.0, 0.65, 0.3));
+    let tree_winter = tinted_tree(Color::rgb(0.6, 0.55, 0.5));
+
+    let mut sprite_sheet = |path: &str| {
+        texture_atlases.add(TextureAtlas::from_grid(
+            track_asset(&asset_server, &mut items, path),
+            Vec2::splat(FRAME_SIZE),
+            SHEET_COLUMNS,
+            SHEET_ROWS,
+        ))
+    };
+    commands.insert_resource(SpriteSheets {
+        villager: sprite_sheet(PATHS.texture_man_sheet),
+        wolf: sprite_sheet(PATHS.texture_wolf_sheet),
     });
 
+    let shadow_texture: Handle<Texture> =
+        track_asset(&asset_server, &mut items, PATHS.texture_grad_shadow);
+
+    let house_texture: Handle<Texture> =
+        track_asset(&asset_server, &mut items, PATHS.texture_house);
+
     commands.insert_resource(Materials {
-        tile: materials.add(Color::rgb(0.5, 0.78, 0.52).into()),
-        man: materials.add(
-            track_asset(&asset_server, &mut items, PATHS.texture_man)
-                .clone()
-                .into(),
-        ),
-        house: materials.add(
-            track_asset(&asset_server, &mut items, PATHS.texture_house)
-                .clone()
-                .into(),
-        ),
-        tree: materials.add(
-            track_asset(&asset_server, &mut items, PATHS.texture_tree)
-                .clone()
-                .into(),
-        ),
+        rain_drop: materials.add(Color::rgba(0.7, 0.8, 0.95, 0.6).into()),
+        snowflake: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.9).into()),
+        house: materials.add(house_texture.clone().into()),
+        house_blueprint: materials.add(ColorMaterial::modulated_texture(
+            house_texture,
+            Color::rgba(0.6, 0.8, 1.0, 0.5),
+        )),
+        felling_mark: materials.add(ColorMaterial::modulated_texture(
+            shadow_texture.clone(),
+            Color::rgba(0.9, 0.25, 0.2, 0.9),
+        )),
+        drag_area: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.2).into()),
+        tree: materials.add(tree_texture.into()),
+        tree_spring,
+        tree_autumn,
+        tree_winter,
         wood_logs: materials.add(
-            track_asset(&asset_server, &mut items, PATHS.texture_wood_logs)
-                .clone()
-                .into(),
-        ),
-        shadow: materials.add(
-            track_asset(&asset_server, &mut items, PATHS.texture_grad_shadow)
-                .clone()
-                .into(),
+            track_asset(&asset_server, &mut items, PATHS.texture_wood_logs).into(),
         ),
+        highlight: materials.add(ColorMaterial::modulated_texture(
+            shadow_texture.clone(),
+            Color::rgba(1.0, 0.9, 0.3, 0.9),
+        )),
+        shadow: materials.add(shadow_texture.into()),
         stockpile: materials.add(
-            track_asset(&asset_server, &mut items, PATHS.texture_stockpile)
-                .clone()
-                .into(),
+            track_asset(&asset_server, &mut items, PATHS.texture_stockpile).into(),
         ),
     });
 
+    commands.insert_resource(Definitions {
+        world: track_asset(&asset_server, &mut items, PATHS.definition_world),
+        tree: track_asset(&asset_server, &mut items, PATHS.definition_tree),
+        villager: track_asset(&asset_server, &mut items, PATHS.definition_villager),
+        wolf: track_asset(&asset_server, &mut items, PATHS.definition_wolf),
+        house: track_asset(&asset_server, &mut items, PATHS.definition_house),
+        stockpile: track_asset(&asset_server, &mut items, PATHS.definition_stockpile),
+        log_pile: track_asset(&asset_server, &mut items, PATHS.definition_log_pile),
+    });
+
     commands.insert_resource(LoadingState { items });
 }
 
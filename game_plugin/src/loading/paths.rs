@@ -1,21 +1,41 @@
 pub struct AssetPaths {
     pub fira_sans: &'static str,
     pub audio_birds: &'static str,
+    pub audio_rain: &'static str,
+    pub audio_wind: &'static str,
     pub texture_tree: &'static str,
     pub texture_wood_logs: &'static str,
     pub texture_house: &'static str,
-    pub texture_man: &'static str,
+    pub texture_man_sheet: &'static str,
+    pub texture_wolf_sheet: &'static str,
     pub texture_grad_shadow: &'static str,
     pub texture_stockpile: &'static str,
+    pub definition_world: &'static str,
+    pub definition_tree: &'static str,
+    pub definition_villager: &'static str,
+    pub definition_wolf: &'static str,
+    pub definition_house: &'static str,
+    pub definition_stockpile: &'static str,
+    pub definition_log_pile: &'static str,
 }
 
 pub const PATHS: AssetPaths = AssetPaths {
     fira_sans: "fonts/FiraSans-Bold.ttf",
     audio_birds: "audio/birds-1.ogg",
+    audio_rain: "audio/rain-1.wav",
+    audio_wind: "audio/wind-1.wav",
     texture_tree: "textures/tree.png",
     texture_wood_logs: "textures/wood-logs.png",
     texture_house: "textures/house.png",
-    texture_man: "textures/man.png",
+    texture_man_sheet: "textures/man-sheet.png",
+    texture_wolf_sheet: "textures/wolf-sheet.png",
     texture_grad_shadow: "textures/grad-shadow.png",
     texture_stockpile: "textures/stockpile.png",
+    definition_world: "definitions/world.world.ron",
+    definition_tree: "definitions/tree.species.ron",
+    definition_villager: "definitions/villager.creature.ron",
+    definition_wolf: "definitions/wolf.creature.ron",
+    definition_house: "definitions/house.building.ron",
+    definition_stockpile: "definitions/stockpile.building.ron",
+    definition_log_pile: "definitions/log_pile.building.ron",
 };
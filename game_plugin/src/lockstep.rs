@@ -0,0 +1,674 @@
+use std::{
+    collections::{hash_map::DefaultHasher, BTreeMap},
+    env,
+    hash::{Hash, Hasher},
+    io::{self, BufRead, BufReader, Write},
+    mem,
+    net::{Shutdown, TcpListener, TcpStream},
+    sync::{
+        mpsc::{channel, Receiver, TryRecvError},
+        Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    chunks::ChunkStreaming,
+    ordered_executor::run_in_order,
+    physics::PhysicalObject,
+    player_commands::{ExecuteCommandEvent, PlayerCommand, PlayerCommandEvent},
+    time_cycle::{TimeCycle, TICK_SECONDS},
+    tree_cutting::{ResourceStorage, TaskQue},
+    world_gen::SimParams,
+    GameState,
+};
+
+// `host:7777` waits for a peer on the port, `join:127.0.0.1:7777` connects to a host
+const SESSION_VARIABLE: &str = "VILLAGE_SIM_LOCKSTEP";
+// commands are executed this many ticks after they are issued, so that they reach the peer in time
+const INPUT_DELAY: u64 = 6;
+// e.g. while the peer is paused, after that the game plays on alone
+const PEER_TIMEOUT: Duration = Duration::from_secs(10);
+const CONNECT_ATTEMPTS: u32 = 20;
+const CONNECT_INTERVAL: Duration = Duration::from_millis(500);
+// positions are compared in hundredths, so that printing and parsing floats can't cause a desync
+const CHECKSUM_PRECISION: f32 = 100.0;
+
+/// Sent by the host right after connecting, the guest plays in the world of the host instead of its own
+#[derive(Serialize, Deserialize)]
+pub struct Handshake {
+    pub sim_params: SimParams,
+}
+
+#[derive(Serialize, Deserialize, Debug, PartialEq)]
+pub struct TickMessage {
+    /// The tick the commands are executed on
+    pub tick: u64,
+    pub commands: Vec<PlayerCommand>,
+    /// The tick and checksum of the state of the sender, before that tick's commands were executed
+    pub checksum: Option<(u64, u64)>,
+}
+
+/// Commands of the host are executed before the ones of the guest
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Role {
+    Host,
+    Guest,
+}
+
+/// How the game is played, read from `VILLAGE_SIM_LOCKSTEP` unless it is inserted before the plugin is added
+#[derive(Clone, Debug, PartialEq)]
+pub enum SessionSetting {
+    Alone,
+    Online { role: Role, address: String },
+}
+
+impl SessionSetting {
+    pub fn from_env() -> Self {
+        let setting = match env::var(SESSION_VARIABLE) {
+            Ok(setting) => setting,
+            Err(_) => return SessionSetting::Alone,
+        };
+        match setting.split_once(':') {
+            Some(("host", port)) => SessionSetting::Online {
+                role: Role::Host,
+                address: format!("0.0.0.0:{}", port),
+            },
+            Some(("join", address)) => SessionSetting::Online {
+                role: Role::Guest,
+                address: address.to_string(),
+            },
+            _ => {
+                println!(
+                    "{} should be host:<port> or join:<address>:<port>, playing alone",
+                    SESSION_VARIABLE
+                );
+                SessionSetting::Alone
+            }
+        }
+    }
+}
+
+/// Keeps the ticks of the peers in step, without any networking of its own
+pub struct Lockstep {
+    /// `None` plays alone, every tick is ready straight away
+    role: Option<Role>,
+    tick: u64,
+    pending: Vec<PlayerCommand>,
+    local: BTreeMap<u64, Vec<PlayerCommand>>,
+    remote: BTreeMap<u64, Vec<PlayerCommand>>,
+    checksums: BTreeMap<u64, u64>,
+    remote_checksums: BTreeMap<u64, u64>,
+    /// The first tick the peers disagreed on
+    pub desync_tick: Option<u64>,
+}
+
+impl Lockstep {
+    pub fn new(role: Option<Role>) -> Self {
+        Self {
+            role,
+            tick: 0,
+            pending: Vec::new(),
+            local: BTreeMap::new(),
+            remote: BTreeMap::new(),
+            checksums: BTreeMap::new(),
+            remote_checksums: BTreeMap::new(),
+            desync_tick: None,
+        }
+    }
+
+    pub fn is_online(&self) -> bool {
+        self.role.is_some()
+    }
+
+    pub fn schedule(&mut self, command: PlayerCommand) {
+        self.pending.push(command);
+    }
+
+    /// The commands of the peer for the current tick are known
+    pub fn is_ready(&self) -> bool {
+        self.role.is_none() || self.tick < INPUT_DELAY || self.remote.contains_key(&self.tick)
+    }
+
+    /// Executes the current tick, given the checksum of the state before it.
+    /// Returns the commands of both players in the same order on every peer, and the message for the peer.
+    pub fn advance(&mut self, checksum: u64) -> (Vec<PlayerCommand>, TickMessage) {
+        let mut local = self.local.remove(&self.tick).unwrap_or_default();
+        let mut remote = self.remote.remove(&self.tick).unwrap_or_default();
+        let commands = if self.role == Some(Role::Guest) {
+            remote.append(&mut local);
+            remote
+        } else {
+            local.append(&mut remote);
+            local
+        };
+
+        let execute_at = self.tick + INPUT_DELAY;
+        let issued = mem::take(&mut self.pending);
+        self.local.insert(execute_at, issued.clone());
+        let message = TickMessage {
+            tick: execute_at,
+            commands: issued,
+            checksum: Some((self.tick, checksum)),
+        };
+
+        if self.role.is_some() {
+            self.checksums.insert(self.tick, checksum);
+            self.compare_checksums();
+        }
+        self.tick += 1;
+        (commands, message)
+    }
+
+    pub fn receive(&mut self, message: TickMessage) {
+        self.remote.insert(message.tick, message.commands);
+        if let Some((tick, checksum)) = message.checksum {
+            self.remote_checksums.insert(tick, checksum);
+        }
+        self.compare_checksums();
+    }
+
+    /// Plays on alone, e.g. after the peer has left
+    pub fn go_offline(&mut self) {
+        self.role = None;
+        self.remote.clear();
+        self.checksums.clear();
+        self.remote_checksums.clear();
+    }
+
+    fn compare_checksums(&mut self) {
+        let compared: Vec<u64> = self
+            .remote_checksums
+            .keys()
+            .filter(|tick| self.checksums.contains_key(tick))
+            .copied()
+            .collect();
+        for tick in compared {
+            let own = self.checksums.remove(&tick).unwrap();
+            let remote = self.remote_checksums.remove(&tick).unwrap();
+            if own != remote && self.desync_tick.is_none() {
+                println!("Desync with the peer at tick {}", tick);
+                self.desync_tick = Some(tick);
+            }
+        }
+    }
+}
+
+enum Connection {
+    Offline,
+    Connected {
+        writer: TcpStream,
+        incoming: Mutex<Receiver<TickMessage>>,
+    },
+}
+
+pub struct Session {
+    pub lockstep: Lockstep,
+    connection: Connection,
+    last_tick: Option<Instant>,
+}
+
+impl Default for Session {
+    fn default() -> Self {
+        Self {
+            lockstep: Lockstep::new(None),
+            connection: Connection::Offline,
+            last_tick: None,
+        }
+    }
+}
+
+impl Session {
+    /// Agrees with the peer on the world, the host sends its `SimParams` and the guest's are replaced by them
+    pub fn start(role: Role, stream: TcpStream, sim_params: &mut SimParams) -> io::Result<Self> {
+        stream.set_nodelay(true)?;
+        let mut reader = BufReader::new(stream.try_clone()?);
+        let mut writer = stream;
+        match role {
+            Role::Host => {
+                let handshake = Handshake {
+                    sim_params: sim_params.clone(),
+                };
+                send_message(&mut writer, &handshake)?;
+            }
+            Role::Guest => {
+                let mut line = String::new();
+                reader.read_line(&mut line)?;
+                let handshake: Handshake = ron::de::from_str(&line)
+                    .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+                *sim_params = handshake.sim_params;
+            }
+        }
+        Ok(Self {
+            lockstep: Lockstep::new(Some(role)),
+            connection: Connection::Connected {
+                writer,
+                incoming: Mutex::new(read_messages(reader)),
+            },
+            last_tick: None,
+        })
+    }
+
+    fn end(&mut self) {
+        if let Connection::Connected { writer, .. } = &self.connection {
+            // the peer notices straight away instead of waiting for the timeout
+            writer.shutdown(Shutdown::Both).ok();
+        }
+        self.lockstep.go_offline();
+        self.connection = Connection::Offline;
+    }
+}
+
+/// Keeps peers in step: player commands are exchanged and executed on fixed ticks,
+/// and every tick the state is checksummed to notice when the peers drift apart.
+pub struct LockstepPlugin;
+
+impl Plugin for LockstepPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        if app.world().get_resource::<SessionSetting>().is_none() {
+            app.insert_resource(SessionSetting::from_env());
+        }
+        // the peers have to run the systems in the same order, which is slower than running them in parallel
+        if *app.world().get_resource::<SessionSetting>().unwrap() != SessionSetting::Alone {
+            run_in_order(app);
+        }
+        app.init_resource::<Session>()
+            .add_system_set(SystemSet::on_exit(GameState::Menu).with_system(start_session.system()))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(enter_session.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing).with_system(run_ticks.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(end_session.system()),
+            );
+    }
+}
+
+// the peers connect when leaving the menu, before the world is generated, so that the guest generates the one of the host
+fn start_session(
+    setting: Res<SessionSetting>,
+    mut session: ResMut<Session>,
+    mut sim_params: ResMut<SimParams>,
+) {
+    let (role, address) = match &*setting {
+        SessionSetting::Alone => return,
+        SessionSetting::Online { role, address } => (*role, address),
+    };
+
+    let stream = match role {
+        Role::Host => {
+            println!("Waiting for a peer on {}", address);
+            accept_peer(address)
+        }
+        Role::Guest => {
+            println!("Joining {}", address);
+            connect_to_host(address)
+        }
+    };
+    match stream.and_then(|stream| Session::start(role, stream, &mut sim_params)) {
+        Ok(started) => {
+            println!("Connected to the peer");
+            *session = started;
+        }
+        Err(error) => println!("Could not reach the peer ({}), playing alone", error),
+    }
+}
+
+// the ticks are fixed and the view stays out of the simulation while the peers play together
+fn enter_session(
+    session: Res<Session>,
+    mut time_cycle: ResMut<TimeCycle>,
+    mut streaming: ResMut<ChunkStreaming>,
+) {
+    let online = session.lockstep.is_online();
+    time_cycle.set_fixed_step(if online { Some(TICK_SECONDS) } else { None });
+    streaming.follows_view = !online;
+}
+
+// the peer can't follow a restart or a loaded game, so leaving the world leaves the session
+fn end_session(mut session: ResMut<Session>) {
+    if session.lockstep.is_online() {
+        println!("Left the session, playing alone");
+    }
+    session.end();
+    *session = Session::default();
+}
+
+// the peer has as long to show up as a guest keeps trying to connect
+pub fn accept_peer(address: &str) -> io::Result<TcpStream> {
+    let listener = TcpListener::bind(address)?;
+    listener.set_nonblocking(true)?;
+    let mut attempt = 1;
+    loop {
+        match listener.accept() {
+            Ok((stream, _)) => {
+                stream.set_nonblocking(false)?;
+                return Ok(stream);
+            }
+            Err(error) if error.kind() != io::ErrorKind::WouldBlock => return Err(error),
+            Err(error) if attempt >= CONNECT_ATTEMPTS => return Err(error),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(CONNECT_INTERVAL);
+            }
+        }
+    }
+}
+
+// the host may not be listening yet when both are started together
+pub fn connect_to_host(address: &str) -> io::Result<TcpStream> {
+    let mut attempt = 1;
+    loop {
+        match TcpStream::connect(address) {
+            Ok(stream) => return Ok(stream),
+            Err(error) if attempt >= CONNECT_ATTEMPTS => return Err(error),
+            Err(_) => {
+                attempt += 1;
+                thread::sleep(CONNECT_INTERVAL);
+            }
+        }
+    }
+}
+
+/// One message per line, so that the reading side knows where a message ends
+pub fn send_message(writer: &mut TcpStream, message: &impl Serialize) -> io::Result<()> {
+    let line = ron::ser::to_string(message)
+        .map_err(|error| io::Error::new(io::ErrorKind::InvalidData, error))?;
+    writeln!(writer, "{}", line)
+}
+
+/// Reads the messages of the peer on a thread of its own, until the connection is closed
+pub fn read_messages(reader: BufReader<TcpStream>) -> Receiver<TickMessage> {
+    let (sender, receiver) = channel();
+    thread::spawn(move || {
+        for line in reader.lines() {
+            let message = match line.map(|line| ron::de::from_str(&line)) {
+                Ok(Ok(message)) => message,
+                Ok(Err(error)) => {
+                    println!("Invalid message from the peer: {}", error);
+                    break;
+                }
+                Err(_) => break,
+            };
+            if sender.send(message).is_err() {
+                break;
+            }
+        }
+    });
+    receiver
+}
+
+// every frame is one tick, the game stands still while the commands of the peer are late, so that neither runs ahead
+fn run_ticks(
+    mut session: ResMut<Session>,
+    mut time_cycle: ResMut<TimeCycle>,
+    mut ev_player_command: EventReader<PlayerCommandEvent>,
+    mut ev_execute_command: EventWriter<ExecuteCommandEvent>,
+    physical_object_query: Query<&PhysicalObject>,
+    storage_query: Query<&ResourceStorage>,
+    task_que_query: Query<&TaskQue>,
+) {
+    let session = &mut *session;
+    for PlayerCommandEvent(command) in ev_player_command.iter() {
+        session.lockstep.schedule(command.clone());
+    }
+
+    let mut peer_left = false;
+    if let Connection::Connected { incoming, .. } = &session.connection {
+        let incoming = incoming.lock().unwrap();
+        loop {
+            // whatever has arrived is taken, and only a missing tick is waited for
+            let received = if session.lockstep.is_ready() {
+                match incoming.try_recv() {
+                    Err(TryRecvError::Empty) => break,
+                    received => received.map_err(|_| ()),
+                }
+            } else {
+                incoming.recv_timeout(PEER_TIMEOUT).map_err(|_| ())
+            };
+            match received {
+                Ok(message) => session.lockstep.receive(message),
+                Err(()) => {
+                    peer_left = true;
+                    break;
+                }
+            }
+        }
+    }
+    if peer_left {
+        println!("The peer has left or stopped answering, playing alone");
+        session.end();
+        time_cycle.set_fixed_step(None);
+    }
+
+    // the ticks keep the pace of real time, however fast the frames are
+    if session.lockstep.is_online() {
+        if let Some(last_tick) = session.last_tick {
+            if let Some(rest) =
+                Duration::from_secs_f32(TICK_SECONDS).checked_sub(last_tick.elapsed())
+            {
+                thread::sleep(rest);
+            }
+        }
+        session.last_tick = Some(Instant::now());
+    }
+
+    let checksum = if session.lockstep.is_online() {
+        state_checksum(
+            physical_object_query.iter(),
+            storage_query.iter(),
+            task_que_query.iter(),
+        )
+    } else {
+        0
+    };
+    let (commands, message) = session.lockstep.advance(checksum);
+    let sent = match &mut session.connection {
+        Connection::Connected { writer, .. } => send_message(writer, &message),
+        Connection::Offline => Ok(()),
+    };
+    if let Err(error) = sent {
+        println!("Could not send to the peer ({}), playing alone", error);
+        session.end();
+        time_cycle.set_fixed_step(None);
+    }
+
+    for command in commands {
+        ev_execute_command.send(ExecuteCommandEvent(command));
+    }
+}
+
+// entity ids differ between the peers, so everything is hashed in an order independent of them
+pub fn state_checksum<'a>(
+    physical_objects: impl Iterator<Item = &'a PhysicalObject>,
+    storages: impl Iterator<Item = &'a ResourceStorage>,
+    task_ques: impl Iterator<Item = &'a TaskQue>,
+) -> u64 {
+    let quantize = |value: f32| (value * CHECKSUM_PRECISION).round() as i64;
+
+    let mut physical_objects: Vec<(i64, i64, i64)> = physical_objects
+        .map(|physical_object| {
+            (
+                quantize(physical_object.position.x),
+                quantize(physical_object.position.y),
+                quantize(physical_object.footprint.radius()),
+            )
+        })
+        .collect();
+    physical_objects.sort_unstable();
+
+    let mut storages: Vec<i64> = storages.map(|storage| quantize(storage.wood)).collect();
+    storages.sort_unstable();
+
+    let mut task_ques: Vec<u64> = task_ques
+        .map(|task_que| {
+            let mut hasher = DefaultHasher::new();
+            for task in task_que.0.iter() {
+                mem::discriminant(task).hash(&mut hasher);
+            }
+            hasher.finish()
+        })
+        .collect();
+    task_ques.sort_unstable();
+
+    let mut hasher = DefaultHasher::new();
+    physical_objects.hash(&mut hasher);
+    storages.hash(&mut hasher);
+    task_ques.hash(&mut hasher);
+    hasher.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::Path;
+
+    use bevy::app::Events;
+
+    use super::*;
+    use crate::{
+        player_commands::BuildingKind,
+        scenarios::{build_app, load_scenario, request_entities, scenario_params},
+    };
+
+    const SCENARIO: &str = "tests/scenarios/woodcutting.scenario.ron";
+    const SESSION_TICKS: usize = 240;
+
+    fn place_house(x: f32) -> PlayerCommand {
+        PlayerCommand::PlaceBuilding {
+            kind: BuildingKind::House,
+            position: (x, 0.0),
+        }
+    }
+
+    // two peers on loopback play some ticks, each placing a house, and stay in step
+    #[test]
+    fn peers_execute_the_same_commands_on_the_same_tick() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let guest_stream = connect_to_host(&address).unwrap();
+        let (host_stream, _) = listener.accept().unwrap();
+
+        let mut peers: Vec<(Lockstep, TcpStream, Receiver<TickMessage>, Vec<_>)> = vec![
+            (Lockstep::new(Some(Role::Host)), host_stream),
+            (Lockstep::new(Some(Role::Guest)), guest_stream),
+        ]
+        .into_iter()
+        .map(|(lockstep, stream)| {
+            let incoming = read_messages(BufReader::new(stream.try_clone().unwrap()));
+            (lockstep, stream, incoming, Vec::new())
+        })
+        .collect();
+        peers[0].0.schedule(place_house(1.0));
+        peers[1].0.schedule(place_house(2.0));
+
+        for tick in 0..20 {
+            for (lockstep, writer, incoming, executed) in peers.iter_mut() {
+                while !lockstep.is_ready() {
+                    let message = incoming.recv_timeout(Duration::from_secs(5)).unwrap();
+                    lockstep.receive(message);
+                }
+                let (commands, message) = lockstep.advance(42);
+                send_message(writer, &message).unwrap();
+                executed.extend(commands.into_iter().map(|command| (tick, command)));
+            }
+        }
+
+        let expected = vec![
+            (INPUT_DELAY, place_house(1.0)),
+            (INPUT_DELAY, place_house(2.0)),
+        ];
+        for (lockstep, _, _, executed) in peers.iter() {
+            assert_eq!(executed, &expected);
+            assert_eq!(lockstep.desync_tick, None);
+        }
+    }
+
+    #[test]
+    fn differing_checksums_are_a_desync() {
+        let mut host = Lockstep::new(Some(Role::Host));
+        let mut guest = Lockstep::new(Some(Role::Guest));
+        for tick in 0..INPUT_DELAY {
+            let (_, host_message) = host.advance(tick);
+            let (_, guest_message) = guest.advance(if tick == 1 { 0 } else { tick });
+            host.receive(guest_message);
+            guest.receive(host_message);
+        }
+        assert_eq!(host.desync_tick, Some(1));
+        assert_eq!(guest.desync_tick, Some(1));
+    }
+
+    fn world_checksum(world: &mut World) -> u64 {
+        let mut physical_object_query = world.query::<&PhysicalObject>();
+        let mut storage_query = world.query::<&ResourceStorage>();
+        let mut task_que_query = world.query::<&TaskQue>();
+        state_checksum(
+            physical_object_query.iter(world),
+            storage_query.iter(world),
+            task_que_query.iter(world),
+        )
+    }
+
+    // the guest knows nothing of the world but what the host sends it, and the host issues the commands of the scenario
+    fn play_scenario_in_session(role: Role, stream: TcpStream) -> (Vec<u64>, Lockstep) {
+        let scenario =
+            load_scenario(&Path::new(env!("CARGO_MANIFEST_DIR")).join(SCENARIO)).unwrap();
+        let mut sim_params = match role {
+            Role::Host => scenario_params(scenario.seed),
+            Role::Guest => scenario_params(0),
+        };
+        let session = Session::start(role, stream, &mut sim_params).unwrap();
+        let mut app = build_app(sim_params);
+        app.world.insert_resource(session);
+        app.schedule
+            .add_system_to_stage(CoreStage::Update, run_ticks.system());
+        request_entities(&mut app, &scenario);
+        if role == Role::Host {
+            let mut ev_player_command = app
+                .world
+                .get_resource_mut::<Events<PlayerCommandEvent>>()
+                .unwrap();
+            for command in scenario.commands.iter() {
+                ev_player_command.send(PlayerCommandEvent(command.clone()));
+            }
+        }
+
+        let checksums = (0..SESSION_TICKS)
+            .map(|_| {
+                app.update();
+                world_checksum(&mut app.world)
+            })
+            .collect();
+        let session = app.world.remove_resource::<Session>().unwrap();
+        (checksums, session.lockstep)
+    }
+
+    // two worlds play a scenario together over loopback, and after every tick they are the same
+    #[test]
+    fn worlds_in_a_session_stay_the_same() {
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+        let guest = thread::spawn(move || {
+            play_scenario_in_session(Role::Guest, connect_to_host(&address).unwrap())
+        });
+        let (host_stream, _) = listener.accept().unwrap();
+        let (host_checksums, host_lockstep) = play_scenario_in_session(Role::Host, host_stream);
+        let (guest_checksums, guest_lockstep) = guest.join().unwrap();
+
+        assert!(host_lockstep.is_online() && guest_lockstep.is_online());
+        assert_eq!(host_lockstep.desync_tick, None);
+        assert_eq!(guest_lockstep.desync_tick, None);
+        let first_difference = host_checksums
+            .iter()
+            .zip(guest_checksums.iter())
+            .position(|(host, guest)| host != guest);
+        assert_eq!(first_difference, None);
+        // the villager has gone to work, the worlds did not just stand still
+        assert_ne!(host_checksums.first(), host_checksums.last());
+    }
+}
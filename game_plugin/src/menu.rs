@@ -1,34 +1,36 @@
-use crate::{hexagon::HexagonBuilder, world_gen::SimParams, GameState};
-use bevy::prelude::*;
+use crate::{
+    definitions::WorldDefinition, hexagon::HexagonBuilder, loading::Definitions,
+    world_gen::SimParams, GameState,
+};
+use bevy::{ecs::component::Component, prelude::*};
 
 pub struct MenuPlugin;
 
 impl Plugin for MenuPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        // TODO: build a menu item to specify resources
-
-        let hexagon_size = 10.0; // will probably be a const
-        let hexagon_builder = HexagonBuilder::new(hexagon_size);
-        let size = Vec2::new(600.0, 600.0);
-        let (world_columns, world_rows) = hexagon_builder.get_world_columns_rows(size.x, size.y);
-
-        app.insert_resource(SimParams {
-            start_pos: Vec2::new(20., 40.),
-            world_rect: hexagon_builder.get_world_rect(world_columns, world_rows),
-            hexagon_builder,
-        });
-
         app.init_resource::<ButtonMaterials>()
-            .add_system_set(SystemSet::on_enter(GameState::Menu).with_system(setup_menu.system()))
+            .add_startup_system(spawn_ui_camera.system())
             .add_system_set(
-                SystemSet::on_update(GameState::Menu).with_system(click_play_button.system()),
-            );
+                SystemSet::on_enter(GameState::Menu)
+                    .with_system(setup_new_game_settings.system())
+                    .with_system(setup_menu.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Menu)
+                    .with_system(highlight_buttons.system())
+                    .with_system(click_setting_buttons.system())
+                    .with_system(sync_setting_values.system())
+                    .with_system(click_play_button.system()),
+            )
+            .add_system_set(SystemSet::on_exit(GameState::Menu).with_system(remove_menu.system()));
     }
 }
 
-struct ButtonMaterials {
-    normal: Handle<ColorMaterial>,
-    hovered: Handle<ColorMaterial>,
+pub struct ButtonMaterials {
+    pub normal: Handle<ColorMaterial>,
+    pub hovered: Handle<ColorMaterial>,
+    pub background: Handle<ColorMaterial>,
+    pub overlay: Handle<ColorMaterial>,
 }
 
 impl FromWorld for ButtonMaterials {
@@ -37,23 +39,251 @@ impl FromWorld for ButtonMaterials {
         ButtonMaterials {
             normal: materials.add(Color::rgb(0.15, 0.15, 0.15).into()),
             hovered: materials.add(Color::rgb(0.25, 0.25, 0.25).into()),
+            background: materials.add(Color::NONE.into()),
+            overlay: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.6).into()),
+        }
+    }
+}
+
+/// World parameters picked on the setup screen, turned into `SimParams` when the game starts
+pub struct NewGameSettings {
+    pub world_size: Vec2,
+    pub hexagon_size: f32,
+    pub start_pos: Vec2,
+    pub seed: u64,
+    pub villagers: u32,
+    pub houses: u32,
+    pub tree_density: f32,
+    pub starting_wood: f32,
+}
+
+impl NewGameSettings {
+    fn from_definition(world_definition: &WorldDefinition) -> Self {
+        Self {
+            world_size: world_definition.size(),
+            hexagon_size: world_definition.hexagon_size,
+            start_pos: world_definition.start_pos(),
+            seed: rand::random::<u32>() as u64,
+            villagers: world_definition.villagers,
+            houses: world_definition.houses,
+            tree_density: world_definition.tree_density,
+            starting_wood: world_definition.starting_wood,
+        }
+    }
+
+    pub fn to_sim_params(&self) -> SimParams {
+        let hexagon_builder = HexagonBuilder::new(self.hexagon_size);
+        let (world_columns, world_rows) =
+            hexagon_builder.get_world_columns_rows(self.world_size.x, self.world_size.y);
+
+        SimParams {
+            start_pos: self.start_pos,
+            world_rect: hexagon_builder.get_world_rect(world_columns, world_rows),
+            hexagon_builder,
+            seed: self.seed,
+            villagers: self.villagers,
+            houses: self.houses,
+            tree_density: self.tree_density,
+            starting_wood: self.starting_wood,
+        }
+    }
+
+    fn change(&mut self, setting: Setting, steps: i32) {
+        let steps_f = steps as f32;
+        match setting {
+            Setting::WorldWidth => self.world_size.x = change_world_side(self.world_size.x, steps),
+            Setting::WorldHeight => self.world_size.y = change_world_side(self.world_size.y, steps),
+            Setting::HexagonSize => {
+                self.hexagon_size = (self.hexagon_size + 2.0 * steps_f).clamp(4.0, 40.0)
+            }
+            Setting::Seed => self.seed = (self.seed as i64 + steps as i64).max(0) as u64,
+            Setting::Villagers => {
+                self.villagers = (self.villagers as i32 + steps).clamp(1, 50) as u32
+            }
+            Setting::Houses => self.houses = (self.houses as i32 + steps).clamp(0, 25) as u32,
+            Setting::TreeDensity => {
+                self.tree_density = (self.tree_density + 0.25 * steps_f).clamp(0.0, 5.0)
+            }
+            Setting::StartingWood => {
+                self.starting_wood = (self.starting_wood + 10.0 * steps_f).clamp(0.0, 500.0)
+            }
+        }
+    }
+
+    fn value_text(&self, setting: Setting) -> String {
+        match setting {
+            Setting::WorldWidth => format!("{}", self.world_size.x),
+            Setting::WorldHeight => format!("{}", self.world_size.y),
+            Setting::HexagonSize => format!("{}", self.hexagon_size),
+            Setting::Seed => format!("{}", self.seed),
+            Setting::Villagers => format!("{}", self.villagers),
+            Setting::Houses => format!("{}", self.houses),
+            Setting::TreeDensity => format!("{:.2}", self.tree_density),
+            Setting::StartingWood => format!("{}", self.starting_wood),
         }
     }
 }
 
+#[derive(Clone, Copy)]
+enum Setting {
+    WorldWidth,
+    WorldHeight,
+    HexagonSize,
+    Seed,
+    Villagers,
+    Houses,
+    TreeDensity,
+    StartingWood,
+}
+
+// small worlds are tuned in small steps, large ones in large steps
+fn change_world_side(side: f32, steps: i32) -> f32 {
+    let step = if side + steps as f32 * 100.0 < 2000.0 {
+        100.0
+    } else {
+        1000.0
+    };
+    (side + step * steps as f32).clamp(300.0, 30000.0)
+}
+
+const SETTINGS: [(Setting, &str); 8] = [
+    (Setting::WorldWidth, "World width"),
+    (Setting::WorldHeight, "World height"),
+    (Setting::HexagonSize, "Hexagon size"),
+    (Setting::Seed, "Seed"),
+    (Setting::Villagers, "Villagers"),
+    (Setting::Houses, "Houses"),
+    (Setting::TreeDensity, "Trees per 100x100"),
+    (Setting::StartingWood, "Starting wood"),
+];
+
+struct MenuUi;
+
 struct PlayButton;
 
+struct SettingButton {
+    setting: Setting,
+    steps: i32,
+}
+
+struct SettingValue(Setting);
+
+fn setup_new_game_settings(
+    mut commands: Commands,
+    settings: Option<Res<NewGameSettings>>,
+    definitions: Res<Definitions>,
+    world_definitions: Res<Assets<WorldDefinition>>,
+) {
+    // keep whatever was picked for the previous game
+    if settings.is_none() {
+        let world_definition = world_definitions.get(&definitions.world).unwrap();
+        commands.insert_resource(NewGameSettings::from_definition(world_definition));
+    }
+}
+
+// the UI camera lives for the whole app, both menus and the in-game UI are drawn with it
+fn spawn_ui_camera(mut commands: Commands) {
+    commands.spawn_bundle(UiCameraBundle::default());
+}
+
+pub fn text_bundle(value: &str, font: Handle<Font>, font_size: f32, width: f32) -> TextBundle {
+    TextBundle {
+        style: Style {
+            size: Size::new(Val::Px(width), Val::Auto),
+            margin: Rect::all(Val::Px(4.0)),
+            ..Default::default()
+        },
+        text: Text {
+            sections: vec![TextSection {
+                value: value.to_string(),
+                style: TextStyle {
+                    font,
+                    font_size,
+                    color: Color::rgb(0.9, 0.9, 0.9),
+                },
+            }],
+            alignment: Default::default(),
+        },
+        ..Default::default()
+    }
+}
+
 fn setup_menu(
     mut commands: Commands,
     asset_server: Res<AssetServer>,
     button_materials: Res<ButtonMaterials>,
 ) {
-    commands.spawn_bundle(UiCameraBundle::default());
+    let font: Handle<Font> = asset_server.get_handle("fonts/FiraSans-Bold.ttf");
+
     commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.background.clone(),
+            ..Default::default()
+        })
+        .insert(MenuUi)
+        .with_children(|parent| {
+            for (setting, label) in SETTINGS.iter() {
+                parent
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Row,
+                            align_items: AlignItems::Center,
+                            ..Default::default()
+                        },
+                        material: button_materials.background.clone(),
+                        ..Default::default()
+                    })
+                    .with_children(|row| {
+                        row.spawn_bundle(text_bundle(label, font.clone(), 24.0, 220.0));
+                        for (sign, steps) in [("-", -1), ("+", 1)].iter() {
+                            row.spawn_bundle(ButtonBundle {
+                                style: Style {
+                                    size: Size::new(Val::Px(32.0), Val::Px(32.0)),
+                                    justify_content: JustifyContent::Center,
+                                    align_items: AlignItems::Center,
+                                    ..Default::default()
+                                },
+                                material: button_materials.normal.clone(),
+                                ..Default::default()
+                            })
+                            .insert(SettingButton {
+                                setting: *setting,
+                                steps: *steps,
+                            })
+                            .with_children(|button| {
+                                button.spawn_bundle(text_bundle(sign, font.clone(), 24.0, 12.0));
+                            });
+                            if *steps < 0 {
+                                row.spawn_bundle(text_bundle("", font.clone(), 24.0, 100.0))
+                                    .insert(SettingValue(*setting));
+                            }
+                        }
+                    });
+            }
+
+            spawn_text_button(parent, &button_materials, font.clone(), "Play", PlayButton);
+        });
+}
+
+pub fn spawn_text_button<T: Component>(
+    parent: &mut ChildBuilder,
+    button_materials: &ButtonMaterials,
+    font: Handle<Font>,
+    label: &str,
+    marker: T,
+) {
+    parent
         .spawn_bundle(ButtonBundle {
             style: Style {
-                size: Size::new(Val::Px(120.0), Val::Px(50.0)),
-                margin: Rect::all(Val::Auto),
+                size: Size::new(Val::Px(240.0), Val::Px(50.0)),
+                margin: Rect::all(Val::Px(8.0)),
                 justify_content: JustifyContent::Center,
                 align_items: AlignItems::Center,
                 ..Default::default()
@@ -61,14 +291,14 @@ fn setup_menu(
             material: button_materials.normal.clone(),
             ..Default::default()
         })
-        .insert(PlayButton)
+        .insert(marker)
         .with_children(|parent| {
             parent.spawn_bundle(TextBundle {
                 text: Text {
                     sections: vec![TextSection {
-                        value: "Play".to_string(),
+                        value: label.to_string(),
                         style: TextStyle {
-                            font: asset_server.get_handle("fonts/FiraSans-Bold.ttf"),
+                            font,
                             font_size: 40.0,
                             color: Color::rgb(0.9, 0.9, 0.9),
                         },
@@ -80,29 +310,17 @@ fn setup_menu(
         });
 }
 
-type ButtonInteraction<'a> = (
-    Entity,
-    &'a Interaction,
-    &'a mut Handle<ColorMaterial>,
-    &'a Children,
-);
-
-fn click_play_button(
-    mut commands: Commands,
+#[allow(clippy::type_complexity)]
+pub fn highlight_buttons(
     button_materials: Res<ButtonMaterials>,
-    mut state: ResMut<State<GameState>>,
-    mut interaction_query: Query<ButtonInteraction, (Changed<Interaction>, With<Button>)>,
-    text_query: Query<Entity, With<Text>>,
+    mut interaction_query: Query<
+        (&Interaction, &mut Handle<ColorMaterial>),
+        (Changed<Interaction>, With<Button>),
+    >,
 ) {
-    for (button, interaction, mut material, children) in interaction_query.iter_mut() {
-        let text = text_query.get(children[0]).unwrap();
+    for (interaction, mut material) in interaction_query.iter_mut() {
         match *interaction {
-            Interaction::Clicked => {
-                commands.entity(button).despawn();
-                commands.entity(text).despawn();
-                state.set(GameState::Playing).unwrap();
-            }
-            Interaction::Hovered => {
+            Interaction::Clicked | Interaction::Hovered => {
                 *material = button_materials.hovered.clone();
             }
             Interaction::None => {
@@ -111,3 +329,49 @@ fn click_play_button(
         }
     }
 }
+
+fn click_setting_buttons(
+    mut settings: ResMut<NewGameSettings>,
+    interaction_query: Query<(&Interaction, &SettingButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            settings.change(button.setting, button.steps);
+        }
+    }
+}
+
+fn sync_setting_values(
+    settings: Res<NewGameSettings>,
+    mut value_query: Query<(&mut Text, &SettingValue)>,
+) {
+    for (mut text, SettingValue(setting)) in value_query.iter_mut() {
+        let value = settings.value_text(*setting);
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+fn click_play_button(
+    mut commands: Commands,
+    settings: Res<NewGameSettings>,
+    mut state: ResMut<State<GameState>>,
+    interaction_query: Query<&Interaction, (Changed<Interaction>, With<PlayButton>)>,
+) {
+    for interaction in interaction_query.iter() {
+        if *interaction == Interaction::Clicked {
+            // a double click or another state change queued in this frame starts no second game
+            if state.set(GameState::Playing).is_err() {
+                return;
+            }
+            commands.insert_resource(settings.to_sim_params());
+        }
+    }
+}
+
+fn remove_menu(mut commands: Commands, menu_query: Query<Entity, With<MenuUi>>) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
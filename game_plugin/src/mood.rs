@@ -0,0 +1,297 @@
+use bevy::prelude::*;
+
+use crate::{
+    blueprints::SpawnRequest,
+    combat::Health,
+    idle::{GatheringSpot, Idling},
+    names::VillagerName,
+    physics::PhysicalObject,
+    relationships::Grief,
+    residence::{let_go_of_villager, CreatureLeftVillageEvent, Resident, Villager},
+    time_cycle::TimeCycle,
+    tree_cutting::{ResourceCarrier, TaskQue},
+    village::Heating,
+    GameState,
+};
+
+// a day without a meal leaves a villager starving
+const HUNGER_PER_HOUR: f32 = 1.0 / 24.0;
+const WORK_HUNGER_FACTOR: f32 = 1.5;
+// a meal takes half an hour
+const EATING_PER_HOUR: f32 = 2.0;
+const HUNGRY: f32 = 0.5;
+// close enough to home, the gathering spot or another villager
+const NEARBY: f32 = 10.0;
+// a night is enough to sleep well or badly
+const SLEEP_PER_HOUR: f32 = 0.25;
+const LONELINESS_PER_HOUR: f32 = 1.0 / 24.0;
+const COMPANY_PER_HOUR: f32 = 0.5;
+// work hours in the last day that feel fine, and the ones that are too much
+const COMFORTABLE_WORK_HOURS: f32 = 8.0;
+const EXHAUSTING_WORK_HOURS: f32 = 16.0;
+// the mood follows the needs over a few hours
+const MOOD_HOURS: f32 = 4.0;
+// villagers this unhappy for a whole day leave the village
+const LEAVING_MOOD: f32 = 0.25;
+const LEAVING_HOURS: f32 = 24.0;
+
+/// What a villager has been through lately, the mood is made of it
+pub struct Needs {
+    /// From 0.0 for fed to 1.0 for starving, villagers eat at home or at the gathering spot
+    pub hunger: f32,
+    /// From 0.0 to 1.0 for a good night in a warm home
+    pub sleep_quality: f32,
+    /// Game hours of work over about the last day
+    pub work_hours: f32,
+    /// From 0.0 for lonely to 1.0 for plenty of company
+    pub company: f32,
+}
+
+impl Default for Needs {
+    fn default() -> Self {
+        Self {
+            hunger: 0.2,
+            sleep_quality: 0.8,
+            work_hours: 0.0,
+            company: 0.5,
+        }
+    }
+}
+
+impl Needs {
+    pub fn is_hungry(&self) -> bool {
+        self.hunger >= HUNGRY
+    }
+}
+
+/// How content each part of a villager's life is, from 0.0 to 1.0
+#[derive(Clone, Copy, Debug, Default)]
+pub struct MoodFactors {
+    pub housing: f32,
+    pub sleep: f32,
+    pub food: f32,
+    pub rest: f32,
+    pub company: f32,
+    /// From 0.0 to 1.0 for the loss of someone close, it takes away from the rest
+    pub grief: f32,
+}
+
+impl MoodFactors {
+    fn weighted(&self) -> f32 {
+        (self.housing * 0.25
+            + self.sleep * 0.2
+            + self.food * 0.25
+            + self.rest * 0.15
+            + self.company * 0.15
+            - self.grief * 0.4)
+            .max(0.0)
+    }
+}
+
+/// From 0.0 for miserable to 1.0 for content, villagers work faster in a good mood and leave in a bad one
+pub struct Mood {
+    pub value: f32,
+    pub factors: MoodFactors,
+    hours_miserable: f32,
+}
+
+impl Default for Mood {
+    fn default() -> Self {
+        Self {
+            value: 0.6,
+            factors: MoodFactors::default(),
+            hours_miserable: 0.0,
+        }
+    }
+}
+
+impl Mood {
+    /// Half the speed when miserable, one and a half when content
+    pub fn work_speed(&self) -> f32 {
+        0.5 + self.value
+    }
+}
+
+pub fn average_mood<'a>(moods: impl Iterator<Item = &'a Mood>) -> Option<f32> {
+    let (sum, count) = moods.fold((0.0, 0), |(sum, count), mood| (sum + mood.value, count + 1));
+    if count > 0 {
+        Some(sum / count as f32)
+    } else {
+        None
+    }
+}
+
+pub struct MoodPlugin;
+
+impl Plugin for MoodPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(update_needs.system().label("needs"))
+                .with_system(update_mood.system().label("mood").after("needs"))
+                .with_system(
+                    leave_village
+                        .system()
+                        .after("mood")
+                        .after("strike")
+                        .before("residence")
+                        .before("spawn"),
+                ),
+        );
+    }
+}
+
+#[allow(clippy::type_complexity)]
+fn update_needs(
+    time_cycle: Res<TimeCycle>,
+    mut villager_query: Query<
+        (
+            Entity,
+            &PhysicalObject,
+            &TaskQue,
+            Option<&Idling>,
+            Option<&Resident>,
+            &mut Needs,
+        ),
+        With<Villager>,
+    >,
+    home_query: Query<(&PhysicalObject, Option<&Heating>)>,
+    gathering_spot_query: Query<&PhysicalObject, With<GatheringSpot>>,
+) {
+    let hours = time_cycle.delta_hours();
+    if hours <= 0.0 {
+        return;
+    }
+
+    let villagers: Vec<(Entity, PhysicalObject)> = villager_query
+        .iter_mut()
+        .map(|(villager_id, villager, ..)| (villager_id, *villager))
+        .collect();
+
+    for (villager_id, villager, task_que, idling, resident, mut needs) in villager_query.iter_mut()
+    {
+        let home = resident.and_then(|resident| home_query.get(resident.residence_id).ok());
+        let is_at_home = home.map_or(false, |(home, _)| villager.gap_to(home) <= NEARBY);
+        let is_at_gathering_spot = gathering_spot_query
+            .iter()
+            .any(|spot| villager.gap_to(spot) <= NEARBY);
+        let is_working = task_que.0.front().map_or(false, |task| task.is_work());
+
+        if idling.is_some() && (is_at_home || is_at_gathering_spot) {
+            needs.hunger -= EATING_PER_HOUR * hours;
+        } else if is_working {
+            needs.hunger += HUNGER_PER_HOUR * WORK_HUNGER_FACTOR * hours;
+        } else {
+            needs.hunger += HUNGER_PER_HOUR * hours;
+        }
+        needs.hunger = needs.hunger.clamp(0.0, 1.0);
+
+        if time_cycle.is_night() {
+            // sleeping is idling at night
+            let sleep_target = match (idling.is_some(), home) {
+                (false, _) => 0.0,
+                (true, Some((_, heating))) if is_at_home => {
+                    if heating.map_or(true, |heating| heating.is_heated) {
+                        1.0
+                    } else {
+                        0.5
+                    }
+                }
+                (true, _) => 0.2,
+            };
+            needs.sleep_quality +=
+                (sleep_target - needs.sleep_quality) * (SLEEP_PER_HOUR * hours).min(1.0);
+        }
+
+        if is_working {
+            needs.work_hours += hours;
+        }
+        // wears off over about a day
+        needs.work_hours -= needs.work_hours * (hours / 24.0).min(1.0);
+
+        let has_company = idling.is_some()
+            && villagers.iter().any(|(other_id, other)| {
+                *other_id != villager_id && villager.gap_to(other) <= NEARBY
+            });
+        needs.company += if has_company {
+            COMPANY_PER_HOUR * hours
+        } else {
+            -LONELINESS_PER_HOUR * hours
+        };
+        needs.company = needs.company.clamp(0.0, 1.0);
+    }
+}
+
+fn update_mood(
+    time_cycle: Res<TimeCycle>,
+    mut villager_query: Query<(&Needs, Option<&Resident>, Option<&Grief>, &mut Mood)>,
+    heating_query: Query<&Heating>,
+) {
+    let hours = time_cycle.delta_hours();
+    if hours <= 0.0 {
+        return;
+    }
+
+    for (needs, resident, grief, mut mood) in villager_query.iter_mut() {
+        let housing = match resident.map(|resident| heating_query.get(resident.residence_id)) {
+            Some(Ok(heating)) if !heating.is_heated => 0.6,
+            Some(_) => 1.0,
+            None => 0.0,
+        };
+        mood.factors = MoodFactors {
+            housing,
+            sleep: needs.sleep_quality,
+            food: 1.0 - needs.hunger,
+            rest: 1.0
+                - ((needs.work_hours - COMFORTABLE_WORK_HOURS)
+                    / (EXHAUSTING_WORK_HOURS - COMFORTABLE_WORK_HOURS))
+                    .clamp(0.0, 1.0),
+            company: needs.company,
+            grief: grief.map_or(0.0, |grief| grief.intensity),
+        };
+        let target = mood.factors.weighted();
+        mood.value += (target - mood.value) * (hours / MOOD_HOURS).min(1.0);
+
+        if mood.value < LEAVING_MOOD {
+            mood.hours_miserable += hours;
+        } else {
+            mood.hours_miserable = 0.0;
+        }
+    }
+}
+
+// the village lets go of leaving villagers in the same frame, while their residence can still be looked up
+#[allow(clippy::type_complexity)]
+fn leave_village(
+    mut commands: Commands,
+    villager_query: Query<
+        (
+            Entity,
+            &VillagerName,
+            &Mood,
+            &Health,
+            &PhysicalObject,
+            Option<&ResourceCarrier>,
+        ),
+        With<Villager>,
+    >,
+    mut ev_creature_left_village: EventWriter<CreatureLeftVillageEvent>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    for (villager_id, name, mood, health, physical_object, carrier) in villager_query.iter() {
+        // the dead leave through `handle_deaths`
+        if mood.hours_miserable < LEAVING_HOURS || health.current <= 0.0 {
+            continue;
+        }
+
+        println!("{} is unhappy and leaves the village", name.full());
+        let_go_of_villager(
+            &mut commands,
+            villager_id,
+            physical_object.position,
+            carrier,
+            &mut ev_creature_left_village,
+            &mut spawn_requests,
+        );
+    }
+}
@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use rand::{prelude::StdRng, seq::SliceRandom, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+use crate::{world_gen::SimParams, GameState};
+
+const FIRST_NAME_ONSETS: &[&str] = &[
+    "b", "br", "d", "dr", "f", "g", "h", "j", "k", "l", "m", "n", "p", "r", "s", "t", "th", "v",
+    "w", "",
+];
+const FIRST_NAME_VOWELS: &[&str] = &["a", "e", "i", "o", "u", "ae", "ia", "ei", "ou"];
+// the first names of women end open, the ones of men mostly closed
+const FEMALE_FIRST_NAME_ENDINGS: &[&str] = &["a", "ia", "ie", "wen", "lyn", "ra", "na", "elle"];
+const MALE_FIRST_NAME_ENDINGS: &[&str] = &["", "n", "l", "r", "s", "th", "m", "rk", "nd", "ric"];
+const SURNAME_ROOTS: &[&str] = &[
+    "Ash", "Birch", "Black", "Brook", "Cold", "Elm", "Fair", "Fox", "Green", "Hawk", "Hill",
+    "Holt", "Marsh", "Oak", "Raven", "Red", "Stone", "Thorn", "Water", "White", "Wolf",
+];
+const SURNAME_SUFFIXES: &[&str] = &[
+    "wood", "ford", "field", "well", "ley", "by", "ton", "smith", "wright", "hurst", "more",
+];
+// names are made up until one is not taken yet, then the generation is numbered
+const NAME_ATTEMPTS: u32 = 20;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Gender {
+    Female,
+    Male,
+}
+
+/// The name of a villager, the one place it is kept
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VillagerName {
+    pub first: String,
+    pub surname: String,
+    pub gender: Gender,
+}
+
+impl VillagerName {
+    pub fn full(&self) -> String {
+        format!("{} {}", self.first, self.surname)
+    }
+}
+
+/// Makes up names from syllables, the same seed gives the same names in the same order
+pub struct NameGenerator {
+    rng: StdRng,
+    taken: HashSet<String>,
+}
+
+impl NameGenerator {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            rng: StdRng::seed_from_u64(seed),
+            taken: HashSet::new(),
+        }
+    }
+
+    /// A name no one else in the game has, children keep the surname of a parent
+    pub fn unique_name(&mut self, surname: Option<&str>) -> VillagerName {
+        let gender = if self.rng.gen_bool(0.5) {
+            Gender::Female
+        } else {
+            Gender::Male
+        };
+        let surname = surname.map_or_else(|| self.surname(), str::to_string);
+        let mut name = VillagerName {
+            first: self.first_name(gender),
+            surname,
+            gender,
+        };
+        for _ in 1..NAME_ATTEMPTS {
+            if !self.taken.contains(&name.full()) {
+                break;
+            }
+            name.first = self.first_name(gender);
+        }
+        let first = name.first.clone();
+        let mut generation = 2;
+        while self.taken.contains(&name.full()) {
+            name.first = format!("{} {}", first, roman_numeral(generation));
+            generation += 1;
+        }
+        self.taken.insert(name.full());
+        name
+    }
+
+    /// Keeps a name given elsewhere, e.g. in a saved game, from being made up again
+    pub fn reserve(&mut self, name: VillagerName) -> VillagerName {
+        self.taken.insert(name.full());
+        name
+    }
+
+    fn first_name(&mut self, gender: Gender) -> String {
+        let syllables = self.rng.gen_range(2..=3);
+        let mut name = String::new();
+        for _ in 0..syllables - 1 {
+            name.push_str(FIRST_NAME_ONSETS.choose(&mut self.rng).unwrap());
+            name.push_str(FIRST_NAME_VOWELS.choose(&mut self.rng).unwrap());
+        }
+        // only the last syllable has an ending, so that the names stay easy to say
+        name.push_str(FIRST_NAME_ONSETS.choose(&mut self.rng).unwrap());
+        match gender {
+            Gender::Female => {
+                name.push_str(FEMALE_FIRST_NAME_ENDINGS.choose(&mut self.rng).unwrap());
+            }
+            Gender::Male => {
+                name.push_str(FIRST_NAME_VOWELS.choose(&mut self.rng).unwrap());
+                name.push_str(MALE_FIRST_NAME_ENDINGS.choose(&mut self.rng).unwrap());
+            }
+        }
+        capitalize(&name)
+    }
+
+    fn surname(&mut self) -> String {
+        format!(
+            "{}{}",
+            SURNAME_ROOTS.choose(&mut self.rng).unwrap(),
+            SURNAME_SUFFIXES.choose(&mut self.rng).unwrap()
+        )
+    }
+}
+
+fn capitalize(name: &str) -> String {
+    let mut chars = name.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+fn roman_numeral(number: u32) -> String {
+    const NUMERALS: [(u32, &str); 9] = [
+        (100, "C"),
+        (90, "XC"),
+        (50, "L"),
+        (40, "XL"),
+        (10, "X"),
+        (9, "IX"),
+        (5, "V"),
+        (4, "IV"),
+        (1, "I"),
+    ];
+    let mut number = number;
+    let mut numeral = String::new();
+    for (value, letters) in NUMERALS.iter() {
+        while number >= *value {
+            numeral.push_str(letters);
+            number -= value;
+        }
+    }
+    numeral
+}
+
+pub struct NamesPlugin;
+
+impl Plugin for NamesPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.insert_resource(NameGenerator::new(0)).add_system_set(
+            SystemSet::on_enter(GameState::Playing)
+                .with_system(reset_names.system().before("generate_world")),
+        );
+    }
+}
+
+// every game names its villagers anew, from the world seed
+fn reset_names(sim_params: Res<SimParams>, mut name_generator: ResMut<NameGenerator>) {
+    *name_generator = NameGenerator::new(sim_params.seed);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn names_are_unique() {
+        let mut generator = NameGenerator::new(1);
+        let mut names = HashSet::new();
+        for _ in 0..2000 {
+            assert!(names.insert(generator.unique_name(None).full()));
+        }
+        for _ in 0..200 {
+            assert!(names.insert(generator.unique_name(Some("Oakwood")).full()));
+        }
+    }
+
+    #[test]
+    fn same_seed_gives_same_names() {
+        let mut a = NameGenerator::new(42);
+        let mut b = NameGenerator::new(42);
+        for _ in 0..100 {
+            assert_eq!(a.unique_name(None), b.unique_name(None));
+        }
+    }
+
+    #[test]
+    fn children_keep_the_surname() {
+        let mut generator = NameGenerator::new(7);
+        let parent = generator.unique_name(None);
+        let child = generator.unique_name(Some(&parent.surname));
+        assert_eq!(child.surname, parent.surname);
+        assert_ne!(child.first, parent.first);
+    }
+
+    #[test]
+    fn first_names_of_women_end_open() {
+        let mut generator = NameGenerator::new(3);
+        for _ in 0..200 {
+            let name = generator.unique_name(None);
+            let first = name.first.split(' ').next().unwrap();
+            let ends_like_a_woman = FEMALE_FIRST_NAME_ENDINGS
+                .iter()
+                .any(|ending| first.ends_with(ending));
+            if name.gender == Gender::Female {
+                assert!(ends_like_a_woman, "{}", first);
+            }
+        }
+    }
+}
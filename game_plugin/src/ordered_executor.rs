@@ -0,0 +1,107 @@
+use std::{borrow::Cow, collections::BTreeSet};
+
+use bevy::{
+    ecs::{
+        archetype::ArchetypeGeneration,
+        schedule::{ParallelSystemContainer, ParallelSystemExecutor},
+    },
+    prelude::*,
+};
+
+/// Runs the systems of a stage one after another, in the same order on every run of the game.
+/// Bevy orders the systems without dependencies between them anew on every run, and applies their commands in that order,
+/// so two worlds with the same seed would play out differently. Here they are ordered by their dependencies and then by name.
+pub struct OrderedExecutor {
+    order: Vec<usize>,
+    archetype_generation: ArchetypeGeneration,
+}
+
+impl Default for OrderedExecutor {
+    fn default() -> Self {
+        Self {
+            order: Vec::new(),
+            // every archetype is new to the systems on the first run
+            archetype_generation: ArchetypeGeneration::new(usize::MAX),
+        }
+    }
+}
+
+/// Runs the systems of the update stage in order, see `OrderedExecutor`
+pub fn run_in_order(app: &mut AppBuilder) {
+    app.stage(CoreStage::Update, |stage: &mut SystemStage| {
+        stage.set_executor(Box::new(OrderedExecutor::default()));
+        stage
+    });
+}
+
+impl ParallelSystemExecutor for OrderedExecutor {
+    fn rebuild_cached_data(&mut self, systems: &[ParallelSystemContainer]) {
+        let mut dependants = vec![Vec::new(); systems.len()];
+        let mut waiting_for: Vec<usize> = systems
+            .iter()
+            .map(|system| system.dependencies().len())
+            .collect();
+        for (index, system) in systems.iter().enumerate() {
+            for dependency in system.dependencies() {
+                dependants[*dependency].push(index);
+            }
+        }
+
+        // of the systems whose dependencies have run, the first by name runs next
+        let mut ready: BTreeSet<(Cow<'static, str>, usize)> = systems
+            .iter()
+            .enumerate()
+            .filter(|(index, _)| waiting_for[*index] == 0)
+            .map(|(index, system)| (system.name(), index))
+            .collect();
+        self.order.clear();
+        while let Some(next) = ready.iter().next().cloned() {
+            ready.remove(&next);
+            let (_, index) = next;
+            self.order.push(index);
+            for dependant in dependants[index].iter() {
+                waiting_for[*dependant] -= 1;
+                if waiting_for[*dependant] == 0 {
+                    ready.insert((systems[*dependant].name(), *dependant));
+                }
+            }
+        }
+    }
+
+    fn run_systems(&mut self, systems: &mut [ParallelSystemContainer], world: &mut World) {
+        self.update_archetypes(systems, world);
+
+        for index in self.order.iter() {
+            if systems[*index].should_run() {
+                systems[*index].system_mut().run((), world);
+            }
+        }
+        // the stage applies the commands after this in an order of its own, by then there are none left
+        for index in self.order.iter() {
+            if systems[*index].should_run() {
+                systems[*index].system_mut().apply_buffers(world);
+            }
+        }
+    }
+}
+
+impl OrderedExecutor {
+    // the systems are told of the archetypes added since the last run, like bevy's own executors do
+    fn update_archetypes(&mut self, systems: &mut [ParallelSystemContainer], world: &World) {
+        let archetypes = world.archetypes();
+        let new_generation = archetypes.generation();
+        if self.archetype_generation == new_generation {
+            return;
+        }
+        let known = match self.archetype_generation.value() {
+            usize::MAX => 0,
+            known => known,
+        };
+        for archetype in archetypes.iter().skip(known) {
+            for container in systems.iter_mut() {
+                container.system_mut().new_archetype(archetype);
+            }
+        }
+        self.archetype_generation = new_generation;
+    }
+}
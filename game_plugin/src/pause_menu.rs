@@ -0,0 +1,130 @@
+use crate::{
+    menu::{highlight_buttons, spawn_text_button, ButtonMaterials},
+    save_game::{LoadGameEvent, SaveGameEvent},
+    GameState,
+};
+use bevy::prelude::*;
+
+pub struct PauseMenuPlugin;
+
+impl Plugin for PauseMenuPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(pause_on_escape.system()),
+        )
+        .add_system_set(
+            SystemSet::on_enter(GameState::Paused).with_system(setup_pause_menu.system()),
+        )
+        .add_system_set(
+            SystemSet::on_update(GameState::Paused)
+                .with_system(highlight_buttons.system())
+                .with_system(resume_on_escape.system())
+                .with_system(click_pause_menu_buttons.system()),
+        )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Paused).with_system(remove_pause_menu.system()),
+        );
+    }
+}
+
+struct PauseMenuUi;
+
+enum PauseMenuButton {
+    Resume,
+    Save,
+    Load,
+    Restart,
+    QuitToMenu,
+}
+
+fn pause_on_escape(
+    mut state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        // the paused state is updated in the same frame, it should not see this key press
+        keyboard_input.reset(KeyCode::Escape);
+        // a state change queued earlier in this frame wins
+        state.push(GameState::Paused).ok();
+    }
+}
+
+fn resume_on_escape(
+    mut state: ResMut<State<GameState>>,
+    mut keyboard_input: ResMut<Input<KeyCode>>,
+) {
+    if keyboard_input.just_pressed(KeyCode::Escape) {
+        keyboard_input.reset(KeyCode::Escape);
+        state.pop().ok();
+    }
+}
+
+fn setup_pause_menu(
+    mut commands: Commands,
+    asset_server: Res<AssetServer>,
+    button_materials: Res<ButtonMaterials>,
+) {
+    let font: Handle<Font> = asset_server.get_handle("fonts/FiraSans-Bold.ttf");
+
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Percent(100.0), Val::Percent(100.0)),
+                position_type: PositionType::Absolute,
+                flex_direction: FlexDirection::ColumnReverse,
+                justify_content: JustifyContent::Center,
+                align_items: AlignItems::Center,
+                ..Default::default()
+            },
+            material: button_materials.overlay.clone(),
+            ..Default::default()
+        })
+        .insert(PauseMenuUi)
+        .with_children(|parent| {
+            let buttons = vec![
+                ("Resume", PauseMenuButton::Resume),
+                ("Save", PauseMenuButton::Save),
+                ("Load", PauseMenuButton::Load),
+                ("Restart", PauseMenuButton::Restart),
+                ("Quit to menu", PauseMenuButton::QuitToMenu),
+            ];
+            for (label, button) in buttons {
+                spawn_text_button(parent, &button_materials, font.clone(), label, button);
+            }
+        });
+}
+
+fn click_pause_menu_buttons(
+    mut state: ResMut<State<GameState>>,
+    mut ev_save_game: EventWriter<SaveGameEvent>,
+    mut ev_load_game: EventWriter<LoadGameEvent>,
+    interaction_query: Query<(&Interaction, &PauseMenuButton), Changed<Interaction>>,
+) {
+    for (interaction, button) in interaction_query.iter() {
+        if *interaction != Interaction::Clicked {
+            continue;
+        }
+
+        // two buttons clicked in one frame queue only the first state change
+        let _ = match button {
+            PauseMenuButton::Resume => state.pop(),
+            PauseMenuButton::Save => {
+                ev_save_game.send(SaveGameEvent);
+                Ok(())
+            }
+            PauseMenuButton::Load => {
+                ev_load_game.send(LoadGameEvent);
+                Ok(())
+            }
+            // leaving the whole stack tears the world down, and entering `Playing` again generates a new one
+            PauseMenuButton::Restart => state.replace(GameState::Playing),
+            PauseMenuButton::QuitToMenu => state.replace(GameState::Menu),
+        };
+    }
+}
+
+fn remove_pause_menu(mut commands: Commands, menu_query: Query<Entity, With<PauseMenuUi>>) {
+    for menu in menu_query.iter() {
+        commands.entity(menu).despawn_recursive();
+    }
+}
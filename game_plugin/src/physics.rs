@@ -1,17 +1,188 @@
+use std::collections::HashMap;
+
 use bevy::math::Vec2;
+use rand::Rng;
+use serde::Deserialize;
+
+use crate::{hexagon::Rectangle, world_gen::gen_in_rect};
+
+/// The area an object takes on the ground
+#[derive(Clone, Copy, Debug, Deserialize)]
+pub enum Footprint {
+    Circle {
+        radius: f32,
+    },
+    /// A flat topped hexagon, `radius` is the distance from its center to a corner
+    Hexagon {
+        radius: f32,
+    },
+}
+
+impl Footprint {
+    /// Distance from the center to the border in the given direction
+    pub fn extent_towards(&self, direction: Vec2) -> f32 {
+        match *self {
+            Footprint::Circle { radius } => radius,
+            Footprint::Hexagon { radius } => {
+                // edges of a flat topped hexagon face 30, 90, 150... degrees
+                let sector = std::f32::consts::PI / 3.0;
+                let angle = direction.y.atan2(direction.x) - sector / 2.0;
+                let to_edge_normal = angle - (angle / sector).round() * sector;
+                radius * 3f32.sqrt() / 2.0 / to_edge_normal.cos()
+            }
+        }
+    }
 
+    /// Farthest distance from the center to the border
+    pub fn radius(&self) -> f32 {
+        match *self {
+            Footprint::Circle { radius } | Footprint::Hexagon { radius } => radius,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug)]
 pub struct PhysicalObject {
     pub position: Vec2,
+    pub footprint: Footprint,
 }
 
-pub struct Speed(pub f32); // TODO: deprecate in favour of Velocity
+impl PhysicalObject {
+    fn direction_to(&self, point: Vec2) -> Vec2 {
+        let direction = point - self.position;
+        if direction == Vec2::ZERO {
+            Vec2::X
+        } else {
+            direction.normalize()
+        }
+    }
+
+    /// Distance between the borders of two objects, it is negative when they overlap
+    pub fn gap_to(&self, other: &PhysicalObject) -> f32 {
+        let direction = self.direction_to(other.position);
+        self.position.distance(other.position)
+            - self.footprint.extent_towards(direction)
+            - other.footprint.extent_towards(-direction)
+    }
+
+    pub fn overlaps(&self, other: &PhysicalObject) -> bool {
+        self.gap_to(other) < 0.0
+    }
+
+    /// A point just outside of the border, on the side facing `from`
+    pub fn approach_point(&self, from: Vec2, clearance: f32) -> Vec2 {
+        let direction = self.direction_to(from);
+        self.position + direction * (self.footprint.extent_towards(direction) + clearance)
+    }
+}
+
+/// Finds free spots for new objects among the ones already placed
+#[derive(Default)]
+pub struct Placement {
+    placed: Vec<PhysicalObject>,
+}
+
+impl Placement {
+    pub fn new(existing: Vec<PhysicalObject>) -> Self {
+        Self { placed: existing }
+    }
+
+    pub fn is_free(&self, object: &PhysicalObject) -> bool {
+        !self.placed.iter().any(|placed| placed.overlaps(object))
+    }
 
-// pub struct Velocity {
-//     pub speed: f32,
-//     pub direction: Vec2,
-// }
+    pub fn add(&mut self, object: PhysicalObject) {
+        self.placed.push(object);
+    }
+
+    pub fn find_free<R: Rng>(
+        &mut self,
+        rng: &mut R,
+        rect: &Rectangle,
+        footprint: Footprint,
+        attempts: u32,
+    ) -> Option<Vec2> {
+        for _ in 0..attempts {
+            let object = PhysicalObject {
+                position: gen_in_rect(rng, rect),
+                footprint,
+            };
+            if self.is_free(&object) {
+                self.add(object);
+                return Some(object.position);
+            }
+        }
+        None
+    }
+}
+
+/// Objects sorted into square cells, so that the ones near a spot are found without looking at all of them
+pub struct ObjectGrid {
+    cell_size: f32,
+    cells: HashMap<(i32, i32), Vec<PhysicalObject>>,
+    // how far any object reaches beyond the cell of its position
+    max_radius: f32,
+}
+
+impl ObjectGrid {
+    pub fn new(cell_size: f32, objects: impl Iterator<Item = PhysicalObject>) -> Self {
+        let mut grid = Self {
+            cell_size,
+            cells: HashMap::new(),
+            max_radius: 0.0,
+        };
+        for object in objects {
+            grid.add(object);
+        }
+        grid
+    }
+
+    fn cell_of(&self, position: Vec2) -> (i32, i32) {
+        let cell = (position / self.cell_size).floor();
+        (cell.x as i32, cell.y as i32)
+    }
+
+    pub fn add(&mut self, object: PhysicalObject) {
+        self.max_radius = self.max_radius.max(object.footprint.radius());
+        self.cells
+            .entry(self.cell_of(object.position))
+            .or_default()
+            .push(object);
+    }
+
+    pub fn is_free(&self, object: &PhysicalObject) -> bool {
+        let reach = Vec2::splat(object.footprint.radius() + self.max_radius);
+        let (min_x, min_y) = self.cell_of(object.position - reach);
+        let (max_x, max_y) = self.cell_of(object.position + reach);
+        (min_x..=max_x).all(|x| {
+            (min_y..=max_y).all(|y| {
+                self.cells.get(&(x, y)).map_or(true, |placed| {
+                    !placed.iter().any(|placed| placed.overlaps(object))
+                })
+            })
+        })
+    }
+}
+
+/// `speed` is in world units per game hour, `direction` is normalized or zero when standing still
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Velocity {
+    pub speed: f32,
+    pub direction: Vec2,
+}
+
+impl Velocity {
+    pub fn is_moving(&self) -> bool {
+        self.speed > 0.0
+    }
+
+    pub fn stop(&mut self) {
+        self.speed = 0.0;
+        self.direction = Vec2::ZERO;
+    }
+}
 
-pub struct Mobile(pub Speed);
+pub struct Mobile(pub Velocity);
 
 pub fn get_point_between(point1: Vec2, point2: Vec2, distance_from_point1: f32) -> Vec2 {
     let total_distance = point2.distance(point1);
@@ -1,12 +1,24 @@
-use rand_distr::{Binomial, Distribution};
+use rand_distr::{Binomial, Distribution, Poisson};
 
 pub struct Tree;
 
 pub struct PlantSize {
     pub current: f32,
     pub max: f32,
+    pub growth_per_second: f32,
 }
 
+/// Trees far from the camera and the villagers are not simulated every frame, see `catch_up_growth`
+pub struct Dormant {
+    /// See `TimeCycle::seconds_since_start`
+    pub since: f64,
+}
+
+// roughly half of the year is a growing season
+const GROWING_SHARE: f32 = 0.5;
+// about the seeding area of a tree, so that a sapling is checked against the objects of a few cells
+const SEEDING_CELL_SIZE: f32 = 20.0;
+
 // TODO: could be some feritily settings, affeted by the plant's age, soil, weather, which will not be implemented for this MVP
 pub struct Seeder {
     pub seeds_since_last_time: f32,
@@ -15,8 +27,8 @@ pub struct Seeder {
 }
 
 impl Seeder {
-    fn produce(&mut self, delta_seconds: f32) -> u32 {
-        let seeds = rand::thread_rng().gen_range(
+    fn produce(&mut self, delta_seconds: f32, rng: &mut impl Rng) -> u32 {
+        let seeds = rng.gen_range(
             self.seed_growth_per_second.start * delta_seconds
                 ..self.seed_growth_per_second.end * delta_seconds,
         );
@@ -25,18 +37,22 @@ impl Seeder {
         self.seeds_since_last_time -= whole_seeds;
 
         let bin = Binomial::new(whole_seeds as u64, self.survival_probability.into()).unwrap();
-        return bin.sample(&mut rand::thread_rng()).try_into().unwrap();
+        bin.sample(rng).try_into().unwrap()
     }
 }
 
 use std::{convert::TryInto, ops::Range};
 
 use crate::{
+    blueprints::SpawnRequest,
+    definitions::{Species, SpeciesDefinition},
     hexagon::Rectangle,
     loading::Materials,
-    physics::PhysicalObject,
-    sprite_helpers::spawn_sprite_bundles,
-    world_gen::{gen_in_rect, SimParams},
+    physics::{ObjectGrid, PhysicalObject},
+    sprite_helpers::{spawn_sprite_bundles, MainSprite},
+    time_cycle::{Season, TimeCycle},
+    weather::Weather,
+    world_gen::{gen_in_rect, SimRng},
     GameState,
 };
 use bevy::prelude::*;
@@ -51,64 +67,155 @@ impl Plugin for PlantLifePlugin {
         app.add_system_set(
             SystemSet::on_update(GameState::Playing)
                 .with_system(grow.system())
-                .with_system(seed.system()),
+                .with_system(seed.system())
+                .with_system(change_leaf_colour.system()),
         );
     }
 }
 
 fn grow(
-    time: Res<Time>,
-    mut plant_size_query: Query<(&mut Transform, &mut PlantSize, &mut WoodResource)>,
+    time_cycle: Res<TimeCycle>,
+    weather: Res<Weather>,
+    mut plant_size_query: Query<
+        (&mut Transform, &mut PlantSize, &mut WoodResource),
+        Without<Dormant>,
+    >,
 ) {
+    if !time_cycle.season().is_growing() {
+        return;
+    }
+
     for (mut transform, mut plant_size, mut wood_res) in plant_size_query.iter_mut() {
-        set_tree_size_and_resource(&time, &mut transform, &mut plant_size, &mut wood_res);
+        set_tree_size_and_resource(
+            time_cycle.delta_seconds(),
+            weather.growth_factor(),
+            &mut transform,
+            &mut plant_size,
+            &mut wood_res,
+        );
     }
 }
 
 pub fn set_tree_size_and_resource(
-    time: &Res<Time>,
+    delta_seconds: f32,
+    growth_factor: f32,
     transform: &mut Mut<Transform>,
     plant_size: &mut Mut<PlantSize>,
     wood_res: &mut Mut<WoodResource>,
 ) {
     if plant_size.current < plant_size.max {
-        plant_size.current = plant_size
-            .max
-            .min(plant_size.current + 0.1 * time.delta_seconds());
-        transform.scale = get_scale_from_tree_size(&plant_size);
+        let growth = plant_size.growth_per_second * growth_factor * delta_seconds;
+        plant_size.current = plant_size.max.min(plant_size.current + growth);
+        transform.scale = get_scale_from_tree_size(plant_size);
         wood_res.0 = plant_size.current;
     }
 }
 
-fn seed(
-    time: Res<Time>,
-    mut seeder_query: Query<(&Transform, &mut Seeder)>,
-    mut commands: Commands,
-    materials: Res<Materials>,
-    sim_params: Res<SimParams>,
+/// Grows a tree which was dormant for `seconds` in one step, returns how many saplings survived meanwhile
+pub fn catch_up_growth(
+    seconds: f32,
+    transform: &mut Transform,
+    plant_size: &mut PlantSize,
+    wood_res: &mut WoodResource,
+    seeder: &Seeder,
+    rng: &mut impl Rng,
+) -> u32 {
+    let growing_seconds = seconds * GROWING_SHARE;
+    plant_size.current = plant_size
+        .max
+        .min(plant_size.current + plant_size.growth_per_second * growing_seconds);
+    transform.scale = get_scale_from_tree_size(plant_size);
+    wood_res.0 = plant_size.current;
+
+    let mean_seeds = (seeder.seed_growth_per_second.start + seeder.seed_growth_per_second.end)
+        / 2.0
+        * growing_seconds
+        * seeder.survival_probability;
+    if mean_seeds <= 0.0 {
+        return 0;
+    }
+    let saplings: f32 = Poisson::new(mean_seeds).unwrap().sample(rng);
+    saplings as u32
+}
+
+pub(crate) fn seed(
+    mut seeder_query: Query<(&Transform, &mut Seeder, &Species), Without<Dormant>>,
+    species_definitions: Res<Assets<SpeciesDefinition>>,
+    time_cycle: Res<TimeCycle>,
+    weather: Res<Weather>,
+    physical_object_query: Query<&PhysicalObject>,
+    mut sim_rng: ResMut<SimRng>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
 ) {
-    let rng = &mut rand::thread_rng();
+    // no seeds grow in a frame the simulation does not advance
+    if !time_cycle.season().is_growing() || time_cycle.delta_seconds() <= 0.0 {
+        return;
+    }
 
-    for (transform, mut seeder) in seeder_query.iter_mut() {
-        let trees = seeder.produce(time.delta_seconds());
+    let rng = &mut sim_rng.0;
+    // only sorted in frames in which a seed survives
+    let mut object_grid: Option<ObjectGrid> = None;
+
+    for (transform, mut seeder, Species(species)) in seeder_query.iter_mut() {
+        let trees = seeder.produce(time_cycle.delta_seconds() * weather.seeding_factor(), rng);
+        let species_definition = species_definitions.get(species).unwrap();
         for _ in 0..trees {
             let tree_pos = gen_in_rect(
                 rng,
                 &Rectangle {
                     position: transform.translation.truncate(),
-                    size: Vec2::new(20., 20.),
+                    size: species_definition.seeding_area(),
                 },
             );
 
-            spawn_tree(
-                tree_pos,
-                0.0,
-                &sim_params.world_rect,
-                &mut commands,
-                &materials.tree,
-                &materials.shadow,
-            );
+            // a sapling can not sprout where something already stands
+            let sapling = PhysicalObject {
+                position: tree_pos,
+                footprint: species_definition.footprint,
+            };
+            let object_grid = object_grid.get_or_insert_with(|| {
+                ObjectGrid::new(SEEDING_CELL_SIZE, physical_object_query.iter().copied())
+            });
+            if !object_grid.is_free(&sapling) {
+                continue;
+            }
+            // nor where another sapling of this frame sprouts
+            object_grid.add(sapling);
+
+            spawn_requests.send(SpawnRequest::Tree {
+                species: species.clone(),
+                position: tree_pos,
+                size: 0.0,
+            });
+        }
+    }
+}
+
+fn change_leaf_colour(
+    time_cycle: Res<TimeCycle>,
+    materials: Res<Materials>,
+    mut last_season: Local<Option<Season>>,
+    tree_query: Query<&Children, With<Tree>>,
+    new_tree_query: Query<&Children, Added<Tree>>,
+    mut sprite_query: Query<&mut Handle<ColorMaterial>, With<MainSprite>>,
+) {
+    let season = time_cycle.season();
+    let season_changed = *last_season != Some(season);
+    *last_season = Some(season);
+
+    let material = materials.tree_for_season(season);
+    let mut paint = |children: &Children| {
+        for child in children.iter() {
+            if let Ok(mut sprite_material) = sprite_query.get_mut(*child) {
+                *sprite_material = material.clone();
+            }
         }
+    };
+
+    if season_changed {
+        tree_query.iter().for_each(&mut paint);
+    } else {
+        new_tree_query.iter().for_each(&mut paint);
     }
 }
 
@@ -116,37 +223,63 @@ pub fn get_scale_from_tree_size(plant_size: &PlantSize) -> Vec3 {
     Vec3::new(plant_size.current, plant_size.current, 1.0)
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_tree(
     position: Vec2,
     init_plant_size: f32,
+    species: &Handle<SpeciesDefinition>,
+    species_definition: &SpeciesDefinition,
     world_rect: &Rectangle,
     commands: &mut Commands,
     tree_material: &Handle<ColorMaterial>,
     shadow_material: &Handle<ColorMaterial>,
 ) {
-    let plant_size = PlantSize {
-        current: init_plant_size,
-        max: 1.0,
-    };
-
-    let bounding_box = Vec3::new(24.0, 48.0, 24.0);
+    let bundle = tree_bundle(init_plant_size, species, species_definition);
 
     spawn_sprite_bundles(
         commands,
-        get_scale_from_tree_size(&plant_size),
-        position,
-        bounding_box,
+        get_scale_from_tree_size(&bundle.size),
+        PhysicalObject {
+            position,
+            footprint: species_definition.footprint,
+        },
+        species_definition.bounding_box(),
         tree_material.clone(),
         shadow_material.clone(),
-        world_rect.size,
+        world_rect,
     )
-    .insert(PhysicalObject { position })
-    .insert(Tree)
-    .insert(WoodResource(0.0))
-    .insert(Seeder {
-        seed_growth_per_second: (0.0..1.0),
-        seeds_since_last_time: 0.0,
-        survival_probability: 0.01,
-    })
-    .insert(plant_size);
+    .insert_bundle(bundle);
+}
+
+/// Everything a tree is made of but its sprites and position
+#[derive(Bundle)]
+pub struct TreeBundle {
+    pub tree: Tree,
+    pub species: Species,
+    pub wood: WoodResource,
+    pub seeder: Seeder,
+    pub size: PlantSize,
+}
+
+pub fn tree_bundle(
+    init_plant_size: f32,
+    species: &Handle<SpeciesDefinition>,
+    species_definition: &SpeciesDefinition,
+) -> TreeBundle {
+    TreeBundle {
+        tree: Tree,
+        species: Species(species.clone()),
+        // the wood follows the size, `grow` only updates it while the tree is still growing
+        wood: WoodResource(init_plant_size),
+        seeder: Seeder {
+            seed_growth_per_second: species_definition.seed_growth_per_second.clone(),
+            seeds_since_last_time: 0.0,
+            survival_probability: species_definition.survival_probability,
+        },
+        size: PlantSize {
+            current: init_plant_size,
+            max: species_definition.max_size,
+            growth_per_second: species_definition.growth_per_second,
+        },
+    }
 }
@@ -0,0 +1,383 @@
+use std::collections::HashSet;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    actions::{Actions, DesignationTool},
+    behaviour::{Task, TravelToPosition, TravelToTarget},
+    blueprints::SpawnRequest,
+    definitions::BuildingDefinition,
+    designations::{ConstructionSite, MarkedForFelling, StockpileZone},
+    hex_coords::Hex,
+    land_grid::LandGrid,
+    layers::DESIGNATION_LAYER,
+    loading::{Definitions, Materials},
+    physics::{PhysicalObject, Placement},
+    plants::Tree,
+    tree_cutting::{CuttingTree, ResourceStorage, TaskQue},
+    village::{Building, WorkPriorities},
+    GameState,
+};
+
+// a click without dragging picks whatever is this close to the cursor
+const CLICK_MARGIN: f32 = 6.0;
+// the area is sampled this densely to find the hexes under it
+const ZONE_SAMPLE_STEP: f32 = 4.0;
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum BuildingKind {
+    House,
+    Stockpile,
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub enum Job {
+    Woodcutting,
+}
+
+/// Two opposite corners of a rectangle dragged over the world, both the same for a click
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq)]
+pub struct Area {
+    pub from: (f32, f32),
+    pub to: (f32, f32),
+}
+
+impl Area {
+    fn corners(&self) -> (Vec2, Vec2) {
+        let from = Vec2::new(self.from.0, self.from.1);
+        let to = Vec2::new(self.to.0, self.to.1);
+        (
+            from.min(to) - Vec2::splat(CLICK_MARGIN),
+            from.max(to) + Vec2::splat(CLICK_MARGIN),
+        )
+    }
+
+    pub fn contains(&self, position: Vec2) -> bool {
+        let (min, max) = self.corners();
+        position.cmpge(min).all() && position.cmple(max).all()
+    }
+}
+
+/// Something a player does to the village, in multiplayer every peer executes it on the same tick
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub enum PlayerCommand {
+    /// Houses are placed as blueprints for the villagers to build
+    PlaceBuilding {
+        kind: BuildingKind,
+        position: (f32, f32),
+    },
+    DesignateFelling {
+        area: Area,
+    },
+    PaintStockpileZone {
+        area: Area,
+    },
+    /// Takes back every designation in the area
+    CancelDesignations {
+        area: Area,
+    },
+    /// 0 stops the job
+    SetPriority {
+        job: Job,
+        priority: u8,
+    },
+}
+
+/// A command of the local player, executed straight away or in a few ticks in multiplayer
+pub struct PlayerCommandEvent(pub PlayerCommand);
+
+/// A command of any player to be executed now
+pub struct ExecuteCommandEvent(pub PlayerCommand);
+
+/// Shows the area dragged over with a designation tool
+struct DragArea;
+
+/// Turns the designation tools and other actions of the player into commands, and executes them
+pub struct PlayerCommandsPlugin;
+
+impl Plugin for PlayerCommandsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<PlayerCommandEvent>()
+            .add_event::<ExecuteCommandEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(spawn_drag_area.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(issue_commands.system())
+                    .with_system(execute_commands.system().before("spawn"))
+                    .with_system(show_drag_area.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_drag_area.system()),
+            );
+
+        // without the lockstep layer there is nobody to wait for
+        #[cfg(target_arch = "wasm32")]
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing).with_system(execute_at_once.system()),
+        );
+    }
+}
+
+fn issue_commands(
+    actions: Res<Actions>,
+    work_priorities: Res<WorkPriorities>,
+    mut ev_player_command: EventWriter<PlayerCommandEvent>,
+) {
+    if actions.toggle_woodcutting {
+        ev_player_command.send(PlayerCommandEvent(PlayerCommand::SetPriority {
+            job: Job::Woodcutting,
+            priority: if work_priorities.woodcutting == 0 {
+                1
+            } else {
+                0
+            },
+        }));
+    }
+
+    let (tool, (from, to)) = match (actions.designation_tool, actions.designated_area) {
+        (Some(tool), Some(area)) => (tool, area),
+        _ => return,
+    };
+    let area = Area {
+        from: from.into(),
+        to: to.into(),
+    };
+    ev_player_command.send(PlayerCommandEvent(match tool {
+        DesignationTool::Fell => PlayerCommand::DesignateFelling { area },
+        DesignationTool::StockpileZone => PlayerCommand::PaintStockpileZone { area },
+        DesignationTool::HouseBlueprint => PlayerCommand::PlaceBuilding {
+            kind: BuildingKind::House,
+            position: to.into(),
+        },
+        DesignationTool::Cancel => PlayerCommand::CancelDesignations { area },
+    }));
+}
+
+#[cfg(target_arch = "wasm32")]
+fn execute_at_once(
+    mut ev_player_command: EventReader<PlayerCommandEvent>,
+    mut ev_execute_command: EventWriter<ExecuteCommandEvent>,
+) {
+    for PlayerCommandEvent(command) in ev_player_command.iter() {
+        ev_execute_command.send(ExecuteCommandEvent(command.clone()));
+    }
+}
+
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
+fn execute_commands(
+    mut commands: Commands,
+    mut ev_execute_command: EventReader<ExecuteCommandEvent>,
+    mut work_priorities: ResMut<WorkPriorities>,
+    mut land_grid: ResMut<LandGrid>,
+    definitions: Res<Definitions>,
+    building_definitions: Res<Assets<BuildingDefinition>>,
+    tree_query: Query<(Entity, &PhysicalObject), With<Tree>>,
+    obstacle_query: Query<
+        &PhysicalObject,
+        Or<(With<Building>, With<ConstructionSite>, With<StockpileZone>)>,
+    >,
+    site_query: Query<(Entity, &PhysicalObject, &ConstructionSite)>,
+    zone_query: Query<(Entity, &PhysicalObject, &ResourceStorage), With<StockpileZone>>,
+    mut task_que_query: Query<(Entity, &mut TaskQue)>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    // buildings and zones placed by earlier commands in the same frame are not spawned yet
+    let mut placement = Placement::new(obstacle_query.iter().copied().collect());
+
+    for ExecuteCommandEvent(command) in ev_execute_command.iter() {
+        match *command {
+            PlayerCommand::PlaceBuilding { kind, position } => {
+                let position = Vec2::new(position.0, position.1);
+                let (definition, request) = match kind {
+                    BuildingKind::House => (
+                        &definitions.house,
+                        SpawnRequest::HouseBlueprint { position },
+                    ),
+                    BuildingKind::Stockpile => (
+                        &definitions.stockpile,
+                        SpawnRequest::Stockpile {
+                            position,
+                            wood: 0.0,
+                        },
+                    ),
+                };
+                let building = PhysicalObject {
+                    position,
+                    footprint: building_definitions.get(definition).unwrap().footprint,
+                };
+                if !land_grid.contains(land_grid.hex_at(position)) || !placement.is_free(&building)
+                {
+                    println!("There is no room for a {:?} there", kind);
+                    continue;
+                }
+                // the building site is cleared first
+                mark_for_felling(&mut commands, &tree_query, |tree| building.overlaps(tree));
+                placement.add(building);
+                spawn_requests.send(request);
+            }
+            PlayerCommand::DesignateFelling { area } => {
+                let marked = mark_for_felling(&mut commands, &tree_query, |tree| {
+                    area.contains(tree.position)
+                });
+                println!("{} trees are marked for felling", marked);
+            }
+            PlayerCommand::PaintStockpileZone { area } => {
+                let footprint = building_definitions
+                    .get(&definitions.log_pile)
+                    .unwrap()
+                    .footprint;
+                for hex in hexes_in_area(&land_grid, &area) {
+                    let cell = PhysicalObject {
+                        position: land_grid.hex_center(hex),
+                        footprint,
+                    };
+                    let is_zone = land_grid.tile(hex).map(|tile| tile.stockpile_zone);
+                    if is_zone != Some(false) || !placement.is_free(&cell) {
+                        continue;
+                    }
+                    land_grid.update_tile(hex, |tile| {
+                        tile.stockpile_zone = true;
+                        true
+                    });
+                    mark_for_felling(&mut commands, &tree_query, |tree| cell.overlaps(tree));
+                    placement.add(cell);
+                    spawn_requests.send(SpawnRequest::ZoneStorage {
+                        position: cell.position,
+                    });
+                }
+            }
+            PlayerCommand::CancelDesignations { area } => {
+                let unmarked: Vec<Entity> = tree_query
+                    .iter()
+                    .filter(|(_, tree)| area.contains(tree.position))
+                    .map(|(tree_id, _)| tree_id)
+                    .collect();
+                for tree_id in unmarked.iter() {
+                    commands.entity(*tree_id).remove::<MarkedForFelling>();
+                }
+                // villagers on their way to those trees stop, the rest of their tasks depends on the felling
+                for (villager_id, mut task_que) in task_que_query.iter_mut() {
+                    let is_felling = task_que.0.iter().any(
+                        |task| matches!(task, Task::CutTree(tree_id) if unmarked.contains(tree_id)),
+                    );
+                    if is_felling {
+                        task_que.0.clear();
+                        commands
+                            .entity(villager_id)
+                            .remove::<TravelToTarget>()
+                            .remove::<TravelToPosition>()
+                            .remove::<CuttingTree>();
+                    }
+                }
+
+                // delivered wood is left on the ground, to be hauled back
+                for (site_id, site, construction_site) in site_query.iter() {
+                    if area.contains(site.position) {
+                        leave_wood(
+                            &mut spawn_requests,
+                            site.position,
+                            construction_site.wood_delivered,
+                        );
+                        commands.entity(site_id).despawn_recursive();
+                    }
+                }
+                for (zone_id, zone, storage) in zone_query.iter() {
+                    if area.contains(zone.position) {
+                        leave_wood(&mut spawn_requests, zone.position, storage.wood);
+                        let hex = land_grid.hex_at(zone.position);
+                        land_grid.update_tile(hex, |tile| {
+                            tile.stockpile_zone = false;
+                            true
+                        });
+                        commands.entity(zone_id).despawn_recursive();
+                    }
+                }
+            }
+            PlayerCommand::SetPriority { job, priority } => match job {
+                Job::Woodcutting => {
+                    println!("Woodcutting priority is {}", priority);
+                    work_priorities.woodcutting = priority;
+                }
+            },
+        }
+    }
+}
+
+fn mark_for_felling(
+    commands: &mut Commands,
+    tree_query: &Query<(Entity, &PhysicalObject), With<Tree>>,
+    is_marked: impl Fn(&PhysicalObject) -> bool,
+) -> usize {
+    let mut count = 0;
+    for (tree_id, tree) in tree_query.iter() {
+        if is_marked(tree) {
+            commands.entity(tree_id).insert(MarkedForFelling);
+            count += 1;
+        }
+    }
+    count
+}
+
+fn leave_wood(spawn_requests: &mut EventWriter<SpawnRequest>, position: Vec2, wood: f32) {
+    if wood > 0.0 {
+        spawn_requests.send(SpawnRequest::LogPile { position, wood });
+    }
+}
+
+// in the order they are sampled in, so that every peer paints them the same way
+fn hexes_in_area(land_grid: &LandGrid, area: &Area) -> Vec<Hex> {
+    let (min, max) = area.corners();
+    let mut hexes = Vec::new();
+    let mut sampled = HashSet::new();
+    let mut y = min.y;
+    while y <= max.y {
+        let mut x = min.x;
+        while x <= max.x {
+            let hex = land_grid.hex_at(Vec2::new(x, y));
+            if sampled.insert(hex) {
+                hexes.push(hex);
+            }
+            x += ZONE_SAMPLE_STEP;
+        }
+        y += ZONE_SAMPLE_STEP;
+    }
+    hexes
+}
+
+fn spawn_drag_area(mut commands: Commands, materials: Res<Materials>) {
+    commands
+        .spawn_bundle(SpriteBundle {
+            material: materials.drag_area.clone(),
+            visible: Visible {
+                is_visible: false,
+                is_transparent: true,
+            },
+            ..Default::default()
+        })
+        .insert(DragArea);
+}
+
+fn show_drag_area(
+    actions: Res<Actions>,
+    mut drag_area_query: Query<(&mut Transform, &mut Sprite, &mut Visible), With<DragArea>>,
+) {
+    if let Ok((mut transform, mut sprite, mut visible)) = drag_area_query.single_mut() {
+        match (actions.drag_start, actions.cursor_position) {
+            (Some(start), Some(end)) => {
+                transform.translation = ((start + end) / 2.0).extend(DESIGNATION_LAYER);
+                sprite.size = (end - start).abs();
+                visible.is_visible = true;
+            }
+            _ => visible.is_visible = false,
+        }
+    }
+}
+
+fn remove_drag_area(mut commands: Commands, drag_area_query: Query<Entity, With<DragArea>>) {
+    for drag_area in drag_area_query.iter() {
+        commands.entity(drag_area).despawn_recursive();
+    }
+}
@@ -1,102 +0,0 @@
-pub static RANDOM_NAMES: &'static [&'static str] = &[
-    "Rayan Calvert",
-    "Anne-Marie Johnson",
-    "Steffan Flowers",
-    "Khloe Bishop",
-    "Antonina Vega",
-    "Marwah Ortega",
-    "Korban Tucker",
-    "Emme O'Quinn",
-    "Shakir Pritchard",
-    "Marta Odom",
-    "Emilis Wells",
-    "Arnie Adam",
-    "Felicity Velazquez",
-    "Umair Simon",
-    "Gregory Craig",
-    "Claude Wheatley",
-    "Alanis Coulson",
-    "Dillon Southern",
-    "Anton Villalobos",
-    "Danni Millington",
-    "Hannah Ellison",
-    "Willis Hanna",
-    "Arun Delaney",
-    "Finnlay Bernal",
-    "Vinnie Miller",
-    "Viktoria Mac",
-    "Lyra James",
-    "Lilly Avery",
-    "Imran Randolph",
-    "Skye Hough",
-    "Briana Avalos",
-    "Ivor Bateman",
-    "Hughie Griffiths",
-    "Hugh Herman",
-    "Lavinia Becker",
-    "Garry Stott",
-    "Faizaan Rosario",
-    "Kuba Sweeney",
-    "Marc Murray",
-    "Alicja Greig",
-    "Evangeline Blake",
-    "Danika Larsen",
-    "Keyan Martins",
-    "Aliyah Clay",
-    "Conrad Boyer",
-    "Virginia Delarosa",
-    "Sila Bailey",
-    "Ammarah Connelly",
-    "Fay Watkins",
-    "Lillie-May Odonnell",
-    "Hussain Millar",
-    "Huzaifah Le",
-    "Braydon Bonilla",
-    "Ksawery Grainger",
-    "Francisco Maxwell",
-    "Piers Velasquez",
-    "Caspian Hamilton",
-    "Sama Terrell",
-    "Kyron Busby",
-    "Malak Coates",
-    "Ayse Vazquez",
-    "Daisy-May Mohamed",
-    "Saanvi Chaney",
-    "Marion Legge",
-    "Anas Dixon",
-    "Gurleen Humphrey",
-    "Callen Montes",
-    "Esme-Rose Dunkley",
-    "Simra Mejia",
-    "Cynthia Grey",
-    "Hareem Rush",
-    "Murat Melendez",
-    "Rebekka Vo",
-    "Jarred Weber",
-    "Bruce Stacey",
-    "Leonardo Henson",
-    "Christine Spears",
-    "Rio Bradford",
-    "Alayna Healy",
-    "Ibraheem Luna",
-    "Kirby Carver",
-    "Abdulrahman Hunt",
-    "Malikah Bennett",
-    "Faisal Irwin",
-    "Kaylie Penn",
-    "Andreea West",
-    "Denise Sutherland",
-    "Clarissa Redman",
-    "Suzanne Day",
-    "Rafael Sparks",
-    "Theia Montgomery",
-    "Nada Hodges",
-    "Zavier Skinner",
-    "Mitchel Dunn",
-    "Alfie-Jay Farrell",
-    "Casey Moran",
-    "Star Slater",
-    "Camille Coles",
-    "Willard Molloy",
-    "Shana Salt",
-];
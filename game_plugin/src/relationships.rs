@@ -0,0 +1,432 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::{
+    blueprints::SpawnRequest,
+    combat::CreatureDiedEvent,
+    creatures::{Creature, Personality},
+    names::VillagerName,
+    physics::PhysicalObject,
+    residence::{CreatureLeftVillageEvent, Resident, Villager},
+    time_cycle::TimeCycle,
+    tree_cutting::TaskQue,
+    village::{LivingSpace, LivingSpaceAvailableEvent},
+    world_gen::SimRng,
+    GameState,
+};
+
+// close enough to talk while idling or working
+const NEARBY: f32 = 15.0;
+// a few days around each other to know each other well
+const FAMILIARITY_PER_HOUR: f32 = 0.02;
+const AFFINITY_PER_HOUR: f32 = 0.05;
+const FRIEND_FAMILIARITY: f32 = 0.3;
+const FRIEND_AFFINITY: f32 = 0.3;
+const COUPLE_FAMILIARITY: f32 = 0.8;
+const COUPLE_AFFINITY: f32 = 0.6;
+// chance of a couple sharing a house with room to spare having a child
+const BIRTHS_PER_DAY: f32 = 0.05;
+// how well children and their parents know each other from the start
+const FAMILY_RELATIONSHIP: Relationship = Relationship {
+    familiarity: 1.0,
+    affinity: 0.8,
+};
+// the loss of someone close takes days to get over
+const GRIEF_HOURS: f32 = 72.0;
+
+/// How two villagers get along, it grows while they idle or work near each other
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Relationship {
+    /// From 0.0 for strangers to 1.0
+    pub familiarity: f32,
+    /// From -1.0 for dislike to 1.0 for fondness, it depends on how alike their personalities are
+    pub affinity: f32,
+}
+
+impl Relationship {
+    pub fn is_friendly(&self) -> bool {
+        self.familiarity >= FRIEND_FAMILIARITY && self.affinity >= FRIEND_AFFINITY
+    }
+
+    pub fn closeness(&self) -> f32 {
+        self.familiarity * self.affinity.max(0.0)
+    }
+}
+
+/// The relationship graph of the village, each pair of villagers has one relationship both ways
+#[derive(Default)]
+pub struct Relationships {
+    bonds: HashMap<(Entity, Entity), Relationship>,
+    // the pairs in the order they met, they are gone through in that order and not in the one of the map
+    met: Vec<(Entity, Entity)>,
+}
+
+impl Relationships {
+    pub fn get(&self, a: Entity, b: Entity) -> Option<&Relationship> {
+        self.bonds.get(&key(a, b))
+    }
+
+    /// Everyone the villager knows, with the relationship to them
+    pub fn of(&self, villager_id: Entity) -> impl Iterator<Item = (Entity, &Relationship)> {
+        self.iter().filter_map(move |((a, b), relationship)| {
+            if *a == villager_id {
+                Some((*b, relationship))
+            } else if *b == villager_id {
+                Some((*a, relationship))
+            } else {
+                None
+            }
+        })
+    }
+
+    pub fn is_friendly(&self, a: Entity, b: Entity) -> bool {
+        self.get(a, b)
+            .map_or(false, |relationship| relationship.is_friendly())
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&(Entity, Entity), &Relationship)> {
+        self.met.iter().map(move |pair| (pair, &self.bonds[pair]))
+    }
+
+    fn entry(&mut self, a: Entity, b: Entity) -> &mut Relationship {
+        let pair = key(a, b);
+        if !self.bonds.contains_key(&pair) {
+            self.met.push(pair);
+        }
+        self.bonds.entry(pair).or_default()
+    }
+
+    fn forget(&mut self, villager_id: Entity) {
+        self.bonds
+            .retain(|(a, b), _| *a != villager_id && *b != villager_id);
+        self.met
+            .retain(|(a, b)| *a != villager_id && *b != villager_id);
+    }
+}
+
+fn key(a: Entity, b: Entity) -> (Entity, Entity) {
+    if a < b {
+        (a, b)
+    } else {
+        (b, a)
+    }
+}
+
+/// The villager a villager is in a couple with, couples live in the same house
+pub struct Partner(pub Entity);
+
+/// The couple a villager was born to
+pub struct Parents(pub Entity, pub Entity);
+
+/// Mourning someone close who has died, it weighs on the mood
+pub struct Grief {
+    pub hours_left: f32,
+    /// From 0.0 to 1.0
+    pub intensity: f32,
+}
+
+pub struct RelationshipsPlugin;
+
+impl Plugin for RelationshipsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Relationships>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_relationships.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(get_acquainted.system())
+                    .with_system(welcome_children.system())
+                    // inserting components before the dead are despawned, see `handle_deaths`
+                    .with_system(form_couples.system().before("strike"))
+                    .with_system(move_in_together.system().label("move_in").before("strike"))
+                    .with_system(have_children.system().after("move_in").before("spawn"))
+                    .with_system(mourn_departed.system().before("strike"))
+                    .with_system(wear_off_grief.system()),
+            );
+    }
+}
+
+fn reset_relationships(mut relationships: ResMut<Relationships>) {
+    *relationships = Relationships::default();
+}
+
+// villagers around each other get to know each other, and to like or dislike each other
+fn get_acquainted(
+    time_cycle: Res<TimeCycle>,
+    mut relationships: ResMut<Relationships>,
+    villager_query: Query<(Entity, &Creature, &PhysicalObject, &TaskQue), With<Villager>>,
+) {
+    let hours = time_cycle.delta_hours();
+    if hours <= 0.0 || time_cycle.is_night() {
+        return;
+    }
+
+    let villagers: Vec<(Entity, Personality, PhysicalObject)> = villager_query
+        .iter()
+        .filter(|(.., task_que)| !task_que.0.is_empty())
+        .map(|(villager_id, creature, villager, _)| (villager_id, creature.personality, *villager))
+        .collect();
+    for (i, (a_id, a_personality, a)) in villagers.iter().enumerate() {
+        for (b_id, b_personality, b) in villagers.iter().skip(i + 1) {
+            if a.gap_to(b) > NEARBY {
+                continue;
+            }
+            let relationship = relationships.entry(*a_id, *b_id);
+            relationship.familiarity =
+                (relationship.familiarity + FAMILIARITY_PER_HOUR * hours).min(1.0);
+            let compatibility = compatibility(a_personality, b_personality);
+            relationship.affinity +=
+                (compatibility - relationship.affinity) * (AFFINITY_PER_HOUR * hours).min(1.0);
+        }
+    }
+}
+
+// alike personalities get along, from -1.0 to 1.0
+fn compatibility(a: &Personality, b: &Personality) -> f32 {
+    1.0 - (a.sociability - b.sociability).abs() - (a.homeliness - b.homeliness).abs()
+}
+
+fn welcome_children(
+    mut relationships: ResMut<Relationships>,
+    child_query: Query<(Entity, &Parents), Added<Parents>>,
+) {
+    for (child_id, Parents(a, b)) in child_query.iter() {
+        *relationships.entry(child_id, *a) = FAMILY_RELATIONSHIP;
+        *relationships.entry(child_id, *b) = FAMILY_RELATIONSHIP;
+    }
+}
+
+fn form_couples(
+    mut commands: Commands,
+    relationships: Res<Relationships>,
+    single_query: Query<&VillagerName, (With<Villager>, Without<Partner>)>,
+    parents_query: Query<&Parents>,
+) {
+    let mut coupled = HashSet::new();
+    for ((a_id, b_id), relationship) in relationships.iter() {
+        if relationship.familiarity < COUPLE_FAMILIARITY
+            || relationship.affinity < COUPLE_AFFINITY
+            || coupled.contains(a_id)
+            || coupled.contains(b_id)
+            || are_family(&parents_query, *a_id, *b_id)
+        {
+            continue;
+        }
+        if let (Ok(a), Ok(b)) = (single_query.get(*a_id), single_query.get(*b_id)) {
+            println!("{} and {} are a couple", a.full(), b.full());
+            commands.entity(*a_id).insert(Partner(*b_id));
+            commands.entity(*b_id).insert(Partner(*a_id));
+            coupled.insert(*a_id);
+            coupled.insert(*b_id);
+        }
+    }
+}
+
+fn are_family(parents_query: &Query<&Parents>, a: Entity, b: Entity) -> bool {
+    match (parents_query.get(a), parents_query.get(b)) {
+        (Ok(Parents(a1, a2)), Ok(Parents(b1, b2))) if (a1, a2) == (b1, b2) => true,
+        (Ok(Parents(a1, a2)), _) if *a1 == b || *a2 == b => true,
+        (_, Ok(Parents(b1, b2))) => *b1 == a || *b2 == a,
+        _ => false,
+    }
+}
+
+// one of the couple moves into the house of the other, swapping with a single villager if the house is full
+#[allow(clippy::type_complexity)]
+fn move_in_together(
+    mut commands: Commands,
+    couple_query: Query<(Entity, &Partner, Option<&Resident>)>,
+    single_query: Query<(Entity, &Resident), (With<Villager>, Without<Partner>)>,
+    mut living_space_query: Query<&mut LivingSpace>,
+    mut ev_living_space_available: EventWriter<LivingSpaceAvailableEvent>,
+) {
+    for (villager_id, partner, resident) in couple_query.iter() {
+        // each couple is handled once
+        if villager_id > partner.0 {
+            continue;
+        }
+        let partner_resident = match couple_query.get(partner.0) {
+            Ok((_, _, partner_resident)) => partner_resident,
+            Err(_) => continue,
+        };
+        let (mover_id, from, to) = match (resident, partner_resident) {
+            (Some(a), Some(b)) if a.residence_id == b.residence_id => continue,
+            (_, Some(b)) if has_room(&living_space_query, b.residence_id) => (
+                villager_id,
+                resident.map(|a| a.residence_id),
+                b.residence_id,
+            ),
+            (Some(a), _) if has_room(&living_space_query, a.residence_id) => (
+                partner.0,
+                partner_resident.map(|b| b.residence_id),
+                a.residence_id,
+            ),
+            (Some(a), Some(b)) => {
+                // a single villager from the partner's house takes the vacated place
+                let single = single_query
+                    .iter()
+                    .find(|(_, single)| single.residence_id == b.residence_id);
+                if let Some((single_id, _)) = single {
+                    commands.entity(single_id).insert(Resident {
+                        residence_id: a.residence_id,
+                    });
+                    commands.entity(villager_id).insert(Resident {
+                        residence_id: b.residence_id,
+                    });
+                }
+                continue;
+            }
+            _ => continue,
+        };
+
+        commands
+            .entity(mover_id)
+            .insert(Resident { residence_id: to });
+        if let Ok(mut living_space) = living_space_query.get_mut(to) {
+            living_space.current_people += 1;
+        }
+        if let Some(from) = from {
+            if let Ok(mut living_space) = living_space_query.get_mut(from) {
+                living_space.current_people -= 1;
+            }
+            ev_living_space_available.send(LivingSpaceAvailableEvent { residence_id: from });
+        }
+    }
+}
+
+fn has_room(living_space_query: &Query<&mut LivingSpace>, residence_id: Entity) -> bool {
+    living_space_query
+        .get_component::<LivingSpace>(residence_id)
+        .map_or(false, |living_space| {
+            living_space.current_people < living_space.max_people
+        })
+}
+
+// children are born into the house of their parents, so there has to be room for them
+fn have_children(
+    time_cycle: Res<TimeCycle>,
+    couple_query: Query<(Entity, &Partner, &Resident)>,
+    name_query: Query<&VillagerName>,
+    mut house_query: Query<(&PhysicalObject, &mut LivingSpace)>,
+    mut sim_rng: ResMut<SimRng>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+) {
+    let days = time_cycle.delta_hours() / 24.0;
+    let rng = &mut sim_rng.0;
+
+    for (villager_id, partner, resident) in couple_query.iter() {
+        if villager_id > partner.0 {
+            continue;
+        }
+        let shares_home = couple_query
+            .get(partner.0)
+            .map_or(false, |(.., partner_resident)| {
+                partner_resident.residence_id == resident.residence_id
+            });
+        if !shares_home || !rng.gen_bool((BIRTHS_PER_DAY * days).min(1.0) as f64) {
+            continue;
+        }
+        if let Ok((house, mut living_space)) = house_query.get_mut(resident.residence_id) {
+            let (a, b) = match (name_query.get(villager_id), name_query.get(partner.0)) {
+                (Ok(a), Ok(b)) => (a, b),
+                _ => continue,
+            };
+            if living_space.current_people >= living_space.max_people {
+                continue;
+            }
+            // the place is taken right away, so that no homeless villager is given it meanwhile
+            living_space.current_people += 1;
+            println!("{} and {} have a child", a.full(), b.full());
+            // the child takes the surname of either parent
+            let surname = if rng.gen_bool(0.5) { a } else { b }.surname.clone();
+            spawn_requests.send(SpawnRequest::Child {
+                position: house.position
+                    - Vec2::new(0.0, house.footprint.extent_towards(-Vec2::Y) + 4.0),
+                parents: (villager_id, partner.0),
+                residence_id: resident.residence_id,
+                surname,
+            });
+        }
+    }
+}
+
+// the ones close to a villager who has died grieve, and everyone forgets the villagers who are gone
+fn mourn_departed(
+    mut commands: Commands,
+    mut relationships: ResMut<Relationships>,
+    mut ev_creature_died: EventReader<CreatureDiedEvent>,
+    mut ev_creature_left_village: EventReader<CreatureLeftVillageEvent>,
+    partner_query: Query<(Entity, &Partner)>,
+    parents_query: Query<(Entity, &Parents)>,
+    villager_query: Query<(), With<Villager>>,
+) {
+    for CreatureDiedEvent(dead_id) in ev_creature_died.iter() {
+        // a list and not a map, so that the grief is given in the same order every time
+        let mut mourners: Vec<(Entity, f32)> = relationships
+            .of(*dead_id)
+            .map(|(other_id, relationship)| (other_id, relationship.closeness()))
+            .collect();
+        // family grieves the most
+        let mut family: Vec<Entity> = partner_query
+            .iter()
+            .filter(|(_, partner)| partner.0 == *dead_id)
+            .map(|(villager_id, _)| villager_id)
+            .collect();
+        for (child_id, Parents(a, b)) in parents_query.iter() {
+            if a == dead_id || b == dead_id {
+                family.push(child_id);
+            }
+        }
+        if let Ok((_, Parents(a, b))) = parents_query.get(*dead_id) {
+            family.push(*a);
+            family.push(*b);
+        }
+        for relative_id in family {
+            match mourners
+                .iter_mut()
+                .find(|(mourner_id, _)| *mourner_id == relative_id)
+            {
+                Some((_, intensity)) => *intensity = 1.0,
+                None => mourners.push((relative_id, 1.0)),
+            }
+        }
+
+        for (mourner_id, intensity) in mourners {
+            if intensity < FRIEND_FAMILIARITY * FRIEND_AFFINITY
+                || villager_query.get(mourner_id).is_err()
+            {
+                continue;
+            }
+            commands.entity(mourner_id).insert(Grief {
+                hours_left: GRIEF_HOURS * intensity,
+                intensity,
+            });
+        }
+    }
+
+    for CreatureLeftVillageEvent(villager_id) in ev_creature_left_village.iter() {
+        relationships.forget(*villager_id);
+        for (partner_id, partner) in partner_query.iter() {
+            if partner.0 == *villager_id {
+                commands.entity(partner_id).remove::<Partner>();
+            }
+        }
+    }
+}
+
+fn wear_off_grief(
+    mut commands: Commands,
+    time_cycle: Res<TimeCycle>,
+    mut grief_query: Query<(Entity, &mut Grief)>,
+) {
+    let hours = time_cycle.delta_hours();
+    for (villager_id, mut grief) in grief_query.iter_mut() {
+        grief.hours_left -= hours;
+        if grief.hours_left <= 0.0 {
+            commands.entity(villager_id).remove::<Grief>();
+        }
+    }
+}
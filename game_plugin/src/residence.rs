@@ -1,22 +1,28 @@
-use std::collections::{vec_deque, VecDeque};
-use std::iter::FromIterator;
+use std::collections::VecDeque;
 
 use bevy::{
-    math::{Vec2, Vec3},
-    prelude::{AppBuilder, Commands, Entity, EventWriter, Plugin, Res},
+    math::Vec2,
+    prelude::{
+        AppBuilder, BuildChildren, Commands, DespawnRecursiveExt, Entity, EventWriter, Handle,
+        Plugin, Res,
+    },
 };
-use rand::{prelude::ThreadRng, Rng};
+use rand::Rng;
 
 use crate::tree_cutting::ResourceCarrier;
 use crate::{
-    behaviour::{Task, Walker},
-    creatures::{ConstructionSkill, Creature, Fatigue},
-    loading::Materials,
-    physics::{Mobile, PhysicalObject, Speed},
-    random_names::RANDOM_NAMES,
-    sprite_helpers::spawn_sprite_bundles,
+    animation::{spawn_carried_wood, Animation},
+    behaviour::Walker,
+    blueprints::SpawnRequest,
+    combat::combat_bundle,
+    creatures::{ConstructionSkill, Creature, Fatigue, Perception, Personality},
+    definitions::{Archetype, CreatureDefinition},
+    loading::{Materials, SpriteSheets},
+    mood::{Mood, Needs},
+    names::VillagerName,
+    physics::{Mobile, PhysicalObject, Velocity},
+    sprite_helpers::spawn_animated_sprite_bundles,
     tree_cutting::TaskQue,
-    village::VillageTask,
     world_gen::SimParams,
 };
 
@@ -24,18 +30,11 @@ pub struct CreatureJoinedVillageEvent(pub Entity);
 
 pub struct CreatureLeftVillageEvent(pub Entity);
 
-pub struct VillagerSettledEvent {
-    pub resident: Entity,
-    pub residence: Entity,
-}
-
 pub struct Resident {
     pub residence_id: Entity,
 }
 
-pub struct Villager {
-    pub task: Option<VillageTask>,
-}
+pub struct Villager;
 
 pub struct ResidencePlugin;
 
@@ -46,43 +45,78 @@ impl Plugin for ResidencePlugin {
     }
 }
 
+#[allow(clippy::too_many_arguments)]
 pub fn spawn_villager(
     commands: &mut Commands,
     materials: &Res<Materials>,
+    sprite_sheets: &Res<SpriteSheets>,
     position: Vec2,
+    archetype: &Handle<CreatureDefinition>,
+    creature_definition: &CreatureDefinition,
     sim_params: &Res<SimParams>,
-    ev_creature_joined_village: &mut EventWriter<CreatureJoinedVillageEvent>,
+    name: VillagerName,
+    rng: &mut impl Rng,
 ) -> Entity {
-    let bounding_box = Vec3::new(16.0, 16.0, 16.0);
-    let name = RANDOM_NAMES[rand::thread_rng().gen_range(0..RANDOM_NAMES.len() - 1)];
-    let creature_id = spawn_sprite_bundles(
+    let personality = Personality {
+        sociability: rng.gen_range(0.0..1.0),
+        homeliness: rng.gen_range(0.0..1.0),
+    };
+    let creature_id = spawn_animated_sprite_bundles(
         commands,
-        Vec3::ONE,
-        position,
-        bounding_box,
-        materials.man.clone(),
+        PhysicalObject {
+            position,
+            footprint: creature_definition.footprint,
+        },
+        creature_definition.bounding_box(),
+        sprite_sheets.villager.clone(),
         materials.shadow.clone(),
-        sim_params.world_rect.size,
+        &sim_params.world_rect,
     )
-    .insert(Villager {
-        task: Option::<VillageTask>::None,
-    })
-    .insert(Creature { name })
+    .with_children(|parent| spawn_carried_wood(parent, materials.wood_logs.clone()))
+    .insert(Animation::new(position))
+    .insert(Villager)
+    .insert(Creature { personality })
+    .insert(name)
+    .insert(Archetype(archetype.clone()))
     .insert(Fatigue(0.0))
-    .insert(ConstructionSkill(0.75)) // just a sample value, 75% of the standard speed
-    .insert(PhysicalObject { position })
-    .insert(Mobile(Speed(0.0)))
+    .insert(ConstructionSkill(creature_definition.construction_skill))
+    .insert(Mobile(Velocity::default()))
     .insert(ResourceCarrier {
-        max_wood: 10.0,
+        max_wood: creature_definition.max_wood,
         wood: 0.0,
     })
     .insert(Walker {
-        acceleration: 15.0,
-        max_speed: 3.0,
+        acceleration: creature_definition.acceleration,
+        deceleration: creature_definition.deceleration,
+        max_speed: creature_definition.max_speed,
+    })
+    .insert(Perception {
+        radius: creature_definition.perception_radius,
     })
+    .insert_bundle(combat_bundle(creature_definition))
     .insert(TaskQue(VecDeque::new()))
-    //.insert(TaskQue(VecDeque::from_iter([Task::WanderAimlessly])))
+    .insert(Needs::default())
+    .insert(Mood::default())
     .id();
     creature_id
-    // TODO: joined village
+}
+
+/// Lets a villager go from the village: the wood they carry is dropped where they stand, the village is told and they are despawned.
+/// Each villager is let go of once, so a villager who is dying is left to `handle_deaths` and not let go of for any other reason.
+pub fn let_go_of_villager(
+    commands: &mut Commands,
+    villager_id: Entity,
+    position: Vec2,
+    carrier: Option<&ResourceCarrier>,
+    ev_creature_left_village: &mut EventWriter<CreatureLeftVillageEvent>,
+    spawn_requests: &mut EventWriter<SpawnRequest>,
+) {
+    if let Some(carrier) = carrier.filter(|carrier| carrier.wood > 0.0) {
+        spawn_requests.send(SpawnRequest::LogPile {
+            position,
+            wood: carrier.wood,
+        });
+    }
+    ev_creature_left_village.send(CreatureLeftVillageEvent(villager_id));
+    commands.entity(villager_id).despawn_recursive();
 }
@@ -0,0 +1,271 @@
+use std::{
+    cmp::Reverse,
+    collections::{BinaryHeap, HashMap, VecDeque},
+    iter::once,
+};
+
+use bevy::prelude::*;
+
+use crate::{
+    behaviour::Walker,
+    designations::ConstructionSite,
+    hex_coords::Hex,
+    land_grid::{LandGrid, Surface},
+    physics::{Footprint, Mobile, PhysicalObject},
+    time_cycle::TimeCycle,
+    GameState,
+};
+
+// game hours of walking over a tile which wear the grass down to a dirt path
+const DIRT_PATH_TRAFFIC: f32 = 0.5;
+// paths used less than this grow over again
+const GRASS_TRAFFIC: f32 = 0.2;
+// busy paths are worth paving
+const PAVING_TRAFFIC: f32 = 2.0;
+const PAVING_WOOD: f32 = 2.0;
+const PAVING_HOURS: f32 = 1.0;
+const TRAFFIC_HALF_LIFE_HOURS: f32 = 48.0;
+
+// a search bigger than this gives up and walks in a straight line
+const MAX_SEARCH_TILES: usize = 4000;
+// close enough to a waypoint to head to the next one
+const WAYPOINT_RADIUS: f32 = 3.0;
+// a destination moved further than this needs a new route
+const REROUTE_DISTANCE: f32 = 20.0;
+// far away a destination may move this share of the way left before it needs a new route, e.g. a walking target
+const REROUTE_SHARE: f32 = 0.25;
+
+pub struct RoadsPlugin;
+
+impl Plugin for RoadsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_system_set(
+            SystemSet::on_update(GameState::Playing)
+                .with_system(record_foot_traffic.system().after("travel"))
+                .with_system(maintain_roads.system()),
+        );
+    }
+}
+
+/// A busy dirt path to be paved, the villagers bring wood to it and work on it like on any `ConstructionSite`
+pub struct RoadWorks {
+    pub hex: Hex,
+}
+
+/// Waypoints over the land grid towards a destination, following paths and roads where they help
+pub struct Route {
+    destination: Vec2,
+    waypoints: VecDeque<Vec2>,
+}
+
+impl Route {
+    /// Whether the route still heads to a destination which moved since it was planned.
+    /// The further away the destination, the more it may move, so that a long chase is not planned again every few steps.
+    pub fn leads_to(&self, position: Vec2, destination: Vec2) -> bool {
+        let tolerance = REROUTE_DISTANCE.max(position.distance(destination) * REROUTE_SHARE);
+        self.destination.distance(destination) < tolerance
+    }
+
+    /// The point to walk to and the distance left along the route.
+    /// The last leg always ends at `destination`, which may have moved a little since the route was planned.
+    pub fn next_waypoint(&mut self, position: Vec2, destination: Vec2) -> (Vec2, f32) {
+        while let Some(waypoint) = self.waypoints.front() {
+            if position.distance(*waypoint) > WAYPOINT_RADIUS {
+                break;
+            }
+            self.waypoints.pop_front();
+        }
+
+        let next = self.waypoints.front().copied().unwrap_or(destination);
+        let mut distance = position.distance(next);
+        let mut previous = next;
+        for waypoint in self.waypoints.iter().skip(1).chain(once(&destination)) {
+            distance += previous.distance(*waypoint);
+            previous = *waypoint;
+        }
+        (next, distance)
+    }
+}
+
+/// Plans the cheapest way over the land grid, see `Surface::path_cost`
+pub fn find_route(land_grid: &LandGrid, from: Vec2, to: Vec2) -> Route {
+    let start = land_grid.hex_at(from);
+    let goal = land_grid.hex_at(to);
+    let waypoints = match find_hex_path(land_grid, start, goal) {
+        Some(path) => {
+            let path = straighten(land_grid, &path);
+            // the start and goal tiles are crossed straight to the actual positions
+            path.iter()
+                .skip(1)
+                .take(path.len().saturating_sub(2))
+                .map(|hex| land_grid.hex_center(*hex))
+                .collect()
+        }
+        None => VecDeque::new(),
+    };
+    Route {
+        destination: to,
+        waypoints,
+    }
+}
+
+fn path_cost(land_grid: &LandGrid, hex: Hex) -> u32 {
+    land_grid
+        .tile(hex)
+        .map_or(Surface::Grass, |tile| tile.surface)
+        .path_cost()
+}
+
+// A* search, hexes out of the world cannot be walked on
+fn find_hex_path(land_grid: &LandGrid, start: Hex, goal: Hex) -> Option<Vec<Hex>> {
+    let min_cost = Surface::PavedRoad.path_cost();
+    let mut open = BinaryHeap::new();
+    let mut came_from: HashMap<Hex, Hex> = HashMap::new();
+    let mut cost_so_far: HashMap<Hex, u32> = HashMap::new();
+    open.push(Reverse((0, start.q, start.r)));
+    cost_so_far.insert(start, 0);
+
+    while let Some(Reverse((_, q, r))) = open.pop() {
+        let hex = Hex::new(q, r);
+        if hex == goal {
+            let mut path = vec![goal];
+            while let Some(previous) = came_from.get(path.last().unwrap()) {
+                path.push(*previous);
+            }
+            path.reverse();
+            return Some(path);
+        }
+        if cost_so_far.len() > MAX_SEARCH_TILES {
+            return None;
+        }
+
+        let cost = cost_so_far[&hex];
+        for next in hex.neighbours() {
+            if !land_grid.contains(next) && next != goal {
+                continue;
+            }
+            let next_cost = cost + path_cost(land_grid, next);
+            if cost_so_far
+                .get(&next)
+                .map_or(true, |known| next_cost < *known)
+            {
+                cost_so_far.insert(next, next_cost);
+                came_from.insert(next, hex);
+                let priority = next_cost + next.distance(goal) as u32 * min_cost;
+                open.push(Reverse((priority, next.q, next.r)));
+            }
+        }
+    }
+    None
+}
+
+// skips the hexes which can be cut across in a straight line without the way getting more expensive
+fn straighten(land_grid: &LandGrid, path: &[Hex]) -> Vec<Hex> {
+    let mut kept = vec![path[0]];
+    let mut anchor = 0;
+    while anchor + 1 < path.len() {
+        let mut next = anchor + 1;
+        let mut path_so_far = path_cost(land_grid, path[next]);
+        for candidate in anchor + 2..path.len() {
+            path_so_far += path_cost(land_grid, path[candidate]);
+            let line = path[anchor].line_to(path[candidate]);
+            let is_cheaper = line.iter().all(|hex| land_grid.contains(*hex))
+                && line[1..]
+                    .iter()
+                    .map(|hex| path_cost(land_grid, *hex))
+                    .sum::<u32>()
+                    <= path_so_far;
+            if !is_cheaper {
+                break;
+            }
+            next = candidate;
+        }
+        kept.push(path[next]);
+        anchor = next;
+    }
+    kept
+}
+
+fn record_foot_traffic(
+    time_cycle: Res<TimeCycle>,
+    mut land_grid: ResMut<LandGrid>,
+    walker_query: Query<(&PhysicalObject, &Mobile), With<Walker>>,
+) {
+    let hours = time_cycle.delta_hours();
+
+    for (physical_object, mobile) in walker_query.iter() {
+        if !mobile.0.is_moving() {
+            continue;
+        }
+        let hex = land_grid.hex_at(physical_object.position);
+        land_grid.update_tile(hex, |tile| {
+            tile.traffic += hours;
+            if tile.surface == Surface::Grass && tile.traffic >= DIRT_PATH_TRAFFIC {
+                tile.surface = Surface::DirtPath;
+                return true;
+            }
+            false
+        });
+    }
+}
+
+// once per game hour unused paths wear off, and road works are set up on the busiest one unless a road is being paved
+fn maintain_roads(
+    mut commands: Commands,
+    time_cycle: Res<TimeCycle>,
+    mut hours_since_update: Local<f32>,
+    mut land_grid: ResMut<LandGrid>,
+    road_works_query: Query<(), With<RoadWorks>>,
+) {
+    *hours_since_update += time_cycle.delta_hours();
+    if *hours_since_update < 1.0 {
+        return;
+    }
+
+    let decay = 0.5f32.powf(*hours_since_update / TRAFFIC_HALF_LIFE_HOURS);
+    *hours_since_update = 0.0;
+    land_grid.update_tiles(|tile| {
+        if tile.traffic == 0.0 {
+            return false;
+        }
+        tile.traffic *= decay;
+        if tile.surface == Surface::DirtPath && tile.traffic < GRASS_TRAFFIC {
+            tile.surface = Surface::Grass;
+            return true;
+        }
+        false
+    });
+
+    if road_works_query.iter().next().is_some() {
+        return;
+    }
+    let busiest = land_grid
+        .tiles()
+        .filter(|tile| tile.surface == Surface::DirtPath && tile.traffic >= PAVING_TRAFFIC)
+        .max_by(|a, b| a.traffic.partial_cmp(&b.traffic).unwrap())
+        .map(|tile| tile.hex);
+    if let Some(hex) = busiest {
+        commands
+            .spawn()
+            .insert(PhysicalObject {
+                position: land_grid.hex_center(hex),
+                footprint: Footprint::Circle { radius: 1.0 },
+            })
+            .insert(ConstructionSite {
+                wood_required: PAVING_WOOD,
+                wood_delivered: 0.0,
+                work_hours_left: PAVING_HOURS,
+            })
+            .insert(RoadWorks { hex });
+        println!("Road works are set up at {:?}", hex);
+    }
+}
+
+/// Called by `construct` once the road works are done
+pub fn pave(land_grid: &mut LandGrid, hex: Hex) {
+    land_grid.update_tile(hex, |tile| {
+        tile.surface = Surface::PavedRoad;
+        true
+    });
+    println!("Paved a road at {:?}", hex);
+}
@@ -0,0 +1,326 @@
+use std::{fs, path::Path};
+
+use bevy::{ecs::system::SystemParam, prelude::*};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    blueprints::SpawnRequest,
+    designations::StockpileZone,
+    hexagon::HexagonBuilder,
+    land_grid::{ChunkCoord, LandGrid},
+    loading::Definitions,
+    names::VillagerName,
+    physics::PhysicalObject,
+    plants::{PlantSize, Tree},
+    time_cycle::TimeCycle,
+    tree_cutting::{LogPile, ResourceCarrier, ResourceStorage},
+    village::LivingSpace,
+    world_gen::SimParams,
+    GameState,
+};
+
+const SAVE_DIR: &str = "saves";
+const SAVE_FILE: &str = "quicksave.ron";
+
+pub struct SaveGameEvent;
+
+pub struct LoadGameEvent;
+
+/// A saved game to start from instead of generating a world, while entering `Playing`
+pub struct LoadedGame(pub SaveGame);
+
+pub struct SaveGamePlugin;
+
+impl Plugin for SaveGamePlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.add_event::<SaveGameEvent>()
+            .add_event::<LoadGameEvent>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Paused)
+                    .with_system(save_game.system())
+                    .with_system(load_game.system()),
+            );
+    }
+}
+
+/// Roads, stockpile zones, designations and relationships are not saved, they start over after loading.
+/// Neither are the counts of the village, the restored villagers join it again.
+#[derive(Serialize, Deserialize)]
+pub struct SaveGame {
+    seed: u64,
+    /// The tiles the saved positions are on
+    hexagon_builder: HexagonBuilder,
+    world_size: (f32, f32),
+    pub day: u64,
+    pub seconds_of_day: u32,
+    /// Chunks whose trees are generated already, see `ChunkStreaming`
+    pub chunks: Vec<ChunkCoord>,
+    trees: Vec<SavedTree>,
+    villagers: Vec<SavedVillager>,
+    houses: Vec<(f32, f32)>,
+    storages: Vec<SavedStorage>,
+    log_piles: Vec<SavedStorage>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedTree {
+    position: (f32, f32),
+    size: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedVillager {
+    name: VillagerName,
+    position: (f32, f32),
+    wood: f32,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SavedStorage {
+    position: (f32, f32),
+    wood: f32,
+}
+
+/// The resources and entities a save is made of
+#[derive(SystemParam)]
+pub struct GameToSave<'a> {
+    sim_params: Res<'a, SimParams>,
+    time_cycle: Res<'a, TimeCycle>,
+    land_grid: Res<'a, LandGrid>,
+    tree_query: Query<'a, (&'static PhysicalObject, &'static PlantSize), With<Tree>>,
+    villager_query: Query<
+        'a,
+        (
+            &'static VillagerName,
+            &'static PhysicalObject,
+            &'static ResourceCarrier,
+        ),
+    >,
+    house_query: Query<'a, &'static PhysicalObject, With<LivingSpace>>,
+    storage_query:
+        Query<'a, (&'static PhysicalObject, &'static ResourceStorage), Without<StockpileZone>>,
+    log_pile_query: Query<'a, (&'static PhysicalObject, &'static LogPile)>,
+}
+
+impl<'a> GameToSave<'a> {
+    fn save(&self) -> SaveGame {
+        SaveGame {
+            seed: self.sim_params.seed,
+            hexagon_builder: self.sim_params.hexagon_builder.clone(),
+            world_size: self.sim_params.world_rect.size.into(),
+            day: self.time_cycle.day(),
+            seconds_of_day: self.time_cycle.seconds_of_day(),
+            chunks: self.land_grid.generated_chunks(),
+            trees: self
+                .tree_query
+                .iter()
+                .map(|(physical_object, plant_size)| SavedTree {
+                    position: physical_object.position.into(),
+                    size: plant_size.current,
+                })
+                .collect(),
+            villagers: self
+                .villager_query
+                .iter()
+                .map(|(name, physical_object, carrier)| SavedVillager {
+                    name: name.clone(),
+                    position: physical_object.position.into(),
+                    wood: carrier.wood,
+                })
+                .collect(),
+            houses: self
+                .house_query
+                .iter()
+                .map(|physical_object| physical_object.position.into())
+                .collect(),
+            storages: self
+                .storage_query
+                .iter()
+                .map(|(physical_object, storage)| SavedStorage {
+                    position: physical_object.position.into(),
+                    wood: storage.wood,
+                })
+                .collect(),
+            log_piles: self
+                .log_pile_query
+                .iter()
+                .map(|(physical_object, log_pile)| SavedStorage {
+                    position: physical_object.position.into(),
+                    wood: log_pile.wood,
+                })
+                .collect(),
+        }
+    }
+}
+
+fn save_game(mut ev_save_game: EventReader<SaveGameEvent>, game_to_save: GameToSave) {
+    if ev_save_game.iter().next().is_none() {
+        return;
+    }
+
+    match write_save(&game_to_save.save()) {
+        Ok(()) => println!("Game is saved to {}/{}", SAVE_DIR, SAVE_FILE),
+        Err(error) => println!("Could not save the game: {}", error),
+    }
+}
+
+fn write_save(save: &SaveGame) -> Result<(), anyhow::Error> {
+    let content = ron::ser::to_string_pretty(save, ron::ser::PrettyConfig::default())?;
+    fs::create_dir_all(SAVE_DIR)?;
+    fs::write(Path::new(SAVE_DIR).join(SAVE_FILE), content)?;
+    Ok(())
+}
+
+// the world is torn down and entered again like on restart, `generate_world` then restores the save
+fn load_game(
+    mut commands: Commands,
+    mut ev_load_game: EventReader<LoadGameEvent>,
+    mut state: ResMut<State<GameState>>,
+    mut sim_params: ResMut<SimParams>,
+) {
+    if ev_load_game.iter().next().is_none() {
+        return;
+    }
+
+    let save = match read_save() {
+        Ok(save) => save,
+        Err(error) => {
+            println!("Could not load the game: {}", error);
+            return;
+        }
+    };
+    if state.replace(GameState::Playing).is_err() {
+        return;
+    }
+    sim_params.hexagon_builder = save.hexagon_builder.clone();
+    let (columns, rows) = sim_params
+        .hexagon_builder
+        .get_world_columns_rows(save.world_size.0, save.world_size.1);
+    sim_params.world_rect = sim_params.hexagon_builder.get_world_rect(columns, rows);
+    sim_params.seed = save.seed;
+    commands.insert_resource(LoadedGame(save));
+    println!("Game is loaded from {}/{}", SAVE_DIR, SAVE_FILE);
+}
+
+fn read_save() -> Result<SaveGame, anyhow::Error> {
+    let content = fs::read_to_string(Path::new(SAVE_DIR).join(SAVE_FILE))?;
+    Ok(ron::de::from_str(&content)?)
+}
+
+/// Spawns what was saved instead of a generated world
+pub fn restore_world(
+    save: &SaveGame,
+    definitions: &Definitions,
+    spawn_requests: &mut EventWriter<SpawnRequest>,
+) {
+    // villagers are loaded empty handed, what they carried is in the first storage
+    let carried_wood: f32 = save.villagers.iter().map(|villager| villager.wood).sum();
+    for (i, storage) in save.storages.iter().enumerate() {
+        spawn_requests.send(SpawnRequest::Stockpile {
+            position: storage.position.into(),
+            wood: if i == 0 {
+                storage.wood + carried_wood
+            } else {
+                storage.wood
+            },
+        });
+    }
+    for log_pile in save.log_piles.iter() {
+        spawn_requests.send(SpawnRequest::LogPile {
+            position: log_pile.position.into(),
+            wood: log_pile.wood,
+        });
+    }
+    for position in save.houses.iter() {
+        spawn_requests.send(SpawnRequest::House {
+            position: (*position).into(),
+        });
+    }
+    for villager in save.villagers.iter() {
+        spawn_requests.send(SpawnRequest::Villager {
+            position: villager.position.into(),
+            name: Some(villager.name.clone()),
+        });
+    }
+    for tree in save.trees.iter() {
+        spawn_requests.send(SpawnRequest::Tree {
+            species: definitions.tree.clone(),
+            position: tree.position.into(),
+            size: tree.size,
+        });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use bevy::{app::Events, ecs::system::System};
+
+    use super::*;
+    use crate::{
+        residence::Villager,
+        scenarios::{build_app, scenario_params},
+        village::Village,
+        world_gen::generate_world,
+    };
+
+    const VILLAGERS: u32 = 3;
+    const TICKS: usize = 10;
+
+    fn run_once<Out>(world: &mut World, mut system: impl System<In = (), Out = Out>) -> Out {
+        system.initialize(world);
+        let out = system.run((), world);
+        system.apply_buffers(world);
+        out
+    }
+
+    fn save_of(game_to_save: GameToSave) -> SaveGame {
+        game_to_save.save()
+    }
+
+    fn habitants_count(world: &mut World) -> u32 {
+        let mut village_query = world.query::<&Village>();
+        let village = village_query.iter(world).next().unwrap();
+        village.habitants_count
+    }
+
+    // the restored villagers join the village again, it counts each of them once
+    #[test]
+    fn loading_keeps_the_habitants_count() {
+        let mut app = build_app(scenario_params(5));
+        let mut spawn_requests = app
+            .world
+            .get_resource_mut::<Events<SpawnRequest>>()
+            .unwrap();
+        for i in 0..VILLAGERS {
+            spawn_requests.send(SpawnRequest::Villager {
+                position: Vec2::new(30.0 * i as f32, 0.0),
+                name: None,
+            });
+        }
+        for _ in 0..TICKS {
+            app.update();
+        }
+        assert_eq!(habitants_count(&mut app.world), VILLAGERS);
+
+        let save = run_once(&mut app.world, save_of.system());
+        let content = ron::ser::to_string(&save).unwrap();
+        let save: SaveGame = ron::de::from_str(&content).unwrap();
+
+        // a new world entered with the save, like `load_game` does
+        let mut app = build_app(scenario_params(save.seed));
+        let mut village_query = app.world.query_filtered::<Entity, With<Village>>();
+        let villages: Vec<Entity> = village_query.iter(&app.world).collect();
+        for village in villages {
+            app.world.despawn(village);
+        }
+        app.world.insert_resource(LoadedGame(save));
+        run_once(&mut app.world, generate_world.system());
+        for _ in 0..TICKS {
+            app.update();
+        }
+
+        let mut villager_query = app.world.query_filtered::<(), With<Villager>>();
+        assert_eq!(villager_query.iter(&app.world).count(), VILLAGERS as usize);
+        assert_eq!(habitants_count(&mut app.world), VILLAGERS);
+    }
+}
@@ -0,0 +1,314 @@
+//! Small headless games described in RON files, run by `tests/scenarios.rs`
+
+use std::{fmt, fs, path::Path};
+
+use bevy::{app::Events, prelude::*};
+use serde::Deserialize;
+
+use crate::{
+    actions::Actions,
+    behaviour::MovementPlugin,
+    blueprints::{BlueprintsPlugin, SpawnRequest},
+    combat::CombatPlugin,
+    definitions::{DefinitionsPlugin, SpeciesDefinition},
+    designations::DesignationsPlugin,
+    headless::build_headless,
+    hexagon::HexagonBuilder,
+    idle::IdlePlugin,
+    loading::{Definitions, Materials, SpriteSheets},
+    mood::MoodPlugin,
+    names::NamesPlugin,
+    plants::{PlantLifePlugin, Tree},
+    player_commands::{ExecuteCommandEvent, PlayerCommand, PlayerCommandsPlugin},
+    relationships::RelationshipsPlugin,
+    residence::{ResidencePlugin, Resident, Villager},
+    time_cycle::TimeCyclePlugin,
+    tree_cutting::{ResourceStorage, TaskQuePlugin},
+    village::{Village, VillagePlugin},
+    weather::WeatherChangedEvent,
+    world_gen::SimParams,
+    GameState,
+};
+
+const WORLD_SIZE: f32 = 1000.0;
+
+/// Entities spawned before the first tick, then commands of the player, then ticks and expectations checked after them
+#[derive(Deserialize)]
+pub struct Scenario {
+    pub seed: u64,
+    pub ticks: u32,
+    pub entities: Vec<ScenarioEntity>,
+    /// Executed on the first tick, once the entities are there
+    #[serde(default)]
+    pub commands: Vec<PlayerCommand>,
+    pub expect: Vec<Expectation>,
+}
+
+/// See `SpawnRequest`, positions are relative to the middle of the world
+#[derive(Deserialize)]
+pub enum ScenarioEntity {
+    Villager { position: (f32, f32) },
+    Wolf { position: (f32, f32) },
+    Tree { position: (f32, f32), size: f32 },
+    House { position: (f32, f32) },
+    Stockpile { position: (f32, f32), wood: f32 },
+    LogPile { position: (f32, f32), wood: f32 },
+}
+
+#[derive(Deserialize, Debug)]
+pub enum Expectation {
+    /// At least this much more wood in all storages together than right after spawning
+    StoredWoodIncreasedBy(f32),
+    EveryVillagerHasResident,
+    Villagers(usize),
+    /// At least this many of the trees are gone
+    TreesFelled(usize),
+}
+
+/// The expectations that were not met
+#[derive(Debug)]
+pub struct ScenarioFailure(pub Vec<String>);
+
+impl fmt::Display for ScenarioFailure {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.0.join(", "))
+    }
+}
+
+impl std::error::Error for ScenarioFailure {}
+
+pub fn load_scenario(path: &Path) -> Result<Scenario, anyhow::Error> {
+    Ok(ron::de::from_str(&fs::read_to_string(path)?)?)
+}
+
+/// An empty world, the entities of the scenario are spawned into it
+pub fn scenario_params(seed: u64) -> SimParams {
+    let hexagon_builder = HexagonBuilder::new(10.0);
+    let (columns, rows) = hexagon_builder.get_world_columns_rows(WORLD_SIZE, WORLD_SIZE);
+    SimParams {
+        start_pos: Vec2::ZERO,
+        world_rect: hexagon_builder.get_world_rect(columns, rows),
+        hexagon_builder,
+        seed,
+        villagers: 0,
+        houses: 0,
+        tree_density: 0.0,
+        starting_wood: 0.0,
+    }
+}
+
+/// The simulation plugins of the game, without a window, rendering, audio or the generated world
+pub fn build_app(sim_params: SimParams) -> App {
+    let mut app_builder = build_headless(sim_params);
+    app_builder
+        .add_state(GameState::Playing)
+        .init_resource::<Actions>()
+        .add_event::<WeatherChangedEvent>()
+        .init_resource::<Materials>()
+        .init_resource::<SpriteSheets>()
+        .add_plugin(TimeCyclePlugin)
+        .add_plugin(DefinitionsPlugin)
+        .add_plugin(VillagePlugin)
+        .add_plugin(ResidencePlugin)
+        .add_plugin(NamesPlugin)
+        .add_plugin(PlantLifePlugin)
+        .add_plugin(BlueprintsPlugin)
+        .add_plugin(MovementPlugin)
+        .add_plugin(TaskQuePlugin)
+        .add_plugin(IdlePlugin)
+        .add_plugin(CombatPlugin)
+        .add_plugin(PlayerCommandsPlugin)
+        .add_plugin(DesignationsPlugin)
+        .add_plugin(MoodPlugin)
+        .add_plugin(RelationshipsPlugin);
+    let mut app = app_builder.app;
+
+    let definitions = add_definitions(&mut app.world);
+    app.world.insert_resource(definitions);
+    app.world.spawn().insert(Village {
+        habitants_count: 0,
+        homeless_count: 0,
+    });
+    app
+}
+
+// the definitions are read straight away, instead of being loaded by the asset server
+fn add_definitions(world: &mut World) -> Definitions {
+    fn add<T: bevy::asset::Asset + for<'de> Deserialize<'de>>(
+        world: &mut World,
+        ron: &str,
+    ) -> Handle<T> {
+        let definition: T = ron::de::from_str(ron).unwrap();
+        world
+            .get_resource_mut::<Assets<T>>()
+            .unwrap()
+            .add(definition)
+    }
+
+    Definitions {
+        world: add(
+            world,
+            include_str!("../../assets/definitions/world.world.ron"),
+        ),
+        tree: add(
+            world,
+            include_str!("../../assets/definitions/tree.species.ron"),
+        ),
+        villager: add(
+            world,
+            include_str!("../../assets/definitions/villager.creature.ron"),
+        ),
+        wolf: add(
+            world,
+            include_str!("../../assets/definitions/wolf.creature.ron"),
+        ),
+        house: add(
+            world,
+            include_str!("../../assets/definitions/house.building.ron"),
+        ),
+        stockpile: add(
+            world,
+            include_str!("../../assets/definitions/stockpile.building.ron"),
+        ),
+        log_pile: add(
+            world,
+            include_str!("../../assets/definitions/log_pile.building.ron"),
+        ),
+    }
+}
+
+/// Sends the spawn requests for the entities of the scenario, they are spawned on the next tick
+pub fn request_entities(app: &mut App, scenario: &Scenario) {
+    let tree_species = app
+        .world
+        .get_resource::<Definitions>()
+        .unwrap()
+        .tree
+        .clone();
+    let mut spawn_requests = app
+        .world
+        .get_resource_mut::<Events<SpawnRequest>>()
+        .unwrap();
+    for entity in scenario.entities.iter() {
+        spawn_requests.send(entity.spawn_request(&tree_species));
+    }
+}
+
+pub fn run_scenario(scenario: &Scenario) -> Result<(), ScenarioFailure> {
+    let mut app = build_app(scenario_params(scenario.seed));
+    request_entities(&mut app, scenario);
+    app.update();
+
+    let start = Outcome::of(&mut app.world);
+    let mut ev_execute_command = app
+        .world
+        .get_resource_mut::<Events<ExecuteCommandEvent>>()
+        .unwrap();
+    for command in scenario.commands.iter() {
+        ev_execute_command.send(ExecuteCommandEvent(command.clone()));
+    }
+    for _ in 0..scenario.ticks {
+        app.update();
+    }
+    let end = Outcome::of(&mut app.world);
+
+    let failures: Vec<String> = scenario
+        .expect
+        .iter()
+        .filter_map(|expectation| expectation.check(&start, &end).err())
+        .collect();
+    if failures.is_empty() {
+        Ok(())
+    } else {
+        Err(ScenarioFailure(failures))
+    }
+}
+
+impl ScenarioEntity {
+    fn spawn_request(&self, tree_species: &Handle<SpeciesDefinition>) -> SpawnRequest {
+        match *self {
+            ScenarioEntity::Villager { position } => SpawnRequest::Villager {
+                position: position.into(),
+                name: None,
+            },
+            ScenarioEntity::Wolf { position } => SpawnRequest::Wolf {
+                position: position.into(),
+            },
+            ScenarioEntity::Tree { position, size } => SpawnRequest::Tree {
+                species: tree_species.clone(),
+                position: position.into(),
+                size,
+            },
+            ScenarioEntity::House { position } => SpawnRequest::House {
+                position: position.into(),
+            },
+            ScenarioEntity::Stockpile { position, wood } => SpawnRequest::Stockpile {
+                position: position.into(),
+                wood,
+            },
+            ScenarioEntity::LogPile { position, wood } => SpawnRequest::LogPile {
+                position: position.into(),
+                wood,
+            },
+        }
+    }
+}
+
+// what the expectations look at, right after spawning and after the last tick
+struct Outcome {
+    stored_wood: f32,
+    villagers: usize,
+    residents: usize,
+    trees: usize,
+}
+
+impl Outcome {
+    fn of(world: &mut World) -> Self {
+        let stored_wood = world
+            .query::<&ResourceStorage>()
+            .iter(world)
+            .map(|storage| storage.wood)
+            .sum();
+        let villagers = world
+            .query_filtered::<(), With<Villager>>()
+            .iter(world)
+            .count();
+        let residents = world
+            .query_filtered::<(), (With<Villager>, With<Resident>)>()
+            .iter(world)
+            .count();
+        let trees = world.query_filtered::<(), With<Tree>>().iter(world).count();
+        Self {
+            stored_wood,
+            villagers,
+            residents,
+            trees,
+        }
+    }
+}
+
+impl Expectation {
+    fn check(&self, start: &Outcome, end: &Outcome) -> Result<(), String> {
+        let is_met = match *self {
+            Expectation::StoredWoodIncreasedBy(wood) => end.stored_wood - start.stored_wood >= wood,
+            Expectation::EveryVillagerHasResident => end.residents == end.villagers,
+            Expectation::Villagers(count) => end.villagers == count,
+            Expectation::TreesFelled(count) => start.trees.saturating_sub(end.trees) >= count,
+        };
+        if is_met {
+            Ok(())
+        } else {
+            Err(format!(
+                "{:?} is not met: stored wood {} -> {}, villagers {} -> {} with {} residents, trees {} -> {}",
+                self,
+                start.stored_wood,
+                end.stored_wood,
+                start.villagers,
+                end.villagers,
+                end.residents,
+                start.trees,
+                end.trees
+            ))
+        }
+    }
+}
@@ -1,75 +1,194 @@
 use bevy::{
     ecs::system::EntityCommands,
     math::{Vec2, Vec3},
-    prelude::{BuildChildren, ColorMaterial, Commands, Handle, Sprite, SpriteBundle, Transform},
+    prelude::{
+        BuildChildren, ChildBuilder, ColorMaterial, Commands, Handle, Sprite, SpriteBundle,
+        SpriteSheetBundle, TextureAtlas, Transform,
+    },
 };
 
-use crate::layers::{OBJECT_LAYER, SHADOW_LAYER};
+use crate::{
+    animation::FRAME_SIZE,
+    hexagon::Rectangle,
+    layers::{object_depth, Shadow},
+    physics::{Footprint, PhysicalObject},
+};
+
+// The child sprite that shows the object itself, not its shadow
+pub struct MainSprite;
+
+/// How the shadow of an object is generated from its footprint
+#[derive(Clone, Copy, Debug)]
+pub enum ShadowStyle {
+    /// A small ellipse under the legs or the trunk
+    Contact,
+    /// A long ellipse falling away from the light, which comes from the top left
+    Directional,
+}
+
+impl ShadowStyle {
+    /// Offset and size of the shadow ellipse relative to the object position
+    pub fn ellipse(&self, footprint: Footprint, bounding_box: Vec3) -> (Vec2, Vec2) {
+        let radius = footprint.extent_towards(Vec2::X);
+        match self {
+            ShadowStyle::Contact => (Vec2::ZERO, Vec2::new(3.2, 1.6) * radius),
+            ShadowStyle::Directional => {
+                // taller buildings drop longer shadows
+                let length = bounding_box.y * 0.6;
+                (
+                    Vec2::new(length / 2.0, -radius * 0.3),
+                    Vec2::new(2.0 * radius + length, 1.4 * radius),
+                )
+            }
+        }
+    }
+}
 
 pub fn spawn_sprite_bundles<'a, 'b>(
     commands: &'b mut Commands<'a>,
     scale: Vec3,
-    position: Vec2,
+    physical_object: PhysicalObject,
     bounding_box: Vec3,
     main_material: Handle<ColorMaterial>,
     shadow: Handle<ColorMaterial>,
-    world_size: Vec2,
+    world_rect: &Rectangle,
 ) -> EntityCommands<'a, 'b> {
     spawn_sprite_bundles_(
         commands,
         scale,
-        position,
+        physical_object,
         bounding_box,
         main_material,
         shadow,
-        world_size,
+        ShadowStyle::Contact,
+        world_rect,
         Vec2::ZERO,
     )
 }
 
-pub fn get_translation(world_size: Vec2, position: Vec2) -> Vec3 {
-    Vec3::new(
-        position.x,
-        position.y,
-        OBJECT_LAYER + world_size.y - position.y,
-    )
+pub fn get_translation(world_rect: &Rectangle, position: Vec2) -> Vec3 {
+    position.extend(object_depth(world_rect, position.y))
 }
 
-pub fn spawn_sprite_bundles_<'a, 'b>(
-    commands: &'b mut Commands<'a>,
-    scale: Vec3,
-    position: Vec2,
+pub fn spawn_main_sprite(
+    parent: &mut ChildBuilder,
     bounding_box: Vec3,
     main_material: Handle<ColorMaterial>,
-    shadow: Handle<ColorMaterial>,
-    world_size: Vec2,
     origin: Vec2,
+) {
+    // the root is already y-sorted, so the sprite stays at its depth
+    parent
+        .spawn_bundle(SpriteBundle {
+            material: main_material,
+            transform: Transform::from_translation(
+                (Vec2::new(0.0, bounding_box.y / 2.0) + origin).extend(0.0),
+            ),
+            sprite: Sprite::new(Vec2::new(bounding_box.x, bounding_box.y)),
+            ..Default::default()
+        })
+        .insert(MainSprite);
+}
+
+pub fn spawn_animated_sprite(
+    parent: &mut ChildBuilder,
+    bounding_box: Vec3,
+    texture_atlas: Handle<TextureAtlas>,
+) {
+    // frames are drawn at their size in pixels, so they are scaled to the bounding box
+    parent
+        .spawn_bundle(SpriteSheetBundle {
+            texture_atlas,
+            transform: Transform {
+                translation: Vec3::new(0.0, bounding_box.y / 2.0, 0.0),
+                scale: (Vec2::new(bounding_box.x, bounding_box.y) / FRAME_SIZE).extend(1.0),
+                ..Default::default()
+            },
+            ..Default::default()
+        })
+        .insert(MainSprite);
+}
+
+/// The depth of the shadow is set by `y_sort_objects` once it is attached to its parent
+pub fn spawn_shadow(
+    parent: &mut ChildBuilder,
+    footprint: Footprint,
+    bounding_box: Vec3,
+    shadow: Handle<ColorMaterial>,
+    shadow_style: ShadowStyle,
+) {
+    let (offset, size) = shadow_style.ellipse(footprint, bounding_box);
+    parent
+        .spawn_bundle(SpriteBundle {
+            material: shadow,
+            transform: Transform::from_translation(offset.extend(0.0)),
+            sprite: Sprite::new(size),
+            ..Default::default()
+        })
+        .insert(Shadow);
+}
+
+fn spawn_root<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    scale: Vec3,
+    physical_object: PhysicalObject,
+    world_rect: &Rectangle,
 ) -> EntityCommands<'a, 'b> {
     let mut entity_commands = commands.spawn_bundle(SpriteBundle {
         transform: Transform {
-            translation: get_translation(world_size, position),
+            translation: get_translation(world_rect, physical_object.position),
             scale,
             ..Default::default()
         },
         ..Default::default()
     });
+    entity_commands.insert(physical_object);
+    entity_commands
+}
 
+#[allow(clippy::too_many_arguments)]
+pub fn spawn_sprite_bundles_<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    scale: Vec3,
+    physical_object: PhysicalObject,
+    bounding_box: Vec3,
+    main_material: Handle<ColorMaterial>,
+    shadow: Handle<ColorMaterial>,
+    shadow_style: ShadowStyle,
+    world_rect: &Rectangle,
+    origin: Vec2,
+) -> EntityCommands<'a, 'b> {
+    let mut entity_commands = spawn_root(commands, scale, physical_object, world_rect);
     entity_commands.with_children(|parent| {
-        parent.spawn_bundle(SpriteBundle {
-            material: main_material.clone(),
-            transform: Transform::from_translation(
-                (Vec2::new(0.0, bounding_box.y / 2.0) + origin).extend(OBJECT_LAYER),
-            ),
-            sprite: Sprite::new(Vec2::new(bounding_box.x, bounding_box.y)),
-            ..Default::default()
-        });
-        // TODO: right now shadows look out of place for bulky objects. Maybe we need customised realistic shadows
-        // parent.spawn_bundle(SpriteBundle {
-        //     transform: Transform::from_translation(Vec2::new(0.0, 0.0).extend(SHADOW_LAYER)),
-        //     sprite: Sprite::new(Vec2::new(bounding_box.x, bounding_box.z / 2.0) * shadow_factor), // TODO: do isometric calc for Y coord
-        //     material: shadow.clone(),
-        //     ..Default::default()
-        // });
+        spawn_main_sprite(parent, bounding_box, main_material, origin);
+        spawn_shadow(
+            parent,
+            physical_object.footprint,
+            bounding_box,
+            shadow,
+            shadow_style,
+        );
+    });
+    entity_commands
+}
+
+pub fn spawn_animated_sprite_bundles<'a, 'b>(
+    commands: &'b mut Commands<'a>,
+    physical_object: PhysicalObject,
+    bounding_box: Vec3,
+    texture_atlas: Handle<TextureAtlas>,
+    shadow: Handle<ColorMaterial>,
+    world_rect: &Rectangle,
+) -> EntityCommands<'a, 'b> {
+    let mut entity_commands = spawn_root(commands, Vec3::ONE, physical_object, world_rect);
+    entity_commands.with_children(|parent| {
+        spawn_animated_sprite(parent, bounding_box, texture_atlas);
+        spawn_shadow(
+            parent,
+            physical_object.footprint,
+            bounding_box,
+            shadow,
+            ShadowStyle::Contact,
+        );
     });
     entity_commands
 }
@@ -0,0 +1,453 @@
+use std::{collections::VecDeque, fs, path::Path};
+
+use bevy::prelude::*;
+
+use crate::{
+    actions::Actions,
+    plants::{PlantSize, Tree},
+    time_cycle::TimeCycle,
+    tree_cutting::{ResourceStorage, TaskCompletedEvent},
+    village::Village,
+    GameState,
+};
+
+// two weeks of hourly samples
+const HISTORY_HOURS: usize = 24 * 14;
+// the charts show the last two days
+const CHART_HOURS: usize = 48;
+const CHART_WIDTH: f32 = 240.0;
+const CHART_HEIGHT: f32 = 40.0;
+const LINE_WIDTH: f32 = 2.0;
+const STATS_DIR: &str = "stats";
+const STATS_FILE: &str = "village_stats.csv";
+
+/// Keeps the last `capacity` values, dropping the oldest
+pub struct RingBuffer<T> {
+    values: VecDeque<T>,
+    capacity: usize,
+}
+
+impl<T> RingBuffer<T> {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            values: VecDeque::with_capacity(capacity),
+            capacity,
+        }
+    }
+
+    pub fn push(&mut self, value: T) {
+        if self.values.len() == self.capacity {
+            self.values.pop_front();
+        }
+        self.values.push_back(value);
+    }
+
+    pub fn iter(&self) -> impl DoubleEndedIterator<Item = &T> + ExactSizeIterator {
+        self.values.iter()
+    }
+}
+
+/// The state of the village at the start of an in-game hour
+#[derive(Clone, Copy, Debug)]
+pub struct Sample {
+    /// See `TimeCycle::hours_since_start`
+    pub hour: u64,
+    pub population: u32,
+    pub homeless: u32,
+    pub stored_wood: f32,
+    pub trees: u32,
+    pub average_tree_size: f32,
+    /// Work tasks finished during the hour before, see `Task::is_work`
+    pub tasks_done: u32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Statistic {
+    Population,
+    Homeless,
+    StoredWood,
+    Trees,
+    AverageTreeSize,
+    TasksDone,
+}
+
+impl Statistic {
+    pub const ALL: [Statistic; 6] = [
+        Statistic::Population,
+        Statistic::Homeless,
+        Statistic::StoredWood,
+        Statistic::Trees,
+        Statistic::AverageTreeSize,
+        Statistic::TasksDone,
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Statistic::Population => "Population",
+            Statistic::Homeless => "Homeless",
+            Statistic::StoredWood => "Stored wood",
+            Statistic::Trees => "Trees",
+            Statistic::AverageTreeSize => "Average tree size",
+            Statistic::TasksDone => "Tasks per hour",
+        }
+    }
+
+    pub fn value(&self, sample: &Sample) -> f32 {
+        match self {
+            Statistic::Population => sample.population as f32,
+            Statistic::Homeless => sample.homeless as f32,
+            Statistic::StoredWood => sample.stored_wood,
+            Statistic::Trees => sample.trees as f32,
+            Statistic::AverageTreeSize => sample.average_tree_size,
+            Statistic::TasksDone => sample.tasks_done as f32,
+        }
+    }
+
+    fn colour(&self) -> Color {
+        match self {
+            Statistic::Population => Color::rgb(0.9, 0.9, 0.9),
+            Statistic::Homeless => Color::rgb(0.9, 0.4, 0.3),
+            Statistic::StoredWood => Color::rgb(0.8, 0.6, 0.3),
+            Statistic::Trees => Color::rgb(0.3, 0.8, 0.3),
+            Statistic::AverageTreeSize => Color::rgb(0.6, 0.9, 0.5),
+            Statistic::TasksDone => Color::rgb(0.4, 0.6, 0.9),
+        }
+    }
+}
+
+/// Hourly history of the village, for the statistics panel and balance analysis
+pub struct VillageStats {
+    pub samples: RingBuffer<Sample>,
+    last_hour: Option<u64>,
+    tasks_done: u32,
+}
+
+impl Default for VillageStats {
+    fn default() -> Self {
+        Self {
+            samples: RingBuffer::new(HISTORY_HOURS),
+            last_hour: None,
+            tasks_done: 0,
+        }
+    }
+}
+
+impl VillageStats {
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from(
+            "day,hour,population,homeless,stored_wood,trees,average_tree_size,tasks_done\n",
+        );
+        for sample in self.samples.iter() {
+            csv.push_str(&format!(
+                "{},{},{},{},{:.1},{},{:.3},{}\n",
+                sample.hour / 24,
+                sample.hour % 24,
+                sample.population,
+                sample.homeless,
+                sample.stored_wood,
+                sample.trees,
+                sample.average_tree_size,
+                sample.tasks_done
+            ));
+        }
+        csv
+    }
+}
+
+struct StatsMaterials {
+    background: Handle<ColorMaterial>,
+    chart: Handle<ColorMaterial>,
+    lines: Vec<Handle<ColorMaterial>>,
+}
+
+impl FromWorld for StatsMaterials {
+    fn from_world(world: &mut World) -> Self {
+        let mut materials = world.get_resource_mut::<Assets<ColorMaterial>>().unwrap();
+        StatsMaterials {
+            background: materials.add(Color::rgba(0.0, 0.0, 0.0, 0.7).into()),
+            chart: materials.add(Color::rgba(1.0, 1.0, 1.0, 0.05).into()),
+            lines: Statistic::ALL
+                .iter()
+                .map(|statistic| materials.add(statistic.colour().into()))
+                .collect(),
+        }
+    }
+}
+
+struct StatsPanel;
+
+/// The latest value of a statistic, above its chart
+struct StatsLabel(Statistic);
+
+/// The line of a chart from the value of an hour to the one of the next hour
+struct ChartSegment {
+    statistic: Statistic,
+    index: usize,
+}
+
+pub struct StatsPlugin;
+
+impl Plugin for StatsPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<VillageStats>()
+            .init_resource::<StatsMaterials>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_stats.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_stats_panel.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(count_tasks.system().label("count_tasks"))
+                    .with_system(sample_stats.system().after("count_tasks"))
+                    .with_system(toggle_stats_panel.system())
+                    .with_system(sync_stats_panel.system())
+                    .with_system(export_stats.system()),
+            );
+    }
+}
+
+fn reset_stats(mut stats: ResMut<VillageStats>) {
+    *stats = VillageStats::default();
+}
+
+fn count_tasks(
+    mut stats: ResMut<VillageStats>,
+    mut ev_task_completed: EventReader<TaskCompletedEvent>,
+) {
+    stats.tasks_done += ev_task_completed
+        .iter()
+        .filter(|event| event.task.is_work())
+        .count() as u32;
+}
+
+fn sample_stats(
+    time_cycle: Res<TimeCycle>,
+    mut stats: ResMut<VillageStats>,
+    village_query: Query<&Village>,
+    storage_query: Query<&ResourceStorage>,
+    tree_query: Query<&PlantSize, With<Tree>>,
+) {
+    let hour = time_cycle.hours_since_start();
+    if stats.last_hour == Some(hour) {
+        return;
+    }
+    let village = match village_query.single() {
+        Ok(village) => village,
+        Err(_) => return,
+    };
+
+    let (trees, total_size) = tree_query.iter().fold((0, 0.0), |(count, total), size| {
+        (count + 1, total + size.current)
+    });
+    let sample = Sample {
+        hour,
+        population: village.habitants_count,
+        homeless: village.homeless_count,
+        stored_wood: storage_query.iter().map(|storage| storage.wood).sum(),
+        trees,
+        average_tree_size: if trees > 0 {
+            total_size / trees as f32
+        } else {
+            0.0
+        },
+        tasks_done: stats.tasks_done,
+    };
+    stats.samples.push(sample);
+    stats.last_hour = Some(hour);
+    stats.tasks_done = 0;
+}
+
+fn remove_stats_panel(mut commands: Commands, panel_query: Query<Entity, With<StatsPanel>>) {
+    for panel_id in panel_query.iter() {
+        commands.entity(panel_id).despawn_recursive();
+    }
+}
+
+// the panel is spawned once when it is opened, `sync_stats_panel` fills it in
+fn toggle_stats_panel(
+    mut commands: Commands,
+    actions: Res<Actions>,
+    materials: Res<StatsMaterials>,
+    asset_server: Res<AssetServer>,
+    panel_query: Query<Entity, With<StatsPanel>>,
+) {
+    if !actions.toggle_stats {
+        return;
+    }
+    if let Some(panel_id) = panel_query.iter().next() {
+        commands.entity(panel_id).despawn_recursive();
+        return;
+    }
+
+    let font = asset_server.get_handle("fonts/FiraSans-Bold.ttf");
+    commands
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                position_type: PositionType::Absolute,
+                position: Rect {
+                    top: Val::Px(40.0),
+                    right: Val::Px(10.0),
+                    ..Default::default()
+                },
+                flex_direction: FlexDirection::ColumnReverse,
+                padding: Rect::all(Val::Px(8.0)),
+                ..Default::default()
+            },
+            material: materials.background.clone(),
+            ..Default::default()
+        })
+        .insert(StatsPanel)
+        .with_children(|parent| {
+            for (statistic, line_material) in Statistic::ALL.iter().zip(materials.lines.iter()) {
+                parent
+                    .spawn_bundle(TextBundle {
+                        text: Text::with_section(
+                            statistic.label(),
+                            TextStyle {
+                                font: font.clone(),
+                                font_size: 16.0,
+                                color: statistic.colour(),
+                            },
+                            Default::default(),
+                        ),
+                        ..Default::default()
+                    })
+                    .insert(StatsLabel(*statistic));
+                spawn_chart(parent, *statistic, &materials.chart, line_material);
+            }
+        });
+}
+
+// the segments are hidden until there are values for them
+fn spawn_chart(
+    parent: &mut ChildBuilder,
+    statistic: Statistic,
+    chart_material: &Handle<ColorMaterial>,
+    line_material: &Handle<ColorMaterial>,
+) {
+    parent
+        .spawn_bundle(NodeBundle {
+            style: Style {
+                size: Size::new(Val::Px(CHART_WIDTH), Val::Px(CHART_HEIGHT)),
+                margin: Rect {
+                    bottom: Val::Px(6.0),
+                    ..Default::default()
+                },
+                ..Default::default()
+            },
+            material: chart_material.clone(),
+            ..Default::default()
+        })
+        .with_children(|chart| {
+            for index in 0..CHART_HOURS - 1 {
+                chart
+                    .spawn_bundle(NodeBundle {
+                        style: Style {
+                            position_type: PositionType::Absolute,
+                            ..Default::default()
+                        },
+                        material: line_material.clone(),
+                        visible: Visible {
+                            is_visible: false,
+                            is_transparent: true,
+                        },
+                        ..Default::default()
+                    })
+                    .insert(ChartSegment { statistic, index });
+            }
+        });
+}
+
+// the charts are redrawn with every new sample, and when the panel is opened
+fn sync_stats_panel(
+    stats: Res<VillageStats>,
+    opened_query: Query<(), Added<StatsPanel>>,
+    mut label_query: Query<(&mut Text, &StatsLabel)>,
+    mut segment_query: Query<(&ChartSegment, &mut Style, &mut Transform, &mut Visible)>,
+    mut shown_hour: Local<Option<u64>>,
+) {
+    if opened_query.iter().next().is_none() && *shown_hour == stats.last_hour {
+        return;
+    }
+    *shown_hour = stats.last_hour;
+
+    let recent: Vec<&Sample> = stats
+        .samples
+        .iter()
+        .skip(stats.samples.iter().len().saturating_sub(CHART_HOURS))
+        .collect();
+    let values_of = |statistic: &Statistic| -> Vec<f32> {
+        recent
+            .iter()
+            .map(|sample| statistic.value(sample))
+            .collect()
+    };
+
+    for (mut text, StatsLabel(statistic)) in label_query.iter_mut() {
+        let latest = values_of(statistic).last().copied().unwrap_or(0.0);
+        text.sections[0].value = format!("{}: {:.1}", statistic.label(), latest);
+    }
+
+    for statistic in Statistic::ALL.iter() {
+        let points = chart_points(&values_of(statistic));
+        for (segment, mut style, mut transform, mut visible) in segment_query.iter_mut() {
+            if segment.statistic != *statistic {
+                continue;
+            }
+            let (from, to) = match (points.get(segment.index), points.get(segment.index + 1)) {
+                (Some(from), Some(to)) => (*from, *to),
+                _ => {
+                    visible.is_visible = false;
+                    continue;
+                }
+            };
+            // a bar as long as the gap between the points, turned around its middle to join them
+            let middle = (from + to) / 2.0;
+            let length = from.distance(to);
+            style.position = Rect {
+                left: Val::Px(middle.x - length / 2.0),
+                bottom: Val::Px(middle.y - LINE_WIDTH / 2.0),
+                ..Default::default()
+            };
+            style.size = Size::new(Val::Px(length), Val::Px(LINE_WIDTH));
+            let direction = to - from;
+            transform.rotation = Quat::from_rotation_z(direction.y.atan2(direction.x));
+            visible.is_visible = true;
+        }
+    }
+}
+
+// a point for every hour, scaled from zero to the highest value shown
+fn chart_points(values: &[f32]) -> Vec<Vec2> {
+    let max = values.iter().copied().fold(0.0, f32::max);
+    let step = CHART_WIDTH / (CHART_HOURS - 1) as f32;
+    values
+        .iter()
+        .enumerate()
+        .map(|(i, value)| {
+            let height = if max > 0.0 { value / max } else { 0.0 };
+            Vec2::new(
+                i as f32 * step,
+                LINE_WIDTH / 2.0 + height * (CHART_HEIGHT - LINE_WIDTH),
+            )
+        })
+        .collect()
+}
+
+fn export_stats(actions: Res<Actions>, stats: Res<VillageStats>) {
+    if !actions.export_stats {
+        return;
+    }
+    match write_stats(&stats) {
+        Ok(()) => println!("Statistics are exported to {}/{}", STATS_DIR, STATS_FILE),
+        Err(error) => println!("Could not export the statistics: {}", error),
+    }
+}
+
+fn write_stats(stats: &VillageStats) -> Result<(), anyhow::Error> {
+    fs::create_dir_all(STATS_DIR)?;
+    fs::write(Path::new(STATS_DIR).join(STATS_FILE), stats.to_csv())?;
+    Ok(())
+}
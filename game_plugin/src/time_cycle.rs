@@ -1,12 +1,46 @@
 use bevy::core::Time;
 use bevy::prelude::*;
+use std::fmt;
+use std::time::Duration;
 
-use crate::GameState;
+use crate::{save_game::LoadedGame, GameState};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Season {
+    Spring,
+    Summer,
+    Autumn,
+    Winter,
+}
+
+impl Season {
+    // trees only grow and seed in spring and summer
+    pub fn is_growing(&self) -> bool {
+        matches!(self, Season::Spring | Season::Summer)
+    }
+}
+
+const DAYS_PER_MONTH: u64 = 7;
+const MONTHS_PER_YEAR: u64 = 12;
+const DAYS_PER_YEAR: u64 = DAYS_PER_MONTH * MONTHS_PER_YEAR;
+const MONTHS: [&str; MONTHS_PER_YEAR as usize] = [
+    "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+];
+// The game starts on the first of March, at the beginning of spring
+const START_MONTH: u64 = 2;
+// Middle of June, as a fraction of the year
+const SUMMER_SOLSTICE: f32 = 5.5 / MONTHS_PER_YEAR as f32;
+// The fixed step of lockstep sessions and headless worlds, a tick per frame at 60 frames per second
+pub const TICK_SECONDS: f32 = 1.0 / 60.0;
 
 pub struct TimeCycle {
     day: u64,
     time: time::Time,
     speed: u32,
+    // Real seconds the simulation advances in this frame
+    step: f32,
+    // e.g. lockstep sessions and headless worlds advance by the same step every frame
+    fixed_step: Option<f32>,
 }
 impl Default for TimeCycle {
     fn default() -> Self {
@@ -15,6 +49,8 @@ impl Default for TimeCycle {
             // A new day begins at seven o’clock 😀
             time: time::Time::from_hms(7, 0, 0).unwrap(),
             speed: 400,
+            step: 0.0,
+            fixed_step: None,
         }
     }
 }
@@ -22,7 +58,8 @@ pub fn time_cycle_system(
     time: Res<Time>,
     mut time_cycle: ResMut<TimeCycle>,
 ) {
-    let delta = time.delta();
+    time_cycle.step = time_cycle.fixed_step.unwrap_or_else(|| time.delta_seconds());
+    let delta = Duration::from_secs_f32(time_cycle.step);
     let now = time_cycle.time + delta * time_cycle.speed;
     // Warning: Only apply to delta * speed < 24 hours
     if now <= time_cycle.time {
@@ -32,13 +69,93 @@ pub fn time_cycle_system(
 }
 
 impl TimeCycle {
-    // Set: From 6 p.m. to 5 a.m
+    // Daylight is centred around noon, so it's night from 6 p.m. to 6 a.m. at the equinoxes
     pub fn is_night(&self) -> bool {
-        let hour = self.time.hour();
-        hour >= 18 || hour <= 5
-    } 
-    pub fn to_string(&self) -> String {
-        format!("{}day {:02}:{:02}",self.day,self.time.hour(),self.time.minute())
+        let hour = self.time.hour() as f32 + self.time.minute() as f32 / 60.0;
+        let half_daylight = self.daylight_hours() / 2.0;
+        hour < 12.0 - half_daylight || hour >= 12.0 + half_daylight
+    }
+    // From 8 hours in the middle of winter to 16 hours in the middle of summer
+    pub fn daylight_hours(&self) -> f32 {
+        let year_fraction = (self.day_of_year() as f32 + 0.5) / DAYS_PER_YEAR as f32;
+        12.0 + 4.0 * ((year_fraction - SUMMER_SOLSTICE) * std::f32::consts::TAU).cos()
+    }
+    pub fn day(&self) -> u64 {
+        self.day
+    }
+    pub fn seconds_of_day(&self) -> u32 {
+        let (hour, minute, second) = self.time.as_hms();
+        hour as u32 * 3600 + minute as u32 * 60 + second as u32
+    }
+    // In-game seconds since the midnight before the game started, they keep adding up over the days
+    pub fn seconds_since_start(&self) -> f64 {
+        self.day as f64 * 86_400.0 + self.seconds_of_day() as f64 + self.time.nanosecond() as f64 / 1e9
+    }
+    // The time of a saved game, see `seconds_of_day`
+    pub fn restored(day: u64, seconds_of_day: u32) -> Self {
+        let seconds = seconds_of_day % (24 * 3600);
+        Self {
+            day,
+            time: time::Time::from_hms((seconds / 3600) as u8, (seconds / 60 % 60) as u8, (seconds % 60) as u8).unwrap(),
+            ..Default::default()
+        }
+    }
+    // Whole hours since the midnight before the game started
+    pub fn hours_since_start(&self) -> u64 {
+        self.day * 24 + self.time.hour() as u64
+    }
+    fn day_of_year(&self) -> u64 {
+        (START_MONTH * DAYS_PER_MONTH + self.day) % DAYS_PER_YEAR
+    }
+    pub fn day_of_month(&self) -> u64 {
+        self.day_of_year() % DAYS_PER_MONTH + 1
+    }
+    // Zero based, 0 is January
+    pub fn month(&self) -> u64 {
+        self.day_of_year() / DAYS_PER_MONTH
+    }
+    pub fn year(&self) -> u64 {
+        (START_MONTH * DAYS_PER_MONTH + self.day) / DAYS_PER_YEAR + 1
+    }
+    pub fn season(&self) -> Season {
+        match self.month() {
+            2..=4 => Season::Spring,
+            5..=7 => Season::Summer,
+            8..=10 => Season::Autumn,
+            _ => Season::Winter,
+        }
+    }
+    // Converts real seconds into in-game seconds
+    pub fn game_seconds(&self, real_seconds: f32) -> f32 {
+        real_seconds * self.speed as f32
+    }
+    // Converts in-game seconds back into the real seconds they took
+    pub fn real_seconds(&self, game_seconds: f64) -> f32 {
+        (game_seconds / self.speed as f64) as f32
+    }
+    // Real seconds the simulation advances in this frame, the frame time unless the step is fixed
+    pub fn delta_seconds(&self) -> f32 {
+        self.step
+    }
+    // In-game hours passing in this frame
+    pub fn delta_hours(&self) -> f32 {
+        self.game_seconds(self.step) / 3600.0
+    }
+    pub fn set_fixed_step(&mut self, fixed_step: Option<f32>) {
+        self.fixed_step = fixed_step;
+    }
+}
+impl fmt::Display for TimeCycle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{} {}, year {} {:02}:{:02}",
+            self.day_of_month(),
+            MONTHS[self.month() as usize],
+            self.year(),
+            self.time.hour(),
+            self.time.minute()
+        )
     }
 }
 pub struct TimeUi;
@@ -83,6 +200,22 @@ pub fn set_up_ui(
     .insert(TimeUi);
 }
 
+pub fn remove_ui(mut commands: Commands, ui_query: Query<Entity, With<TimeUi>>) {
+    for ui in ui_query.iter() {
+        commands.entity(ui).despawn_recursive();
+    }
+}
+
+pub fn reset_time_cycle(mut time_cycle: ResMut<TimeCycle>, loaded_game: Option<Res<LoadedGame>>) {
+    // the step is fixed by whoever runs the world, not by the game
+    let fixed_step = time_cycle.fixed_step;
+    *time_cycle = match loaded_game {
+        Some(loaded_game) => TimeCycle::restored(loaded_game.0.day, loaded_game.0.seconds_of_day),
+        None => TimeCycle::default(),
+    };
+    time_cycle.fixed_step = fixed_step;
+}
+
 pub fn sync_ui(
     mut ui_query: Query<&mut Text,With<TimeUi>>,
     time_cycle: Res<TimeCycle>
@@ -90,6 +223,11 @@ pub fn sync_ui(
     if let Ok(mut text) = ui_query.single_mut() {
         if let Some(text_section) = text.sections.get_mut(0) {
             text_section.value = time_cycle.to_string();
+            text_section.style.color = if time_cycle.is_night() {
+                Color::rgb(0.6, 0.65, 0.9)
+            } else {
+                Color::rgb(0.9, 0.9, 0.9)
+            };
         }
     }
 }
@@ -102,8 +240,13 @@ impl Plugin for TimeCyclePlugin {
         .init_resource::<TimeCycle>()
         .add_system_set(
             SystemSet::on_enter(GameState::Playing)
+            .with_system(reset_time_cycle.system())
             .with_system(set_up_ui.system())
         )
+        .add_system_set(
+            SystemSet::on_exit(GameState::Playing)
+            .with_system(remove_ui.system())
+        )
         .add_system_set(
             SystemSet::on_update(GameState::Playing)
             .with_system(time_cycle_system.system())
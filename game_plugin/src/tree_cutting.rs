@@ -3,19 +3,26 @@ use std::collections::VecDeque;
 use bevy::{app::Events, prelude::*};
 
 use crate::{
-    actions::Actions,
     behaviour::{CheckTaskEvent, Task, TravelToPosition, TravelToTarget},
-    creatures::Creature,
-    hexagon::Rectangle,
+    blueprints::SpawnRequest,
+    designations::{Constructing, ConstructionSite},
+    idle::Idling,
+    mood::Mood,
     physics::PhysicalObject,
-    world_gen::{gen_in_rect, SimParams},
+    plants::{Tree, WoodResource},
+    time_cycle::TimeCycle,
+    weather::{Weather, WeatherChangedEvent},
     GameState,
 };
 
+/// A villager swinging an axe at a tree, the tree falls when `progress` reaches 1
 pub struct CuttingTree {
     pub tree_id: Entity,
+    pub progress: f32,
 }
 
+const CUTTING_HOURS: f32 = 0.25;
+
 pub struct ResourceCarrier {
     pub wood: f32,
     pub max_wood: f32,
@@ -25,27 +32,50 @@ pub struct ResourceStorage {
     pub wood: f32,
 }
 
+/// Wood left on the ground, e.g. where a tree was cut
+pub struct LogPile {
+    pub wood: f32,
+}
+
+/// A task finished and taken off the task que, e.g. for the statistics
+pub struct TaskCompletedEvent {
+    pub creature_id: Entity,
+    pub task: Task,
+}
+
+// a fully grown tree gives as much wood as a villager picks up at once
+const WOOD_PER_TREE_SIZE: f32 = 5.0;
+
 pub struct TaskQuePlugin;
 
 impl Plugin for TaskQuePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(
+        app.add_event::<TaskCompletedEvent>().add_system_set(
             SystemSet::on_update(GameState::Playing)
-                .with_system(check_tasks.system())
-                .with_system(assign_intent.system()),
+                // log piles left by cut trees are spawned in the same frame, to be picked up in the next one
+                .with_system(check_tasks.system().before("spawn"))
+                .with_system(chop_trees.system())
+                .with_system(resume_tasks_on_weather_change.system()),
         );
     }
 }
 
+#[allow(clippy::too_many_arguments, clippy::type_complexity)]
 pub fn check_tasks(
     mut commands: Commands,
-    sim_params: Res<SimParams>,
     physical_object_query: Query<&PhysicalObject>,
-    physical_object_id_query: Query<Entity, With<PhysicalObject>>,
     mut resource_carrier_query: Query<&mut ResourceCarrier>,
     mut resource_storage_query: Query<(&mut ResourceStorage, Entity)>,
+    wood_resource_query: Query<&WoodResource>,
+    cutting_tree_query: Query<&CuttingTree>,
+    idling_query: Query<&Idling>,
+    mut log_pile_query: Query<(Entity, &PhysicalObject, &mut LogPile)>,
+    mut site_query: Query<&mut ConstructionSite>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
     mut tasks_queries: QuerySet<(Query<&mut TaskQue>, Query<Entity, Changed<TaskQue>>)>,
     mut task_events: ResMut<Events<CheckTaskEvent>>,
+    mut ev_task_completed: EventWriter<TaskCompletedEvent>,
+    weather: Res<Weather>,
 ) {
     let creature_ids: Vec<Entity> = tasks_queries
         .q1()
@@ -66,14 +96,23 @@ pub fn check_tasks(
 
                 if try_accomplish_task(
                     &mut commands,
-                    &sim_params.world_rect,
                     &physical_object_query,
-                    &physical_object_id_query,
                     &mut resource_carrier_query,
                     &mut resource_storage_query,
+                    &wood_resource_query,
+                    &cutting_tree_query,
+                    &idling_query,
+                    &mut log_pile_query,
+                    &mut site_query,
+                    &mut spawn_requests,
+                    weather.halts_outdoor_work(),
                     &creature_id,
-                    &task,
+                    task,
                 ) {
+                    ev_task_completed.send(TaskCompletedEvent {
+                        creature_id,
+                        task: task.clone(),
+                    });
                     tasks.pop_front();
                     println!("Task que is now {:?} for creature {:?}", tasks, creature_id);
 
@@ -84,12 +123,45 @@ pub fn check_tasks(
     }
 }
 
-pub fn assign_intent(
+fn chop_trees(
     mut commands: Commands,
-    creature_without_intent_query: Query<Entity, (With<Creature>, Without<Task>)>,
+    time_cycle: Res<TimeCycle>,
+    weather: Res<Weather>,
+    mut cutting_tree_query: Query<(Entity, &mut CuttingTree, Option<&Mood>)>,
+    tree_query: Query<(), With<Tree>>,
+    mut ev_check_task: EventWriter<CheckTaskEvent>,
+) {
+    if weather.halts_outdoor_work() {
+        return;
+    }
+
+    let hours = time_cycle.delta_hours();
+    for (worker_id, mut cutting, mood) in cutting_tree_query.iter_mut() {
+        if tree_query.get(cutting.tree_id).is_err() {
+            commands.entity(worker_id).remove::<CuttingTree>();
+            continue;
+        }
+        if cutting.progress >= 1.0 {
+            continue;
+        }
+
+        cutting.progress += hours / CUTTING_HOURS * mood.map_or(1.0, Mood::work_speed);
+        if cutting.progress >= 1.0 {
+            ev_check_task.send(CheckTaskEvent(worker_id));
+        }
+    }
+}
+
+// tasks halted by the weather are checked again once it changes
+fn resume_tasks_on_weather_change(
+    mut ev_weather_changed: EventReader<WeatherChangedEvent>,
+    task_que_query: Query<Entity, With<TaskQue>>,
+    mut ev_check_task: EventWriter<CheckTaskEvent>,
 ) {
-    for (creature_id) in creature_without_intent_query.iter() {
-        commands.entity(creature_id).insert(Task::WanderAimlessly);
+    if ev_weather_changed.iter().next().is_some() {
+        for creature_id in task_que_query.iter() {
+            ev_check_task.send(CheckTaskEvent(creature_id));
+        }
     }
 }
 
@@ -125,13 +197,19 @@ Villager: read BBS: deliver as many needed resources as can
     become idle
 */
 
+#[allow(clippy::too_many_arguments)]
 pub fn try_accomplish_task(
     commands: &mut Commands,
-    world_rect: &Rectangle,
     physical_object_query: &Query<&PhysicalObject>,
-    physical_object_id_query: &Query<Entity, With<PhysicalObject>>,
     resource_carrier_query: &mut Query<&mut ResourceCarrier>,
     resource_storage_query: &mut Query<(&mut ResourceStorage, Entity)>,
+    wood_resource_query: &Query<&WoodResource>,
+    cutting_tree_query: &Query<&CuttingTree>,
+    idling_query: &Query<&Idling>,
+    log_pile_query: &mut Query<(Entity, &PhysicalObject, &mut LogPile)>,
+    site_query: &mut Query<&mut ConstructionSite>,
+    spawn_requests: &mut EventWriter<SpawnRequest>,
+    outdoor_work_halted: bool,
     worker_id: &Entity,
     task: &Task,
 ) -> bool {
@@ -142,57 +220,199 @@ pub fn try_accomplish_task(
                 return false; // tree has been cut apparently
             }
 
-            if is_located_near(physical_object_query, worker_id, tree_id, 4.0) {
-                println!("At the tree. Ready to cut");
+            if outdoor_work_halted {
+                println!("Too stormy to cut trees, waiting for the weather to change");
+                return false;
+            }
 
-                // TODO:
-                // commands
-                //     .entity(*worker_id)
-                //     .insert(CuttingTree { tree_id: *tree_id });
-                // then add TreeCut(f32) on the tree, that reaches 1.0, meaning the tree should fall/be despawned. And a resource shoudl appear (a pile of wood)
+            if is_located_near(physical_object_query, worker_id, tree_id, 4.0) {
+                match cutting_tree_query.get(*worker_id) {
+                    Ok(cutting) if cutting.tree_id == *tree_id && cutting.progress >= 1.0 => {}
+                    // `chop_trees` checks the task again once the tree falls
+                    Ok(cutting) if cutting.tree_id == *tree_id => return false,
+                    _ => {
+                        println!("At the tree. Ready to cut");
+                        commands.entity(*worker_id).insert(CuttingTree {
+                            tree_id: *tree_id,
+                            progress: 0.0,
+                        });
+                        return false;
+                    }
+                }
 
-                // dummy cutting logic for now
+                let wood = wood_resource_query
+                    .get(*tree_id)
+                    .map_or(0.0, |wood_res| wood_res.0);
+                spawn_requests.send(SpawnRequest::LogPile {
+                    position: physical_object_query.get(*tree_id).unwrap().position,
+                    wood: wood * WOOD_PER_TREE_SIZE,
+                });
                 commands.entity(*tree_id).despawn_recursive();
-                return true;
+                commands.entity(*worker_id).remove::<CuttingTree>();
+                true
             } else {
                 println!("Travel to the tree");
 
                 commands.entity(*worker_id).insert(TravelToTarget {
-                    time_to_next_location_check: 0.0,
                     last_target_position: None,
                     target_id: *tree_id,
                 });
-                return false;
+                false
             }
         }
         Task::PickUpWood(amount) => {
-            // this is just a dummy, proper logic could be added later
-            pick_up_wood(worker_id, resource_carrier_query, *amount);
-            return true;
+            pick_up_wood(
+                commands,
+                worker_id,
+                physical_object_query,
+                resource_carrier_query,
+                log_pile_query,
+                *amount,
+            );
+            true
         }
         Task::DropOffResources => {
-            let (mut storage, storage_id) = resource_storage_query.single_mut().unwrap();
+            let carries_wood = resource_carrier_query
+                .get_mut(*worker_id)
+                .map_or(false, |carrier| carrier.wood > 0.0);
+            if !carries_wood {
+                return true;
+            }
+            let worker = physical_object_query.get(*worker_id).unwrap();
+            let nearest_storage = resource_storage_query
+                .iter_mut()
+                .filter_map(|(_, storage_id)| {
+                    let storage = physical_object_query.get(storage_id).ok()?;
+                    Some((storage_id, worker.gap_to(storage)))
+                })
+                .min_by(|(_, a), (_, b)| a.partial_cmp(b).unwrap());
+            let storage_id = match nearest_storage {
+                Some((storage_id, _)) => storage_id,
+                None => {
+                    println!("There is no storage to drop off wood at");
+                    return true;
+                }
+            };
+            let (mut storage, _) = resource_storage_query.get_mut(storage_id).unwrap();
             if is_located_near(physical_object_query, worker_id, &storage_id, 4.0) {
                 // what if storage could not contain more wood/resources
                 store_wood(worker_id, &mut storage, resource_carrier_query);
-                return true;
+                true
             } else {
                 commands.entity(*worker_id).insert(TravelToTarget {
-                    time_to_next_location_check: 0.0,
                     last_target_position: None,
                     target_id: storage_id,
                 });
+                false
+            }
+        }
+        Task::StrollTo(position) | Task::FleeTo(position) => {
+            let worker = physical_object_query.get(*worker_id).unwrap();
+            if worker.position.distance(*position) < 4.0 {
+                return true;
+            }
+            commands.entity(*worker_id).insert(TravelToPosition {
+                position: *position,
+            });
+            false
+        }
+        Task::Visit(target_id) => {
+            if physical_object_query.get(*target_id).is_err() {
+                return true; // nobody to visit any more
+            }
+            if is_located_near(physical_object_query, worker_id, target_id, 4.0) {
+                return true;
+            }
+            commands.entity(*worker_id).insert(TravelToTarget {
+                last_target_position: None,
+                target_id: *target_id,
+            });
+            false
+        }
+        Task::TakeWood(source_id, amount) => {
+            if physical_object_query.get(*source_id).is_err() {
+                return true; // somebody else took it all
+            }
+            if !is_located_near(physical_object_query, worker_id, source_id, 4.0) {
+                commands.entity(*worker_id).insert(TravelToTarget {
+                    last_target_position: None,
+                    target_id: *source_id,
+                });
+                return false;
+            }
+
+            let mut carrier = resource_carrier_query.get_mut(*worker_id).unwrap();
+            let wanted = amount.min(carrier.max_wood - carrier.wood);
+            if let Ok((_, _, mut log_pile)) = log_pile_query.get_mut(*source_id) {
+                let taken = wanted.min(log_pile.wood);
+                log_pile.wood -= taken;
+                carrier.wood += taken;
+                if log_pile.wood <= 0.0 {
+                    commands.entity(*source_id).despawn_recursive();
+                }
+            } else if let Ok((mut storage, _)) = resource_storage_query.get_mut(*source_id) {
+                let taken = wanted.min(storage.wood);
+                storage.wood -= taken;
+                carrier.wood += taken;
+            }
+            true
+        }
+        Task::Deliver(site_id) => {
+            if site_query.get_mut(*site_id).is_err() {
+                return true; // the blueprint was cancelled or is built already
+            }
+            if !is_located_near(physical_object_query, worker_id, site_id, 4.0) {
+                commands.entity(*worker_id).insert(TravelToTarget {
+                    last_target_position: None,
+                    target_id: *site_id,
+                });
                 return false;
             }
+
+            let mut site = site_query.get_mut(*site_id).unwrap();
+            let mut carrier = resource_carrier_query.get_mut(*worker_id).unwrap();
+            let delivered = carrier.wood.min(site.wood_missing());
+            site.wood_delivered += delivered;
+            carrier.wood -= delivered;
+            true
         }
-        Task::WanderAimlessly => {
-            let rng = &mut rand::thread_rng();
-            let position = gen_in_rect(rng, world_rect);
-            commands
-                .entity(*worker_id)
-                .insert(TravelToPosition { position });
-            return false;
+        Task::Build(site_id) => {
+            let is_ready = site_query
+                .get_mut(*site_id)
+                .map_or(false, |site| site.wood_missing() <= 0.0);
+            if !is_ready {
+                commands.entity(*worker_id).remove::<Constructing>();
+                return true;
+            }
+            if is_located_near(physical_object_query, worker_id, site_id, 4.0) {
+                // `construct` checks the task again once the building is finished
+                commands
+                    .entity(*worker_id)
+                    .insert(Constructing { site_id: *site_id });
+            } else {
+                commands.entity(*worker_id).insert(TravelToTarget {
+                    last_target_position: None,
+                    target_id: *site_id,
+                });
+            }
+            false
         }
+        // `defend_village` chases the enemy and `strike` fights it
+        Task::Attack(enemy_id) => physical_object_query.get(*enemy_id).is_err(),
+        Task::Idle(hours) => match idling_query.get(*worker_id) {
+            Ok(idling) if idling.hours_left <= 0.0 => {
+                commands.entity(*worker_id).remove::<Idling>();
+                true
+            }
+            // `wait_idle` checks the task again once the time is up
+            Ok(_) => false,
+            Err(_) => {
+                commands
+                    .entity(*worker_id)
+                    .insert(Idling { hours_left: *hours });
+                false
+            }
+        },
     }
 }
 
@@ -206,17 +426,36 @@ fn store_wood(
     (*carrier).wood = 0.0;
 }
 
+// takes wood from a log pile within reach, there is nothing to pick up if there is none
 fn pick_up_wood(
+    commands: &mut Commands,
     worker_id: &Entity,
+    physical_object_query: &Query<&PhysicalObject>,
     resource_carrier_query: &mut Query<&mut ResourceCarrier>,
+    log_pile_query: &mut Query<(Entity, &PhysicalObject, &mut LogPile)>,
     amount: f32,
 ) {
+    let worker = physical_object_query.get(*worker_id).unwrap();
     let mut carrier = resource_carrier_query.get_mut(*worker_id).unwrap();
-    let amount_can_carry = carrier.max_wood - carrier.wood;
-    carrier.wood += amount.min(amount_can_carry);
+    for (log_pile_id, log_pile_object, mut log_pile) in log_pile_query.iter_mut() {
+        if worker.gap_to(log_pile_object) >= 4.0 {
+            continue;
+        }
+
+        let picked = amount
+            .min(carrier.max_wood - carrier.wood)
+            .min(log_pile.wood);
+        carrier.wood += picked;
+        log_pile.wood -= picked;
+        if log_pile.wood <= 0.0 {
+            commands.entity(log_pile_id).despawn_recursive();
+        }
+        return;
+    }
+    println!("No log pile to pick up wood from");
 }
 
-fn is_located_near(
+pub fn is_located_near(
     query: &Query<&PhysicalObject>,
     who_id: &Entity,
     where_id: &Entity,
@@ -224,7 +463,8 @@ fn is_located_near(
 ) -> bool {
     let _who = query.get(*who_id).unwrap();
     let _where = query.get(*where_id).unwrap();
-    (*_who).position.distance((*_where).position) < within_distance
+    // measured between the borders, so that big buildings can be approached from any side
+    _who.gap_to(_where) < within_distance
 }
 
 pub struct TaskQue(pub VecDeque<Task>);
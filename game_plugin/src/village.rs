@@ -1,30 +1,27 @@
 use crate::{
-    creatures::Creature,
-    residence::{CreatureJoinedVillageEvent, CreatureLeftVillageEvent, Resident},
+    names::VillagerName,
+    physics::PhysicalObject,
+    residence::{CreatureJoinedVillageEvent, CreatureLeftVillageEvent, Resident, Villager},
+    time_cycle::{Season, TimeCycle},
+    tree_cutting::ResourceStorage,
     GameState,
 };
 use bevy::prelude::*;
 
-pub enum VillageTask {
-    Construction {
-        target: Option<Entity>,
-        building_type: PlannedBuildingType,
-        workers: Vec<Entity>,
-    },
-}
-
-pub enum PlannedBuildingType {
-    House,
-    Storage,
-}
+const FIREWOOD_PER_HOUSE_PER_DAY: f32 = 2.0;
 
 pub struct LivingSpace {
     pub max_people: u32,
     pub current_people: u32,
 }
 
+// Inhabited houses burn firewood from the storage in winter
+pub struct Heating {
+    pub is_heated: bool,
+}
+
+/// The wood of the village is in its `ResourceStorage`s
 pub struct Village {
-    pub wood: f32,
     pub habitants_count: u32,
     pub homeless_count: u32,
 }
@@ -43,6 +40,17 @@ pub struct Village {
 // ) {
 // }
 
+/// How eager villagers are to take up each job, 0 means the job is not done at all
+pub struct WorkPriorities {
+    pub woodcutting: u8,
+}
+
+impl Default for WorkPriorities {
+    fn default() -> Self {
+        Self { woodcutting: 1 }
+    }
+}
+
 pub struct LivingSpaceAvailableEvent {
     pub residence_id: Entity,
 }
@@ -51,20 +59,36 @@ pub struct VillagePlugin;
 
 impl Plugin for VillagePlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(
-            SystemSet::on_update(GameState::Playing)
-                .with_system(control_residence.system())
-                .with_system(house_homeless.system()),
-        )
-        .add_event::<LivingSpaceAvailableEvent>();
+        app.init_resource::<WorkPriorities>()
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(control_residence.system().label("residence"))
+                    .with_system(house_homeless.system())
+                    .with_system(consume_firewood.system()),
+            )
+            .add_event::<LivingSpaceAvailableEvent>();
     }
 }
 
 pub struct Building;
 
+/// Middle of the buildings of the village
+pub fn village_centre<'a>(buildings: impl Iterator<Item = &'a PhysicalObject>) -> Option<Vec2> {
+    let (sum, count) = buildings.fold((Vec2::ZERO, 0), |(sum, count), building| {
+        (sum + building.position, count + 1)
+    });
+    if count == 0 {
+        None
+    } else {
+        Some(sum / count as f32)
+    }
+}
+
 fn control_residence(
     mut village_query: Query<&mut Village>,
     resident_query: Query<&Resident>,
+    homeless_query: Query<Entity, (With<Villager>, Without<Resident>)>,
+    mut living_space_query: Query<&mut LivingSpace>,
     mut ev_residents_joined: EventReader<CreatureJoinedVillageEvent>,
     mut ev_residents_left: EventReader<CreatureLeftVillageEvent>,
     mut ev_living_space_available: EventWriter<LivingSpaceAvailableEvent>,
@@ -74,18 +98,24 @@ fn control_residence(
         .expect("So far there must be one village");
 
     for CreatureLeftVillageEvent(creature_id) in ev_residents_left.iter() {
-        village.habitants_count -= 1;
+        village.habitants_count = village.habitants_count.saturating_sub(1);
         if let Result::Ok(resident) = resident_query.get(*creature_id) {
-            ev_living_space_available.send(LivingSpaceAvailableEvent {
-                residence_id: resident.residence_id,
-            });
+            if let Ok(mut living_space) = living_space_query.get_mut(resident.residence_id) {
+                living_space.current_people = living_space.current_people.saturating_sub(1);
+                // a house made smaller by its definition takes nobody in until it has room again
+                if living_space.current_people < living_space.max_people {
+                    ev_living_space_available.send(LivingSpaceAvailableEvent {
+                        residence_id: resident.residence_id,
+                    });
+                }
+            }
         }
     }
 
     for _ in ev_residents_joined.iter() {
         village.habitants_count += 1;
-        village.homeless_count += 1;
     }
+    village.homeless_count = homeless_query.iter().count() as u32;
 
     // TODO: maybe settle each resident right away somehow?
 }
@@ -93,9 +123,9 @@ fn control_residence(
 fn house_homeless(
     mut commands: Commands,
     mut ev_living_space_available: EventReader<LivingSpaceAvailableEvent>,
-    homeless_query: Query<Entity, (With<Creature>, Without<Resident>)>,
+    homeless_query: Query<Entity, (With<Villager>, Without<Resident>)>,
     mut living_space_query: Query<&mut LivingSpace>,
-    creature_query: Query<&Creature>,
+    name_query: Query<&VillagerName>,
 ) {
     let mut living_space_events_iter = ev_living_space_available.iter();
 
@@ -105,10 +135,10 @@ fn house_homeless(
                 residence_id: *residence_id,
             });
 
-            let creature = creature_query.get(homeless_id);
+            let name = name_query.get(homeless_id);
             println!(
                 "Villager '{}' is assigned a residence",
-                creature.unwrap().name
+                name.unwrap().full()
             );
             if let Result::Ok(mut living_space) = living_space_query.get_mut(*residence_id) {
                 if living_space.current_people == living_space.max_people {
@@ -121,3 +151,34 @@ fn house_homeless(
         }
     }
 }
+
+fn consume_firewood(
+    time_cycle: Res<TimeCycle>,
+    mut house_query: Query<(&LivingSpace, &mut Heating)>,
+    mut storage_query: Query<&mut ResourceStorage>,
+) {
+    let is_winter = time_cycle.season() == Season::Winter;
+    let days = time_cycle.delta_hours() / 24.0;
+    let firewood_needed = FIREWOOD_PER_HOUSE_PER_DAY * days;
+
+    for (living_space, mut heating) in house_query.iter_mut() {
+        let is_heated = if !is_winter || living_space.current_people == 0 {
+            true
+        } else if let Some(mut storage) = storage_query
+            .iter_mut()
+            .find(|storage| storage.wood >= firewood_needed)
+        {
+            storage.wood -= firewood_needed;
+            true
+        } else {
+            false
+        };
+
+        if heating.is_heated != is_heated {
+            if !is_heated {
+                println!("A house has run out of firewood");
+            }
+            heating.is_heated = is_heated;
+        }
+    }
+}
@@ -0,0 +1,321 @@
+use bevy::prelude::*;
+use rand::{
+    distributions::WeightedIndex, prelude::Distribution, prelude::StdRng, Rng, SeedableRng,
+};
+
+use crate::{
+    camera::{get_view_rect, MainCamera},
+    layers::WEATHER_LAYER,
+    loading::Materials,
+    time_cycle::{Season, TimeCycle},
+    world_gen::{SimParams, SimRng},
+    GameState,
+};
+
+// it takes a game hour for a weather front to fully set in or to clear up
+const TRANSITION_HOURS: f32 = 1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum WeatherKind {
+    Clear,
+    Rain,
+    Storm,
+    Snow,
+}
+
+const WEATHER_KINDS: [WeatherKind; 4] = [
+    WeatherKind::Clear,
+    WeatherKind::Rain,
+    WeatherKind::Storm,
+    WeatherKind::Snow,
+];
+
+fn seasonal_weights(season: Season) -> [f32; 4] {
+    // clear, rain, storm, snow
+    match season {
+        Season::Spring => [0.55, 0.35, 0.1, 0.0],
+        Season::Summer => [0.65, 0.2, 0.15, 0.0],
+        Season::Autumn => [0.45, 0.4, 0.15, 0.0],
+        Season::Winter => [0.5, 0.05, 0.05, 0.4],
+    }
+}
+
+pub struct WeatherFront {
+    pub kind: WeatherKind,
+    /// Game hours until the front arrives
+    pub arrives_in: f32,
+    /// Game hours the weather lasts once the front has arrived
+    pub duration: f32,
+}
+
+pub struct Weather {
+    pub kind: WeatherKind,
+    /// 0.0 when the weather is just setting in or about to clear, 1.0 at its peak
+    pub intensity: f32,
+    pub elapsed: f32,
+    pub remaining: f32,
+    pub next_front: Option<WeatherFront>,
+}
+
+impl Default for Weather {
+    fn default() -> Self {
+        Self {
+            kind: WeatherKind::Clear,
+            intensity: 0.0,
+            elapsed: 0.0,
+            remaining: 0.0,
+            next_front: None,
+        }
+    }
+}
+
+impl Weather {
+    fn blend(&self, factor_at_peak: f32) -> f32 {
+        1.0 + (factor_at_peak - 1.0) * self.intensity
+    }
+
+    pub fn walking_speed_factor(&self) -> f32 {
+        self.blend(match self.kind {
+            WeatherKind::Clear => 1.0,
+            WeatherKind::Rain => 0.8,
+            WeatherKind::Storm => 0.5,
+            WeatherKind::Snow => 0.6,
+        })
+    }
+
+    pub fn growth_factor(&self) -> f32 {
+        self.blend(match self.kind {
+            WeatherKind::Clear => 1.0,
+            WeatherKind::Rain => 1.5,
+            WeatherKind::Storm => 1.2,
+            WeatherKind::Snow => 0.25,
+        })
+    }
+
+    pub fn seeding_factor(&self) -> f32 {
+        self.blend(match self.kind {
+            WeatherKind::Rain => 2.0,
+            _ => 1.0,
+        })
+    }
+
+    pub fn halts_outdoor_work(&self) -> bool {
+        self.kind == WeatherKind::Storm && self.intensity > 0.5
+    }
+
+    fn update(&mut self, hours: f32) -> Option<WeatherKind> {
+        let previous_kind = self.kind;
+
+        if self.kind != WeatherKind::Clear {
+            self.elapsed += hours;
+            self.remaining -= hours;
+            if self.remaining <= 0.0 {
+                self.kind = WeatherKind::Clear;
+            }
+        }
+
+        if let Some(front) = &mut self.next_front {
+            front.arrives_in -= hours;
+            if front.arrives_in <= 0.0 {
+                self.kind = front.kind;
+                self.elapsed = 0.0;
+                self.remaining = front.duration;
+                self.next_front = None;
+            }
+        }
+
+        self.intensity = if self.kind == WeatherKind::Clear {
+            0.0
+        } else {
+            (self.elapsed / TRANSITION_HOURS)
+                .min(self.remaining / TRANSITION_HOURS)
+                .clamp(0.0, 1.0)
+        };
+
+        if self.kind != previous_kind {
+            Some(previous_kind)
+        } else {
+            None
+        }
+    }
+}
+
+fn roll_front(season: Season, rng: &mut impl Rng) -> WeatherFront {
+    let weights = WeightedIndex::new(&seasonal_weights(season)).unwrap();
+    let kind = WEATHER_KINDS[weights.sample(rng)];
+    WeatherFront {
+        kind,
+        arrives_in: rng.gen_range(6.0..48.0),
+        duration: match kind {
+            WeatherKind::Storm => rng.gen_range(1.0..4.0),
+            _ => rng.gen_range(2.0..12.0),
+        },
+    }
+}
+
+pub struct WeatherChangedEvent {
+    pub from: WeatherKind,
+    pub to: WeatherKind,
+}
+
+pub struct WeatherParticle {
+    kind: WeatherKind,
+    velocity: Vec2,
+}
+
+pub struct WeatherPlugin;
+
+impl Plugin for WeatherPlugin {
+    fn build(&self, app: &mut AppBuilder) {
+        app.init_resource::<Weather>()
+            .add_event::<WeatherChangedEvent>()
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing).with_system(reset_weather.system()),
+            )
+            .add_system_set(
+                SystemSet::on_update(GameState::Playing)
+                    .with_system(simulate_weather.system())
+                    .with_system(spawn_weather_particles.system())
+                    .with_system(move_weather_particles.system()),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing)
+                    .with_system(remove_weather_particles.system()),
+            );
+    }
+}
+
+fn reset_weather(mut weather: ResMut<Weather>) {
+    *weather = Weather::default();
+}
+
+fn simulate_weather(
+    time_cycle: Res<TimeCycle>,
+    mut weather: ResMut<Weather>,
+    mut sim_rng: ResMut<SimRng>,
+    mut ev_weather_changed: EventWriter<WeatherChangedEvent>,
+) {
+    if weather.next_front.is_none() {
+        weather.next_front = Some(roll_front(time_cycle.season(), &mut sim_rng.0));
+    }
+
+    let hours = time_cycle.delta_hours();
+    if let Some(from) = weather.update(hours) {
+        println!("Weather changed from {:?} to {:?}", from, weather.kind);
+        ev_weather_changed.send(WeatherChangedEvent {
+            from,
+            to: weather.kind,
+        });
+    }
+}
+
+fn max_particles(kind: WeatherKind) -> usize {
+    match kind {
+        WeatherKind::Clear => 0,
+        WeatherKind::Rain => 300,
+        WeatherKind::Storm => 500,
+        WeatherKind::Snow => 200,
+    }
+}
+
+fn gen_particle_velocity(kind: WeatherKind, rng: &mut impl Rng) -> Vec2 {
+    match kind {
+        WeatherKind::Storm => {
+            Vec2::new(rng.gen_range(-220.0..-180.0), rng.gen_range(-650.0..-550.0))
+        }
+        WeatherKind::Snow => Vec2::new(rng.gen_range(-30.0..10.0), rng.gen_range(-70.0..-50.0)),
+        _ => Vec2::new(rng.gen_range(-40.0..-20.0), rng.gen_range(-420.0..-380.0)),
+    }
+}
+
+#[allow(clippy::too_many_arguments)]
+fn spawn_weather_particles(
+    mut commands: Commands,
+    weather: Res<Weather>,
+    materials: Res<Materials>,
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, With<MainCamera>>,
+    particle_query: Query<(Entity, &WeatherParticle)>,
+    sim_params: Res<SimParams>,
+    mut particle_rng: Local<Option<StdRng>>,
+) {
+    // the particles follow the view, so they draw from a stream apart from the simulation's
+    let rng = particle_rng.get_or_insert_with(|| StdRng::seed_from_u64(sim_params.seed));
+    let target_count = (max_particles(weather.kind) as f32 * weather.intensity) as usize;
+    let mut count = 0;
+
+    for (particle_id, particle) in particle_query.iter() {
+        if particle.kind != weather.kind || count >= target_count {
+            commands.entity(particle_id).despawn();
+        } else {
+            count += 1;
+        }
+    }
+
+    let (center, size) = match get_view_rect(&windows, camera_query.single().ok()) {
+        Some(view) => view,
+        None => return,
+    };
+
+    let (material, sprite_size) = match weather.kind {
+        WeatherKind::Snow => (materials.snowflake.clone(), Vec2::new(3.0, 3.0)),
+        _ => (materials.rain_drop.clone(), Vec2::new(1.0, 8.0)),
+    };
+
+    for _ in count..target_count {
+        let velocity = gen_particle_velocity(weather.kind, rng);
+        let position = center
+            + Vec2::new(
+                rng.gen_range(-size.x / 2.0..size.x / 2.0),
+                rng.gen_range(-size.y / 2.0..size.y / 2.0),
+            );
+        commands
+            .spawn_bundle(SpriteBundle {
+                material: material.clone(),
+                sprite: Sprite::new(sprite_size),
+                transform: Transform {
+                    translation: position.extend(WEATHER_LAYER),
+                    rotation: Quat::from_rotation_z(velocity.x.atan2(-velocity.y)),
+                    ..Default::default()
+                },
+                ..Default::default()
+            })
+            .insert(WeatherParticle {
+                kind: weather.kind,
+                velocity,
+            });
+    }
+}
+
+fn move_weather_particles(
+    time: Res<Time>,
+    windows: Res<Windows>,
+    camera_query: Query<&Transform, (With<MainCamera>, Without<WeatherParticle>)>,
+    mut particle_query: Query<(&WeatherParticle, &mut Transform)>,
+) {
+    let (center, size) = match get_view_rect(&windows, camera_query.single().ok()) {
+        Some(view) => view,
+        None => return,
+    };
+    let bottom_left = center - size / 2.0;
+
+    for (particle, mut transform) in particle_query.iter_mut() {
+        let position = transform.translation.truncate() + particle.velocity * time.delta_seconds();
+        // particles leaving the view come back from the opposite side
+        let wrapped = bottom_left
+            + Vec2::new(
+                (position.x - bottom_left.x).rem_euclid(size.x),
+                (position.y - bottom_left.y).rem_euclid(size.y),
+            );
+        transform.translation = wrapped.extend(WEATHER_LAYER);
+    }
+}
+
+fn remove_weather_particles(
+    mut commands: Commands,
+    particle_query: Query<Entity, With<WeatherParticle>>,
+) {
+    for particle in particle_query.iter() {
+        commands.entity(particle).despawn();
+    }
+}
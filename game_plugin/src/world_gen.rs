@@ -1,192 +1,171 @@
-use std::collections::VecDeque;
-use std::iter::FromIterator;
-
-use crate::behaviour::{CheckTaskEvent, Task};
-use crate::buildings::spawn_stockpile;
+use crate::blueprints::SpawnRequest;
+use crate::definitions::{BuildingDefinition, CreatureDefinition};
 use crate::hexagon::HexagonBuilder;
-use crate::plants::Tree;
-use crate::residence::CreatureJoinedVillageEvent;
-use crate::tree_cutting::TaskQue;
-use crate::village::LivingSpaceAvailableEvent;
-use crate::village::Village;
+use crate::save_game::{restore_world, LoadedGame};
+use crate::village::{Building, Village};
+use crate::{audio::Ambience, camera::MainCamera, loading::Definitions};
 use crate::{
-    audio::Ambience, buildings::spawn_house, layers::TILE_LAYER, loading::Materials,
-    plants::spawn_tree, residence::spawn_villager,
+    hexagon::Rectangle,
+    physics::{PhysicalObject, Placement},
+    GameState,
 };
-use crate::{hexagon::Rectangle, land_grid::LandTile, GameState};
 use bevy::prelude::*;
-use rand::{prelude::ThreadRng, Rng};
+use rand::{prelude::StdRng, Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
+// random spots tried before giving up on placing an object
+pub const PLACEMENT_ATTEMPTS: u32 = 50;
 
+#[derive(Clone, Serialize, Deserialize)]
 pub struct SimParams {
     pub start_pos: Vec2,
     pub hexagon_builder: HexagonBuilder,
     pub world_rect: Rectangle,
+    pub seed: u64,
+    pub villagers: u32,
+    pub houses: u32,
+    /// Trees per 100x100 area
+    pub tree_density: f32,
+    pub starting_wood: f32,
+}
+
+/// The random numbers of the simulation, the same seed draws the same numbers in the same order
+pub struct SimRng(pub StdRng);
+
+impl SimRng {
+    pub fn new(seed: u64) -> Self {
+        // another stream than the one the world is generated from
+        Self(StdRng::seed_from_u64(!seed))
+    }
+}
+
+impl SimParams {
+    pub fn trees_in_area(&self, size: Vec2) -> f32 {
+        size.x * size.y / 10_000.0 * self.tree_density
+    }
 }
 
 pub struct WorldGenPlugin;
 impl Plugin for WorldGenPlugin {
     fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(
-            SystemSet::on_enter(GameState::Playing).with_system(generate_world.system()),
-        );
-        // .add_system_set(
-        //     SystemSet::on_exit(GameState::Playing).with_system(remove_world.system()),
-        // );
+        app.insert_resource(SimRng::new(0))
+            .add_system_set(
+                SystemSet::on_enter(GameState::Playing)
+                    .with_system(reset_sim_rng.system().before("generate_world"))
+                    .with_system(generate_world.system().label("generate_world")),
+            )
+            .add_system_set(
+                SystemSet::on_exit(GameState::Playing).with_system(remove_world.system()),
+            );
     }
 }
 
-fn generate_world(
+fn reset_sim_rng(sim_params: Res<SimParams>, mut sim_rng: ResMut<SimRng>) {
+    *sim_rng = SimRng::new(sim_params.seed);
+}
+
+pub fn generate_world(
     mut commands: Commands,
     sim_params: Res<SimParams>,
-    materials: Res<Materials>,
-    mut ev_creature_joined_village: EventWriter<CreatureJoinedVillageEvent>,
-    mut ev_living_space_available: EventWriter<LivingSpaceAvailableEvent>,
-    mut ev_creature_available_for_tasks: EventWriter<CreatureAvailableForTasks>,
+    definitions: Res<Definitions>,
+    creature_definitions: Res<Assets<CreatureDefinition>>,
+    building_definitions: Res<Assets<BuildingDefinition>>,
+    mut spawn_requests: EventWriter<SpawnRequest>,
+    loaded_game: Option<Res<LoadedGame>>,
 ) {
     commands.spawn().insert(Ambience { is_forest: true });
 
-    let (world_columns, world_rows) = sim_params
-        .hexagon_builder
-        .get_world_columns_rows(sim_params.world_rect.size.x, sim_params.world_rect.size.y);
-
-    create_land_grid(
-        &mut commands,
-        &materials.tile,
-        &sim_params.hexagon_builder,
-        &sim_params.world_rect,
-        world_columns,
-        world_rows,
-    );
-
-    let rng = &mut rand::thread_rng();
-
-    commands.spawn_bundle(OrthographicCameraBundle::new_2d());
-
-    for _ in 0..36 {
-        let tree_pos = gen_in_rect(rng, &sim_params.world_rect);
-        spawn_tree(
-            tree_pos,
-            rng.gen_range(0.0..1.0),
-            &sim_params.world_rect,
-            &mut commands,
-            &materials.tree,
-            &materials.shadow,
-        );
+    let rng = &mut StdRng::seed_from_u64(sim_params.seed);
+
+    let mut camera = OrthographicCameraBundle::new_2d();
+    camera.transform.translation = sim_params.start_pos.extend(camera.transform.translation.z);
+    commands.spawn_bundle(camera).insert(MainCamera);
+
+    // the villagers join the village as they are spawned, whether they are generated or loaded
+    commands.spawn().insert(Village {
+        habitants_count: 0,
+        homeless_count: 0,
+    });
+
+    if let Some(loaded_game) = loaded_game {
+        restore_world(&loaded_game.0, &definitions, &mut spawn_requests);
+        commands.remove_resource::<LoadedGame>();
+        return;
     }
 
+    // trees are generated by the chunks around the camera and the villagers
+
+    // the more houses, the more room the village needs to start with
+    let village_side = 150.0 + 50.0 * (sim_params.houses as f32).sqrt();
     let village_start_rect = Rectangle {
         position: sim_params.start_pos,
-        size: Vec2::new(100., 100.),
+        size: Vec2::new(village_side, village_side),
     };
+    let mut placement = Placement::default();
+
+    let stockpile_definition = building_definitions.get(&definitions.stockpile).unwrap();
+    let stockpile_pos = placement
+        .find_free(
+            rng,
+            &village_start_rect,
+            stockpile_definition.footprint,
+            PLACEMENT_ATTEMPTS,
+        )
+        .unwrap_or(sim_params.start_pos);
+    spawn_requests.send(SpawnRequest::Stockpile {
+        position: stockpile_pos,
+        wood: sim_params.starting_wood,
+    });
 
-    for _ in 0..8 {
-        let resident_pos = gen_in_rect(rng, &village_start_rect);
-        let new_villager_id = spawn_villager(
-            &mut commands,
-            &materials,
-            resident_pos,
-            &sim_params,
-            &mut ev_creature_joined_village,
-        );
-
-        ev_creature_available_for_tasks.send(CreatureAvailableForTasks(new_villager_id))
+    let house_definition = building_definitions.get(&definitions.house).unwrap();
+    for _ in 0..sim_params.houses {
+        match placement.find_free(
+            rng,
+            &village_start_rect,
+            house_definition.footprint,
+            PLACEMENT_ATTEMPTS,
+        ) {
+            Some(house_pos) => spawn_requests.send(SpawnRequest::House {
+                position: house_pos,
+            }),
+            None => println!("No room left for another house in the village"),
+        }
     }
 
-    for _ in 0..2 {
-        let house_pos = gen_in_rect(rng, &village_start_rect);
-        spawn_house(
-            &mut commands,
-            &materials,
-            house_pos,
-            &sim_params,
-            2,
-            &mut ev_living_space_available,
-        );
+    let villager_definition = creature_definitions.get(&definitions.villager).unwrap();
+    for _ in 0..sim_params.villagers {
+        let resident_pos = placement
+            .find_free(
+                rng,
+                &village_start_rect,
+                villager_definition.footprint,
+                PLACEMENT_ATTEMPTS,
+            )
+            .unwrap_or_else(|| gen_in_rect(rng, &village_start_rect));
+        spawn_requests.send(SpawnRequest::Villager {
+            position: resident_pos,
+            name: None,
+        });
     }
+}
 
-    let stockpile_pos = gen_in_rect(rng, &village_start_rect);
-    spawn_stockpile(&mut commands, &materials, stockpile_pos, &sim_params);
-
-    commands.spawn().insert(Village {
-        habitants_count: 0,
-        homeless_count: 0,
-        wood: 0.0,
-    });
+type WorldEntityFilter = Or<(
+    With<PhysicalObject>,
+    With<Building>,
+    With<Village>,
+    With<Ambience>,
+    With<MainCamera>,
+)>;
+
+fn remove_world(mut commands: Commands, world_query: Query<Entity, WorldEntityFilter>) {
+    for entity in world_query.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
 }
 
-pub fn gen_in_rect(rng: &mut ThreadRng, rect: &Rectangle) -> Vec2 {
+pub fn gen_in_rect<R: Rng>(rng: &mut R, rect: &Rectangle) -> Vec2 {
     Vec2::new(
         rng.gen_range(rect.position.x - rect.size.x / 2.0..rect.position.x + rect.size.x / 2.0),
         rng.gen_range(rect.position.y - rect.size.y / 2.0..rect.position.y + rect.size.y / 2.0),
     )
 }
-
-fn create_land_grid(
-    commands: &mut Commands,
-    tile_material: &Handle<ColorMaterial>,
-    hexagon_builder: &HexagonBuilder,
-    world_rect: &Rectangle,
-    world_columns: i32,
-    world_rows: i32,
-) {
-    let origin = world_rect.size / 2.0;
-
-    (0..world_columns * world_rows)
-        .map(|i| LandTile {
-            column: i.rem_euclid(world_columns),
-            row: i / world_columns,
-        })
-        .for_each(|tile| {
-            let hexagon = hexagon_builder.get_hexagon_at(origin, tile.column, tile.row);
-            let rect = hexagon.get_bounding_rectangle();
-            commands
-                .spawn_bundle(SpriteBundle {
-                    material: tile_material.clone(),
-                    transform: Transform::from_translation((rect.position).extend(TILE_LAYER)),
-                    sprite: Sprite::new(rect.size),
-                    ..Default::default()
-                })
-                .insert(tile);
-        })
-}
-
-pub struct ExperimentalPlugin;
-impl Plugin for ExperimentalPlugin {
-    fn build(&self, app: &mut AppBuilder) {
-        app.add_system_set(SystemSet::on_update(GameState::Playing).with_system(cut_tree.system()))
-            .add_event::<CreatureAvailableForTasks>();
-
-        // .add_system_set(
-        //     SystemSet::on_exit(GameState::Playing).with_system(remove_world.system()),
-        // );
-    }
-}
-
-pub struct CreatureAvailableForTasks(pub Entity);
-
-fn cut_tree(
-    mut villager_tasks_query: Query<&mut TaskQue>,
-    tree_query: Query<Entity, With<Tree>>,
-    mut ev_creature_available_for_tasks: EventReader<CreatureAvailableForTasks>,
-    mut ev_check_task_event: EventWriter<CheckTaskEvent>,
-) {
-    let mut villager_iter = ev_creature_available_for_tasks.iter();
-    if let Some(CreatureAvailableForTasks(villager_id)) = villager_iter.next() {
-        println!("cut_tree has creatures available for tasks");
-
-        if let Some(tree_id) = tree_query.iter().next() {
-            let task_que = &mut villager_tasks_query.get_mut(*villager_id).unwrap().0;
-            println!("ready to put a task in a task queue");
-
-            task_que.clear();
-            task_que.push_back(Task::CutTree(tree_id));
-            task_que.push_back(Task::PickUpWood(5.0));
-            task_que.push_back(Task::DropOffResources);
-        }
-
-        // for villager_id in villager_iter {
-        //     let task_que = &mut villager_tasks_query.get_mut(*villager_id).unwrap().0;
-        //     task_que.push_back(Task::WanderAimlessly);
-        // }
-    }
-}
@@ -0,0 +1,32 @@
+//! Runs every scenario in `tests/scenarios`, see `game_plugin::scenarios` for what they can contain
+
+use std::{fs, path::Path};
+
+use game_plugin::scenarios::{load_scenario, run_scenario};
+
+const SCENARIO_SUFFIX: &str = ".scenario.ron";
+
+#[test]
+fn scenarios_meet_their_expectations() {
+    let scenario_dir = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/scenarios");
+    let mut paths: Vec<_> = fs::read_dir(scenario_dir)
+        .unwrap()
+        .map(|entry| entry.unwrap().path())
+        .filter(|path| path.to_string_lossy().ends_with(SCENARIO_SUFFIX))
+        .collect();
+    paths.sort();
+    assert!(!paths.is_empty());
+
+    let failures: Vec<String> = paths
+        .iter()
+        .filter_map(|path| {
+            let name = path.file_name().unwrap().to_string_lossy();
+            let scenario = load_scenario(path)
+                .unwrap_or_else(|error| panic!("{} could not be read: {}", name, error));
+            run_scenario(&scenario)
+                .err()
+                .map(|failure| format!("{}: {}", name, failure))
+        })
+        .collect();
+    assert!(failures.is_empty(), "\n{}", failures.join("\n"));
+}